@@ -1,6 +1,6 @@
 use super::{
-    helpers, Color, DrawType, Fen, GameOverError, GameResult, IllegalMoveError, InvalidSanMoveError, InvalidSquareNameError, InvalidUciMoveError, Move, NoMovesPlayedError, Piece, PieceType, Position,
-    WinType,
+    helpers, Color, DrawType, Fen, GameOverError, GameResult, IllegalMoveError, InvalidDescriptiveMoveError, InvalidIccfMoveError, InvalidLanMoveError, InvalidSanMoveError, InvalidSquareNameError,
+    InvalidUciMoveError, Move, MoveNotation, NoMovesPlayedError, NotationLocale, Piece, PieceType, Position, WinType,
 };
 use std::fmt;
 
@@ -60,18 +60,70 @@ impl Board {
 
     /// Represents a `Move` in SAN, returning an error if the move is illegal.
     pub fn move_to_san(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        self.move_to_san_in(move_, &NotationLocale::ENGLISH)
+    }
+
+    /// Represents a `Move` in SAN using the piece letters of the given locale, returning an error if the move is illegal.
+    pub fn move_to_san_in(&self, move_: Move, locale: &NotationLocale) -> Result<String, IllegalMoveError> {
         let move_ = helpers::as_legal(move_, &self.gen_legal_moves()).ok_or(IllegalMoveError(move_))?;
-        self.position.move_to_san(move_)
+        self.position.move_to_san_in(move_, locale)
     }
 
-    /// Constructs a `Move` from a SAN representation, returning an error if it is invalid or illegal.
+    /// Constructs a `Move` from a SAN representation, returning an error if it cannot be parsed, or is illegal or ambiguous.
+    /// See [`Position::san_to_move`] for the deviations from strict SAN that are accepted.
     pub fn san_to_move(&self, san: &str) -> Result<Move, InvalidSanMoveError> {
-        match self.position.san_to_move(san) {
+        self.san_to_move_in(san, &NotationLocale::ENGLISH)
+    }
+
+    /// Constructs a `Move` from a SAN representation that uses the piece letters of the given locale,
+    /// returning an error if it cannot be parsed, or is illegal or ambiguous.
+    pub fn san_to_move_in(&self, san: &str, locale: &NotationLocale) -> Result<Move, InvalidSanMoveError> {
+        match self.position.san_to_move_in(san, locale) {
             Ok(m) => {
                 if self.is_legal(m) {
                     Ok(m)
                 } else {
-                    Err(InvalidSanMoveError(san.to_owned()))
+                    Err(InvalidSanMoveError::Illegal(san.to_owned()))
+                }
+            }
+            e => e,
+        }
+    }
+
+    /// Represents a `Move` in long algebraic notation, returning an error if the move is illegal.
+    pub fn move_to_lan(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        let move_ = helpers::as_legal(move_, &self.gen_legal_moves()).ok_or(IllegalMoveError(move_))?;
+        self.position.move_to_lan(move_)
+    }
+
+    /// Constructs a `Move` from a long algebraic notation representation, returning an error if it is invalid, illegal or ambiguous.
+    pub fn lan_to_move(&self, lan: &str) -> Result<Move, InvalidLanMoveError> {
+        match self.position.lan_to_move(lan) {
+            Ok(m) => {
+                if self.is_legal(m) {
+                    Ok(m)
+                } else {
+                    Err(InvalidLanMoveError::Illegal(lan.to_owned()))
+                }
+            }
+            e => e,
+        }
+    }
+
+    /// Represents a `Move` in English descriptive notation, returning an error if the move is illegal.
+    pub fn move_to_descriptive(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        let move_ = helpers::as_legal(move_, &self.gen_legal_moves()).ok_or(IllegalMoveError(move_))?;
+        self.position.move_to_descriptive(move_)
+    }
+
+    /// Constructs a `Move` from English descriptive notation, returning an error if it cannot be parsed, or is illegal or ambiguous.
+    pub fn descriptive_to_move(&self, descriptive: &str) -> Result<Move, InvalidDescriptiveMoveError> {
+        match self.position.descriptive_to_move(descriptive) {
+            Ok(m) => {
+                if self.is_legal(m) {
+                    Ok(m)
+                } else {
+                    Err(InvalidDescriptiveMoveError::Illegal(descriptive.to_owned()))
                 }
             }
             e => e,
@@ -106,6 +158,27 @@ impl Board {
             Some(m) => m,
             _ => return Err(IllegalMoveError(move_)),
         };
+        self.push_move(move_);
+        self.update_status();
+        Ok(())
+    }
+
+    /// Plays a move that is legal in the position even if the game has ended by fivefold repetition, the seventy-five-move rule
+    /// or insufficient material, keeping the game ongoing unless the position after the move has no legal moves.
+    /// This is how a move list sent by a GUI is replayed, since the GUI decides when its game is over.
+    #[cfg(feature = "uci")]
+    pub(crate) fn replay_move(&mut self, move_: Move) -> Result<(), IllegalMoveError> {
+        let move_ = match helpers::as_legal(move_, &self.position.gen_non_illegal_moves()) {
+            Some(m) => m,
+            _ => return Err(IllegalMoveError(move_)),
+        };
+        self.push_move(move_);
+        self.ongoing = !self.position.gen_non_illegal_moves().is_empty();
+        Ok(())
+    }
+
+    /// Plays a legal move on the board, updating the clocks and the history but not the status of the game.
+    fn push_move(&mut self, move_: Move) {
         let mut halfmove_clock = self.halfmove_clock;
         let fullmove_number = self.fullmove_number + if self.position.side.is_black() { 1 } else { 0 };
         let Move(move_src, move_dest, ..) = move_;
@@ -120,8 +193,6 @@ impl Board {
         self.move_history.push(move_);
         self.halfmove_clock_history.push(self.halfmove_clock);
         (self.halfmove_clock, self.fullmove_number) = (halfmove_clock, fullmove_number);
-        self.update_status();
-        Ok(())
     }
 
     /// Attempts to parse the UCI representation of a move and play it on the board, returning an error if the move is invalid or illegal.
@@ -130,10 +201,16 @@ impl Board {
         self.make_move(move_).map_err(|_| InvalidUciMoveError::IllegalMove(uci.to_owned()))
     }
 
+    /// Attempts to parse the ICCF numeric representation of a move and play it on the board, returning an error if the move is invalid or illegal.
+    pub fn make_move_iccf(&mut self, iccf: &str) -> Result<(), InvalidIccfMoveError> {
+        let move_ = Move::from_iccf(iccf).map_err(|_| InvalidIccfMoveError::InvalidIccf(iccf.to_owned()))?;
+        self.make_move(move_).map_err(|_| InvalidIccfMoveError::IllegalMove(iccf.to_owned()))
+    }
+
     /// Attempts to interpret the SAN representation of a move and play it on the board, returning an error if it is invalid or illegal.
     pub fn make_move_san(&mut self, san: &str) -> Result<(), InvalidSanMoveError> {
         let move_ = self.san_to_move(san)?;
-        self.make_move(move_).map_err(|_| InvalidSanMoveError(san.to_owned()))
+        self.make_move(move_).map_err(|_| InvalidSanMoveError::Illegal(san.to_owned()))
     }
 
     /// Attempts to play the given line of UCI moves (separated by spaces, **excluding move numbers**) on the board,
@@ -351,6 +428,16 @@ impl Board {
 
     /// Generates the SAN movetext of the game thus far (excluding the game result).
     pub fn gen_movetext(&self) -> String {
+        self.gen_movetext_in(MoveNotation::San)
+    }
+
+    /// Generates the movetext of the game thus far in the given notation (excluding the game result).
+    pub fn gen_movetext_in(&self, notation: MoveNotation) -> String {
+        self.gen_movetext_localized(notation, &NotationLocale::ENGLISH)
+    }
+
+    /// Generates the movetext of the game thus far in the given notation, using the piece letters of the given locale (excluding the game result).
+    pub fn gen_movetext_localized(&self, notation: MoveNotation, locale: &NotationLocale) -> String {
         let mut movetext = String::new();
         let initial_side = self.initial_fen.position().side;
         let initial_fullmove_number: usize = self.initial_fen.fullmove_number();
@@ -358,7 +445,13 @@ impl Board {
         let mut current_fullmove_number = initial_fullmove_number;
         for (movei, &move_) in self.move_history.iter().enumerate() {
             let pos = &self.position_history[movei];
-            let san = pos.move_to_san(move_).unwrap();
+            let san = match notation {
+                MoveNotation::San => pos.move_to_san_in(move_, locale),
+                MoveNotation::Lan => pos.move_to_lan_in(move_, locale),
+                MoveNotation::Descriptive => pos.move_to_descriptive(move_),
+                MoveNotation::Iccf => Ok(move_.to_iccf()),
+            }
+            .unwrap();
             if current_side.is_black() {
                 movetext.push_str(&format!("{}{san} ", if movei == 0 { format!("{current_fullmove_number}... ") } else { String::new() }));
                 current_fullmove_number += 1;
@@ -370,6 +463,17 @@ impl Board {
         movetext.trim().to_owned()
     }
 
+    /// Returns the moves that have been played on the board, in order.
+    pub fn move_history(&self) -> &[Move] {
+        &self.move_history
+    }
+
+    /// Returns the positions that have occurred on the board before the current one, in order.
+    #[cfg(feature = "engine")]
+    pub(crate) fn position_history(&self) -> &[Position] {
+        &self.position_history
+    }
+
     /// Returns the current `Position` on the board.
     pub fn position(&self) -> &Position {
         &self.position
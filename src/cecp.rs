@@ -0,0 +1,662 @@
+//! Handles communication with chess engines over the Chess Engine Communication Protocol (CECP, the XBoard/WinBoard protocol), both as a GUI and as an engine.
+//! Engines implementing [`uci::Engine`] can be run over CECP as well as UCI.
+
+use super::{
+    helpers,
+    uci::{self, CommandEffect, Engine, EngineOption, OptionType, Protocol, SearchControl, SearchInfo, SearchLimits},
+    Board, CecpError, Color, DrawType, Fen, GameResult, Move, Position, Score, WinType,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsStr,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+/// The commands which do not interrupt the search of an engine, but are handled after it
+const NON_INTERRUPTING_COMMANDS: [&str; 12] = ["ping", "time", "otim", "post", "nopost", "hard", "easy", "computer", "name", "rating", "accepted", "rejected"];
+/// The features which are accepted by an `EngineClient`; all others are rejected
+const ACCEPTED_FEATURES: [&str; 15] = [
+    "done",
+    "myname",
+    "setboard",
+    "usermove",
+    "ping",
+    "san",
+    "sigint",
+    "sigterm",
+    "time",
+    "colors",
+    "playother",
+    "reuse",
+    "draw",
+    "option",
+    "debug",
+];
+/// The time an engine has to start sending features after `protover`, after which it is assumed to only speak version 1 of the protocol
+const FEATURE_TIMEOUT: Duration = Duration::from_secs(2);
+/// The magnitude of the scores with which engines conventionally report forced mates, from which the number of moves to mate is subtracted
+const MATE_SCORE: i32 = 100000;
+
+/// Represents a conventional time control, as set with the CECP `level` command.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct TimeControl {
+    /// The number of moves to be played in each period of `base` time (0 if `base` is for the whole game)
+    pub moves_per_session: u32,
+    /// The time given for each session
+    pub base: Duration,
+    /// The time added after each move
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Parses the arguments of a `level` command, in which the base time is given in minutes (`5`) or minutes and seconds (`0:30`),
+    /// and the increment in seconds.
+    fn parse(args: &str) -> Option<Self> {
+        let [mps, base, inc] = args.split_whitespace().collect::<Vec<_>>()[..] else { return None };
+        let base = match base.split_once(':') {
+            Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?,
+            _ => base.parse::<u64>().ok()? * 60,
+        };
+        Some(Self {
+            moves_per_session: mps.parse().ok()?,
+            base: Duration::from_secs(base),
+            increment: Duration::try_from_secs_f64(inc.parse().ok()?).ok()?,
+        })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    /// Represents the time control as a CECP `level` command.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (minutes, seconds) = (self.base.as_secs() / 60, self.base.as_secs() % 60);
+        write!(f, "level {} {minutes}", self.moves_per_session)?;
+        if seconds != 0 {
+            write!(f, ":{seconds:02}")?;
+        }
+        write!(f, " {}", self.increment.as_secs_f64())
+    }
+}
+
+/// Represents the reply of an engine to `go`.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+pub struct EngineReply {
+    /// The move played by the engine (`None` if it resigned or claimed a result instead)
+    pub move_: Option<Move>,
+    /// The thinking output sent by the engine before it replied
+    pub info: Vec<SearchInfo>,
+    /// The result claimed by the engine (e.g. `1-0 {White mates}`), or `resign` if it resigned
+    pub claim: Option<String>,
+    /// Whether the engine offered a draw
+    pub draw_offered: bool,
+}
+
+/// Drives an external chess engine which speaks CECP, running as a child process. The engine is kept in force mode
+/// except while it thinks, so that it only moves when asked to with [`EngineClient::go`].
+#[derive(Debug)]
+pub struct EngineClient {
+    process: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// The features sent by the engine, which were accepted
+    features: HashMap<String, String>,
+    /// The game as known to the engine
+    board: Board,
+    /// The number of the next `ping`
+    pings: usize,
+}
+
+impl EngineClient {
+    /// Starts the engine at the given path and performs the CECP handshake.
+    pub fn spawn<S: AsRef<OsStr>>(program: S) -> Result<Self, CecpError> {
+        Self::from_command(Command::new(program))
+    }
+
+    /// Starts the engine with the given command and performs the CECP handshake, negotiating features with protocol version 2.
+    /// The standard input and output of the command are replaced with pipes.
+    pub fn from_command(mut command: Command) -> Result<Self, CecpError> {
+        let mut process = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = process.stdin.take().unwrap();
+        let lines = uci::spawn_reader(BufReader::new(process.stdout.take().unwrap()));
+        let mut client = Self {
+            process,
+            stdin,
+            lines,
+            features: HashMap::new(),
+            board: Board::default(),
+            pings: 0,
+        };
+        client.send("xboard")?;
+        client.send("protover 2")?;
+        let mut timeout = Some(FEATURE_TIMEOUT);
+        loop {
+            let line = match timeout {
+                Some(timeout) => match client.lines.recv_timeout(timeout) {
+                    Ok(line) => line,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(CecpError::Disconnected),
+                },
+                _ => client.read_line()?,
+            };
+            let Some(features) = line.strip_prefix("feature ") else { continue };
+            for (name, value) in parse_features(features) {
+                if !ACCEPTED_FEATURES.contains(&name.as_str()) {
+                    client.send(&format!("rejected {name}"))?;
+                    continue;
+                }
+                client.send(&format!("accepted {name}"))?;
+                if name == "done" {
+                    timeout = if value == "0" { None } else { Some(Duration::ZERO) };
+                } else if name == "option" {
+                    client.features.entry(name).and_modify(|v| *v = format!("{v}\n{value}")).or_insert(value);
+                } else {
+                    client.features.insert(name, value);
+                }
+            }
+            if timeout == Some(Duration::ZERO) {
+                break;
+            }
+        }
+        client.new_game()?;
+        Ok(client)
+    }
+
+    /// Returns the value of a feature sent by the engine (`None` if it was not sent or was rejected).
+    pub fn feature(&self, name: &str) -> Option<&str> {
+        self.features.get(name).map(String::as_str)
+    }
+
+    /// Returns the name of the engine, if it sent one.
+    pub fn name(&self) -> Option<&str> {
+        self.feature("myname")
+    }
+
+    /// Returns the options declared by the engine.
+    pub fn options(&self) -> Vec<EngineOption> {
+        self.feature("option").map_or(Vec::new(), |options| options.lines().filter_map(parse_option).collect())
+    }
+
+    /// Returns the game as known to the engine.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Sets the value of an option (`None` for buttons).
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), CecpError> {
+        match value {
+            Some(value) => self.send(&format!("option {name}={value}")),
+            _ => self.send(&format!("option {name}")),
+        }
+    }
+
+    /// Starts a new game from the standard starting position, with thinking output enabled.
+    pub fn new_game(&mut self) -> Result<(), CecpError> {
+        self.send("new")?;
+        self.send("force")?;
+        self.send("post")?;
+        self.board = Board::default();
+        Ok(())
+    }
+
+    /// Sets up the game of the board, as its initial position followed by the moves played. A game which does not start
+    /// from the standard position requires the `setboard` feature.
+    pub fn set_position(&mut self, board: &Board) -> Result<(), CecpError> {
+        let standard = board.initial_fen() == Board::default().initial_fen();
+        if !standard && self.feature("setboard") != Some("1") {
+            return Err(CecpError::Unsupported("setboard".to_owned()));
+        }
+        self.new_game()?;
+        if !standard {
+            self.send(&format!("setboard {}", board.initial_fen()))?;
+            self.board = Board::from_fen(board.initial_fen().clone());
+        }
+        for &move_ in board.move_history() {
+            self.user_move(move_)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a move played by the user, which must be legal in the game. If the engine supports the `ping` feature, waits for it to
+    /// process the move and returns an error if it rejects it; otherwise a rejection is only reported by the next [`EngineClient::go`].
+    pub fn user_move(&mut self, move_: Move) -> Result<(), CecpError> {
+        let text = if self.feature("san") == Some("1") {
+            self.board.move_to_san(move_).map_err(|_| CecpError::InvalidMove(move_.to_uci()))?
+        } else if self.board.is_legal(move_) {
+            move_.to_uci()
+        } else {
+            return Err(CecpError::InvalidMove(move_.to_uci()));
+        };
+        match self.feature("usermove") {
+            Some("1") => self.send(&format!("usermove {text}"))?,
+            _ => self.send(&text)?,
+        }
+        if self.feature("ping") == Some("1") {
+            if let Some(rejection) = self.sync()? {
+                return Err(CecpError::Engine(rejection));
+            }
+        }
+        self.board.make_move(move_).unwrap();
+        Ok(())
+    }
+
+    /// Asks the engine to move in the current position within the given limits, and waits for its reply, which is played in the game.
+    /// The depth and the time per move are sent with `sd` and `st`, and the clocks with `level`, `time` and `otim`; node limits are not supported.
+    /// CECP `st` only takes whole seconds, so the time per move is rounded to the nearest second, with a minimum of one.
+    pub fn go(&mut self, limits: &SearchLimits) -> Result<EngineReply, CecpError> {
+        self.go_with(limits, |_| true)
+    }
+
+    /// Asks the engine to move within the given limits, calling `on_info` with each line of thinking output as it arrives.
+    /// When `on_info` returns `false`, the engine is asked to move immediately.
+    pub fn go_with<F: FnMut(&SearchInfo) -> bool>(&mut self, limits: &SearchLimits, mut on_info: F) -> Result<EngineReply, CecpError> {
+        let (own, other, increment) = match self.board.side_to_move() {
+            Color::White => (limits.wtime, limits.btime, limits.winc),
+            Color::Black => (limits.btime, limits.wtime, limits.binc),
+        };
+        if let Some(own) = own {
+            let level = TimeControl {
+                moves_per_session: limits.movestogo.unwrap_or(0),
+                base: own,
+                increment: increment.unwrap_or_default(),
+            };
+            self.send(&level.to_string())?;
+            self.send(&format!("time {}", own.as_millis() / 10))?;
+            self.send(&format!("otim {}", other.unwrap_or(own).as_millis() / 10))?;
+        }
+        if let Some(depth) = limits.depth {
+            self.send(&format!("sd {depth}"))?;
+        }
+        if let Some(movetime) = limits.movetime {
+            self.send(&format!("st {}", ((movetime.as_millis() + 500) / 1000).max(1)))?;
+        }
+        self.send("go")?;
+        let mut reply = EngineReply::default();
+        let mut moving = false;
+        loop {
+            let line = self.read_line()?;
+            let (command, args) = line.split_once(' ').unwrap_or((&line, ""));
+            match command {
+                "move" => {
+                    let move_ = parse_move(args.trim(), &self.board).ok_or_else(|| CecpError::InvalidMove(args.trim().to_owned()))?;
+                    self.board.make_move(move_).unwrap();
+                    reply.move_ = Some(move_);
+                    break;
+                }
+                "resign" | "1-0" | "0-1" | "1/2-1/2" => {
+                    reply.claim = Some(line.clone());
+                    break;
+                }
+                "offer" if args.trim() == "draw" => reply.draw_offered = true,
+                "Illegal" | "Error" | "tellusererror" => return Err(CecpError::Engine(line.clone())),
+                _ => {
+                    if let Some(info) = parse_thinking_output(&line, self.board.position()) {
+                        if !on_info(&info) && !moving {
+                            self.send("?")?;
+                            moving = true;
+                        }
+                        reply.info.push(info);
+                    }
+                }
+            }
+        }
+        self.send("force")?;
+        Ok(reply)
+    }
+
+    /// Tells the engine the result of the game, along with a comment explaining it (e.g. `White mates`).
+    pub fn result(&mut self, result: GameResult, comment: &str) -> Result<(), CecpError> {
+        self.send(&format!("result {result} {{{comment}}}"))
+    }
+
+    /// Sends `ping` and waits for the engine to reply with the matching `pong`, which requires the `ping` feature.
+    pub fn ping(&mut self) -> Result<(), CecpError> {
+        if self.feature("ping") != Some("1") {
+            return Err(CecpError::Unsupported("ping".to_owned()));
+        }
+        self.sync().map(|_| ())
+    }
+
+    /// Sends `ping` and waits for the matching `pong`, returning the first error the engine reported in the meantime, if any.
+    fn sync(&mut self) -> Result<Option<String>, CecpError> {
+        self.pings += 1;
+        self.send(&format!("ping {}", self.pings))?;
+        let pong = format!("pong {}", self.pings);
+        let mut rejection = None;
+        loop {
+            let line = self.read_line()?;
+            if line == pong {
+                return Ok(rejection);
+            }
+            if rejection.is_none() && matches!(line.split_once(' ').map_or(line.as_str(), |(command, _)| command), "Illegal" | "Error") {
+                rejection = Some(line);
+            }
+        }
+    }
+
+    /// Asks the engine to quit and waits for its process to exit.
+    pub fn quit(mut self) -> Result<(), CecpError> {
+        self.send("quit")?;
+        self.process.wait()?;
+        Ok(())
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), CecpError> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, CecpError> {
+        self.lines.recv().map_err(|_| CecpError::Disconnected)
+    }
+}
+
+impl Drop for EngineClient {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        uci::reap(&mut self.process);
+    }
+}
+
+/// Parses the arguments of a `feature` command into pairs of names and values, with the quotes of string values removed.
+fn parse_features(text: &str) -> Vec<(String, String)> {
+    let mut features = Vec::new();
+    let mut rest = text.trim();
+    while let Some((name, after)) = rest.split_once('=') {
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            _ => after.split_once(' ').unwrap_or((after, "")),
+        };
+        features.push((name.trim().to_owned(), value.to_owned()));
+        rest = after.trim_start();
+    }
+    features
+}
+
+/// Parses an option declared with the `option` feature (e.g. `Hash -spin 16 1 1024`).
+fn parse_option(text: &str) -> Option<EngineOption> {
+    let (name, rest) = text.split_once(" -")?;
+    let (kind, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    let numbers = args.split_whitespace().map(str::parse).collect::<Result<Vec<i64>, _>>();
+    let option_type = match kind {
+        "check" => OptionType::Check { default: args.trim() == "1" },
+        "spin" | "slider" => match numbers.ok()?[..] {
+            [default, min, max] => OptionType::Spin { default, min, max },
+            _ => return None,
+        },
+        "combo" => {
+            let choices: Vec<_> = args.split("///").map(str::trim).collect();
+            OptionType::Combo {
+                default: choices.iter().find_map(|c| c.strip_prefix('*')).unwrap_or(choices[0]).to_owned(),
+                choices: choices.iter().map(|c| c.trim_start_matches('*').to_owned()).collect(),
+            }
+        }
+        "button" | "save" | "reset" => OptionType::Button,
+        "string" | "file" | "path" => OptionType::String { default: args.trim().to_owned() },
+        _ => return None,
+    };
+    Some(EngineOption {
+        name: name.trim().to_owned(),
+        option_type,
+    })
+}
+
+/// Writes an option as the value of an `option` feature.
+fn format_option(option: &EngineOption) -> String {
+    match &option.option_type {
+        OptionType::Check { default } => format!("{} -check {}", option.name, u8::from(*default)),
+        OptionType::Spin { default, min, max } => format!("{} -spin {default} {min} {max}", option.name),
+        OptionType::Combo { default, choices } => {
+            let choices: Vec<_> = choices.iter().map(|c| if c == default { format!("*{c}") } else { c.clone() }).collect();
+            format!("{} -combo {}", option.name, choices.join(" /// "))
+        }
+        OptionType::Button => format!("{} -button", option.name),
+        OptionType::String { default } => format!("{} -string {default}", option.name),
+    }
+}
+
+/// Interprets a move as a legal move in the game, whether it is written in coordinate notation (`e2e4`) or SAN.
+fn parse_move(text: &str, board: &Board) -> Option<Move> {
+    uci::parse_move(text, board.position()).or_else(|| board.san_to_move(text).ok()).filter(|&m| board.is_legal(m))
+}
+
+/// Writes the information as a line of thinking output (`ply score time nodes pv`), with the time in centiseconds and the principal variation in SAN.
+pub(crate) fn thinking_output(info: &SearchInfo, position: &Position) -> String {
+    let score = match info.score {
+        Some(Score::Centipawns(cp)) => cp,
+        Some(Score::Mate(moves)) if moves > 0 => MATE_SCORE - moves,
+        Some(Score::Mate(moves)) => -MATE_SCORE - moves,
+        None => 0,
+    };
+    let centis = info.time.map_or(0, |t| t.as_millis() / 10);
+    let mut line = format!("{} {score} {centis} {}", info.depth.unwrap_or(0), info.nodes.unwrap_or(0));
+    let mut position = position.clone();
+    for &move_ in &info.pv {
+        match (position.move_to_san(move_), position.with_move_made(move_)) {
+            (Ok(san), Ok(next)) => {
+                line.push(' ');
+                line.push_str(&san);
+                position = next;
+            }
+            _ => break,
+        }
+    }
+    line
+}
+
+/// Parses a line of thinking output, returning `None` if the line is not thinking output. Move numbers in the principal variation are skipped.
+fn parse_thinking_output(line: &str, position: &Position) -> Option<SearchInfo> {
+    let mut fields = line.split_whitespace();
+    let depth = fields.next()?.parse().ok()?;
+    let score: i32 = fields.next()?.parse().ok()?;
+    let centis: u64 = fields.next()?.parse().ok()?;
+    let nodes = fields.next()?.parse().ok()?;
+    let score = match score {
+        s if s >= MATE_SCORE - 1000 => Score::Mate(MATE_SCORE - s),
+        s if s <= -MATE_SCORE + 1000 => Score::Mate(-MATE_SCORE - s),
+        s => Score::Centipawns(s),
+    };
+    let mut pv = Vec::new();
+    let mut position = position.clone();
+    for text in fields.filter(|f| !f.ends_with('.')) {
+        let Some(move_) = uci::parse_move(text, &position).or_else(|| position.san_to_move(text).ok()) else {
+            break;
+        };
+        position = position.with_move_made(move_).unwrap();
+        pv.push(move_);
+    }
+    Some(SearchInfo {
+        depth: Some(depth),
+        score: Some(score),
+        nodes: Some(nodes),
+        time: Some(Duration::from_millis(centis * 10)),
+        pv,
+        ..Default::default()
+    })
+}
+
+/// Decides how a CECP command received during a search affects it: `?` makes the engine move now, and most other commands end the game being searched.
+fn classify_command(command: &str) -> CommandEffect {
+    match command.split_whitespace().next().unwrap_or_default() {
+        "?" => CommandEffect::Stop,
+        name if NON_INTERRUPTING_COMMANDS.contains(&name) => CommandEffect::Defer,
+        _ => CommandEffect::Interrupt,
+    }
+}
+
+/// Returns the result line sent when a game ends (e.g. `1-0 {White mates}`).
+fn result_line(result: GameResult) -> String {
+    let comment = match result {
+        GameResult::Wins(Color::White, WinType::Checkmate) => "White mates",
+        GameResult::Wins(Color::Black, WinType::Checkmate) => "Black mates",
+        GameResult::Wins(Color::White, WinType::Resignation) => "Black resigns",
+        GameResult::Wins(Color::Black, WinType::Resignation) => "White resigns",
+        GameResult::Draw(DrawType::Stalemate(_)) => "Stalemate",
+        GameResult::Draw(DrawType::FivefoldRepetition) => "Draw by repetition",
+        GameResult::Draw(DrawType::SeventyFiveMoveRule) => "Draw by 75-move rule",
+        GameResult::Draw(DrawType::InsufficientMaterial) => "Insufficient material",
+        GameResult::Draw(DrawType::Agreement) => "Draw agreed",
+    };
+    format!("{result} {{{comment}}}")
+}
+
+/// Represents the state of a game played by an engine run over CECP.
+struct Game {
+    board: Board,
+    /// Whether the engine is in force mode, in which it only checks the legality of moves
+    force: bool,
+    engine_color: Color,
+    post: bool,
+    time_control: Option<TimeControl>,
+    movetime: Option<Duration>,
+    depth: Option<u32>,
+    engine_clock: Option<Duration>,
+    opponent_clock: Option<Duration>,
+}
+
+impl Game {
+    fn new() -> Self {
+        Self {
+            board: Board::default(),
+            force: false,
+            engine_color: Color::Black,
+            post: false,
+            time_control: None,
+            movetime: None,
+            depth: None,
+            engine_clock: None,
+            opponent_clock: None,
+        }
+    }
+
+    /// Returns the limits of the engine's next search.
+    fn limits(&self) -> SearchLimits {
+        let (wtime, btime) = match self.engine_color {
+            Color::White => (self.engine_clock, self.opponent_clock),
+            Color::Black => (self.opponent_clock, self.engine_clock),
+        };
+        let increment = self.time_control.map(|tc| tc.increment);
+        SearchLimits {
+            depth: self.depth,
+            movetime: self.movetime,
+            wtime,
+            btime,
+            winc: increment,
+            binc: increment,
+            movestogo: self
+                .time_control
+                .filter(|tc| tc.moves_per_session != 0)
+                .map(|tc| tc.moves_per_session - (self.board.fullmove_number() as u32 - 1) % tc.moves_per_session),
+            ..Default::default()
+        }
+    }
+}
+
+/// Runs an engine over CECP on the standard input and output, until the GUI sends `quit` or closes the input.
+pub fn run<E: Engine>(engine: &mut E) -> io::Result<()> {
+    run_with(engine, BufReader::new(io::stdin()), io::stdout())
+}
+
+/// Runs an engine over CECP, reading commands from `input` and writing replies to `output`, until the GUI sends `quit` or the input ends.
+/// The input is read on a separate thread, so that `?` (move now) and commands which end the search can arrive while the engine thinks.
+pub fn run_with<E: Engine, R: BufRead + Send + 'static, W: Write>(engine: &mut E, input: R, mut output: W) -> io::Result<()> {
+    let commands = uci::spawn_reader(input);
+    let mut pending = VecDeque::new();
+    let mut game = Game::new();
+    loop {
+        let command = match pending.pop_front() {
+            Some(command) => command,
+            _ => match commands.recv() {
+                Ok(command) => command,
+                _ => return Ok(()),
+            },
+        };
+        let (name, args) = command.split_once(' ').map_or((command.as_str(), ""), |(n, a)| (n, a.trim()));
+        let mut think = false;
+        match name {
+            "protover" => {
+                write!(
+                    output,
+                    "feature myname=\"{}\" setboard=1 usermove=1 ping=1 playother=1 san=0 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0",
+                    engine.name()
+                )?;
+                writeln!(output, " done=0")?;
+                for option in engine.options() {
+                    writeln!(output, "feature option=\"{}\"", format_option(&option))?;
+                }
+                writeln!(output, "feature done=1")?;
+            }
+            "new" => {
+                game = Game { post: game.post, ..Game::new() };
+                engine.new_game();
+            }
+            "setboard" => match Fen::try_from(args) {
+                Ok(fen) => game.board = Board::from_fen(fen),
+                _ => writeln!(output, "tellusererror Illegal position")?,
+            },
+            "force" | "result" => game.force = true,
+            "go" => {
+                game.force = false;
+                game.engine_color = game.board.side_to_move();
+                think = true;
+            }
+            "playother" => {
+                game.force = false;
+                game.engine_color = !game.board.side_to_move();
+            }
+            "usermove" => match parse_move(args, &game.board) {
+                Some(move_) => {
+                    game.board.make_move(move_).unwrap();
+                    if let Some(result) = game.board.game_result() {
+                        writeln!(output, "{}", result_line(result))?;
+                    }
+                    think = true;
+                }
+                _ => writeln!(output, "Illegal move: {args}")?,
+            },
+            "level" => game.time_control = TimeControl::parse(args),
+            "st" => game.movetime = args.parse().ok().map(Duration::from_secs),
+            "sd" => game.depth = args.parse().ok(),
+            "time" => game.engine_clock = args.parse::<u64>().ok().map(|centis| Duration::from_millis(centis * 10)),
+            "otim" => game.opponent_clock = args.parse::<u64>().ok().map(|centis| Duration::from_millis(centis * 10)),
+            "post" => game.post = true,
+            "nopost" => game.post = false,
+            "ping" => writeln!(output, "pong {args}")?,
+            "undo" => {
+                let _ = game.board.undo_move();
+            }
+            "remove" => {
+                let _ = game.board.undo_move().and_then(|_| game.board.undo_move());
+            }
+            "option" => match args.split_once('=') {
+                Some((name, value)) => engine.set_option(name.trim(), Some(value.trim())),
+                _ => engine.set_option(args, None),
+            },
+            "quit" => return Ok(()),
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating" | "?" => (),
+            _ => writeln!(output, "Error (unknown command): {command}")?,
+        }
+        if think && !game.force && game.board.side_to_move() == game.engine_color && game.board.is_ongoing() {
+            let limits = game.limits();
+            let control = SearchControl::new(&commands, &mut pending, &mut output, game.board.position(), Protocol::Cecp { post: game.post }, classify_command, false);
+            let best_move = engine.search(&game.board, &limits, &control).best_move;
+            if control.is_interrupted() {
+                continue;
+            }
+            match best_move.and_then(|m| helpers::as_legal(m, &game.board.gen_legal_moves())) {
+                Some(move_) => {
+                    writeln!(output, "move {}", uci::format_move(move_, game.board.position(), false))?;
+                    game.board.make_move(move_).unwrap();
+                    if let Some(result) = game.board.game_result() {
+                        writeln!(output, "{}", result_line(result))?;
+                    }
+                }
+                _ => writeln!(output, "resign")?,
+            }
+        }
+        output.flush()?;
+    }
+}
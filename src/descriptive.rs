@@ -0,0 +1,298 @@
+//! Handles English descriptive notation (e.g. `P-K4`, `N-KB3` or `QxQch`).
+
+use super::{helpers, Color, IllegalMoveError, InvalidDescriptiveMoveError, Move, Piece, PieceType, Position, SpecialMoveType};
+
+/// The names of the files, from the a-file (queen's rook file) to the h-file (king's rook file)
+const FILE_NAMES: [&str; 8] = ["QR", "QN", "QB", "Q", "K", "KB", "KN", "KR"];
+/// The names of the files without the side of the board, which are ambiguous except for the queen and king files
+const SHORT_FILE_NAMES: [&str; 8] = ["R", "N", "B", "Q", "K", "B", "N", "R"];
+/// The files on the queen's side of the board (a to d)
+const QUEENSIDE: u8 = 0x0f;
+/// The files on the king's side of the board (e to h)
+const KINGSIDE: u8 = 0xf0;
+
+impl Position {
+    /// Converts a `Move` to English descriptive notation, returning an error if the move is illegal. Checks are written as `ch` and mates as `#`.
+    /// Ambiguities are resolved the way books do, by naming the side of the board of the destination (`N-KB3`) or of the moving piece (`KN-B3`),
+    /// the file of a pawn (`KPxP`), or the rank or square of the moving piece (`R(1)-Q1`, `N(K2)-B3`).
+    pub fn move_to_descriptive(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        let legal = self.gen_non_illegal_moves();
+        let move_ = helpers::as_legal(move_, &legal).ok_or(IllegalMoveError(move_))?;
+        let new_position = self.with_move_made(move_).unwrap();
+        let suffix = if new_position.is_checkmate() {
+            "#"
+        } else if new_position.is_check() {
+            "ch"
+        } else {
+            ""
+        };
+        let Move(src, dest, spec) = move_;
+        match spec {
+            Some(SpecialMoveType::CastlingKingside) => return Ok(format!("O-O{suffix}")),
+            Some(SpecialMoveType::CastlingQueenside) => return Ok(format!("O-O-O{suffix}")),
+            _ => (),
+        }
+        let piece_type = match self.content[src] {
+            Some(Piece(pt, _)) => pt,
+            None => panic!("the universe is malfunctioning"),
+        };
+        let movers = self.designations(piece_type, src);
+        let (separator, targets) = if self.is_capture(move_).unwrap() {
+            let captured_sq = if spec == Some(SpecialMoveType::EnPassant) { (src / 8) * 8 + dest % 8 } else { dest };
+            let captured_type = match self.content[captured_sq] {
+                Some(Piece(pt, _)) => pt,
+                None => panic!("the universe is malfunctioning"),
+            };
+            ('x', self.designations(captured_type, captured_sq))
+        } else {
+            ('-', vec![self.square_name(dest, SHORT_FILE_NAMES), self.square_name(dest, FILE_NAMES)])
+        };
+        let promotion = match spec {
+            Some(SpecialMoveType::Promotion(pt)) => format!("={}", char::from(pt)),
+            _ => String::new(),
+        };
+        let mut descriptive = String::new();
+        'search: for mover in &movers {
+            for target in &targets {
+                descriptive = format!("{mover}{separator}{target}{promotion}");
+                if self.descriptive_candidates(&descriptive, &legal) == Some(vec![move_]) {
+                    break 'search;
+                }
+            }
+        }
+        Ok(format!("{descriptive}{suffix}"))
+    }
+
+    /// Constructs a `Move` from English descriptive notation, returning an error if it cannot be parsed, or is illegal or ambiguous.
+    /// `Kt` is accepted for knights, and `e.p.` and annotation suffixes are ignored. Pieces qualified by a side of the board
+    /// (`KN`, `QR`) are the ones currently standing on that side. As in books, a check or mate suffix resolves an otherwise
+    /// ambiguous move (e.g. `QxPch` when only one pawn can be captured with check).
+    pub fn descriptive_to_move(&self, descriptive: &str) -> Result<Move, InvalidDescriptiveMoveError> {
+        let mut candidates = self
+            .descriptive_candidates(descriptive, &self.gen_non_illegal_moves())
+            .ok_or(InvalidDescriptiveMoveError::Unparseable(descriptive.to_owned()))?;
+        let text = descriptive.trim_end_matches(['!', '?']).trim_end();
+        if candidates.len() > 1 && ["ch", "mate", "+", "#"].iter().any(|s| text.ends_with(s)) {
+            candidates.retain(|&m| self.with_move_made(m).unwrap().is_check());
+        }
+        match candidates[..] {
+            [move_] => Ok(move_),
+            [] => Err(InvalidDescriptiveMoveError::Illegal(descriptive.to_owned())),
+            _ => Err(InvalidDescriptiveMoveError::Ambiguous(descriptive.to_owned())),
+        }
+    }
+
+    /// Returns the legal moves that fit a move in descriptive notation (`None` if it cannot be parsed).
+    fn descriptive_candidates(&self, descriptive: &str, legal: &[Move]) -> Option<Vec<Move>> {
+        let text = strip_descriptive_suffixes(descriptive).replace("Kt", "N");
+        let castling = match text.as_str() {
+            "O-O" | "0-0" | "Castles" | "Castles KR" => Some(SpecialMoveType::CastlingKingside),
+            "O-O-O" | "0-0-0" | "Castles QR" => Some(SpecialMoveType::CastlingQueenside),
+            _ => None,
+        };
+        if let Some(castling) = castling {
+            return Some(legal.iter().copied().filter(|m| m.2 == Some(castling)).collect());
+        }
+        let parsed = DescriptiveMove::parse(&text, self.side)?;
+        Some(legal.iter().copied().filter(|&m| parsed.matches(m, self)).collect())
+    }
+
+    /// Returns the ways of designating the piece on a square, from the least to the most specific.
+    fn designations(&self, piece_type: PieceType, sq: usize) -> Vec<String> {
+        let file = sq % 8;
+        match piece_type {
+            PieceType::P => vec![
+                "P".to_owned(),
+                format!("{}P", SHORT_FILE_NAMES[file]),
+                format!("{}P", FILE_NAMES[file]),
+                format!("P({})", self.square_name(sq, FILE_NAMES)),
+            ],
+            PieceType::K => vec!["K".to_owned()],
+            pt => {
+                let letter = char::from(pt);
+                vec![
+                    letter.to_string(),
+                    format!("{}{letter}", if file < 4 { 'Q' } else { 'K' }),
+                    format!("{letter}({})", self.relative_rank(sq)),
+                    format!("{letter}({})", self.square_name(sq, FILE_NAMES)),
+                ]
+            }
+        }
+    }
+
+    /// Returns the rank of a square counted from the side of the board of the side to move.
+    fn relative_rank(&self, sq: usize) -> usize {
+        if self.side.is_white() {
+            sq / 8 + 1
+        } else {
+            8 - sq / 8
+        }
+    }
+
+    /// Returns the name of a square from the perspective of the side to move, using the given file names.
+    fn square_name(&self, sq: usize, file_names: [&str; 8]) -> String {
+        format!("{}{}", file_names[sq % 8], self.relative_rank(sq))
+    }
+}
+
+/// Removes surrounding whitespace, and check, mate, en passant and annotation suffixes, from a move in descriptive notation.
+fn strip_descriptive_suffixes(text: &str) -> &str {
+    let mut text = text.trim();
+    loop {
+        let mut trimmed = text.trim_end_matches(['+', '#', '!', '?']).trim_end();
+        for suffix in ["ch", "mate", "dis", "dbl", "e.p.", "ep"] {
+            trimmed = trimmed.strip_suffix(suffix).unwrap_or(trimmed).trim_end();
+        }
+        if trimmed == text {
+            return text;
+        }
+        text = trimmed;
+    }
+}
+
+/// A set of squares, made up of a set of files and a set of ranks (where bit `i` stands for the file or rank with index `i`)
+#[derive(Copy, Clone, Debug)]
+struct Region {
+    files: u8,
+    ranks: u8,
+}
+
+impl Region {
+    const ALL: Self = Self { files: 0xff, ranks: 0xff };
+
+    fn contains(&self, sq: usize) -> bool {
+        self.files & (1 << (sq % 8)) != 0 && self.ranks & (1 << (sq / 8)) != 0
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        Self {
+            files: self.files & other.files,
+            ranks: self.ranks & other.ranks,
+        }
+    }
+
+    /// Parses the name of a file (e.g. `KB` or `B`).
+    fn parse_file(text: &str) -> Option<Self> {
+        let files = match FILE_NAMES.iter().position(|&n| n == text) {
+            Some(i) => 1 << i,
+            None => SHORT_FILE_NAMES.iter().enumerate().filter(|(_, &n)| n == text).fold(0, |files, (i, _)| files | 1 << i),
+        };
+        (files != 0).then_some(Self { files, ..Self::ALL })
+    }
+
+    /// Parses a rank number counted from the side of the board of `side`.
+    fn parse_rank(c: char, side: Color) -> Option<Self> {
+        let rank = c.to_digit(10).filter(|r| (1..=8).contains(r))? as usize;
+        let idx = if side.is_white() { rank - 1 } else { 8 - rank };
+        Some(Self { ranks: 1 << idx, ..Self::ALL })
+    }
+
+    /// Parses the name of a square (e.g. `KB3` or `B3`) from the perspective of `side`.
+    fn parse_square(text: &str, side: Color) -> Option<Self> {
+        let rank = Self::parse_rank(text.chars().last()?, side)?;
+        Some(Self::parse_file(&text[..text.len() - 1])?.intersection(&rank))
+    }
+}
+
+/// A designation of a piece (e.g. `N`, `KN`, `KBP`, `R(1)` or `N(K2)`).
+#[derive(Copy, Clone, Debug)]
+struct Designation {
+    piece_type: PieceType,
+    region: Region,
+}
+
+impl Designation {
+    fn parse(text: &str, side: Color) -> Option<Self> {
+        let (body, region) = match text.strip_suffix(')') {
+            Some(rest) => {
+                let (body, location) = rest.split_once('(')?;
+                let region = match location.chars().collect::<Vec<_>>()[..] {
+                    [c] => Region::parse_rank(c, side)?,
+                    _ => Region::parse_square(location, side)?,
+                };
+                (body, region)
+            }
+            None => (text, Region::ALL),
+        };
+        let (prefix, piece_type) = match body.chars().last()? {
+            'P' => (&body[..body.len() - 1], PieceType::P),
+            c @ ('K' | 'Q' | 'R' | 'B' | 'N') => (&body[..body.len() - 1], PieceType::try_from(c).unwrap()),
+            _ => return None,
+        };
+        let qualifier = match (piece_type, prefix) {
+            (_, "") => Region::ALL,
+            (PieceType::P, file) => Region::parse_file(file)?,
+            (PieceType::R | PieceType::B | PieceType::N, "K") => Region { files: KINGSIDE, ..Region::ALL },
+            (PieceType::R | PieceType::B | PieceType::N, "Q") => Region { files: QUEENSIDE, ..Region::ALL },
+            _ => return None,
+        };
+        Some(Self {
+            piece_type,
+            region: region.intersection(&qualifier),
+        })
+    }
+
+    fn matches(&self, piece: Option<Piece>, sq: usize) -> bool {
+        matches!(piece, Some(Piece(pt, _)) if pt == self.piece_type) && self.region.contains(sq)
+    }
+}
+
+/// The destination of a move in descriptive notation: either a square, or a piece to be captured
+#[derive(Copy, Clone, Debug)]
+enum Target {
+    Square(Region),
+    Capture(Designation),
+}
+
+/// A move in descriptive notation other than castling.
+#[derive(Copy, Clone, Debug)]
+struct DescriptiveMove {
+    mover: Designation,
+    target: Target,
+    promotion: Option<PieceType>,
+}
+
+impl DescriptiveMove {
+    /// Parses a move other than castling, from which suffixes have been removed, made by `side`.
+    fn parse(text: &str, side: Color) -> Option<Self> {
+        let idx = text.find(['-', 'x'])?;
+        let (mover, capture, rest) = (&text[..idx], text[idx..].starts_with('x'), &text[idx + 1..]);
+        let chars: Vec<char> = rest.chars().collect();
+        let (rest, promotion) = match chars[..] {
+            [.., '=' | '/', p @ ('Q' | 'R' | 'B' | 'N')] => (&rest[..rest.len() - 2], Some(p)),
+            [.., '(', p @ ('Q' | 'R' | 'B' | 'N'), ')'] => (&rest[..rest.len() - 3], Some(p)),
+            [.., '1'..='8', p @ ('Q' | 'R' | 'B' | 'N')] if !capture => (&rest[..rest.len() - 1], Some(p)),
+            _ => (rest, None),
+        };
+        let target = if capture {
+            Target::Capture(Designation::parse(rest, side)?)
+        } else {
+            Target::Square(Region::parse_square(rest, side)?)
+        };
+        Some(Self {
+            mover: Designation::parse(mover, side)?,
+            target,
+            promotion: promotion.map(|p| PieceType::try_from(p).unwrap()),
+        })
+    }
+
+    /// Checks whether a legal move (other than castling) fits the description.
+    fn matches(&self, move_: Move, position: &Position) -> bool {
+        let Move(src, dest, spec) = move_;
+        if matches!(spec, Some(SpecialMoveType::CastlingKingside | SpecialMoveType::CastlingQueenside)) || !self.mover.matches(position.content[src], src) {
+            return false;
+        }
+        let target_matches = match self.target {
+            Target::Square(region) => region.contains(dest) && !position.is_capture(move_).unwrap(),
+            Target::Capture(captured) => {
+                let captured_sq = if spec == Some(SpecialMoveType::EnPassant) { (src / 8) * 8 + dest % 8 } else { dest };
+                position.content[captured_sq].is_some() && captured.matches(position.content[captured_sq], captured_sq)
+            }
+        };
+        target_matches
+            && match spec {
+                Some(SpecialMoveType::Promotion(pt)) => self.promotion.is_none_or(|p| p == pt),
+                _ => self.promotion.is_none(),
+            }
+    }
+}
@@ -0,0 +1,142 @@
+//! Classification of openings by ECO (Encyclopaedia of Chess Openings) code and name.
+
+#[cfg(feature = "pgn")]
+use super::pgn::Pgn;
+use super::{Board, Move, Position, SpecialMoveType};
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+/// The embedded table of openings, with one opening per line in the format `<code>\t<name>\t<variation>\t<moves in SAN>`
+const ECO_TABLE: &str = include_str!("eco.tsv");
+
+/// Represents a named opening line of the ECO classification.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Opening {
+    /// The ECO code, from A00 to E99
+    pub code: &'static str,
+    /// The name of the opening, such as "Sicilian Defense"
+    pub name: &'static str,
+    /// The name of the variation, such as "Najdorf Variation", if the line is a variation of the opening
+    pub variation: Option<&'static str>,
+    /// The moves of the line from the standard starting position, in SAN separated by spaces
+    pub moves: &'static str,
+}
+
+impl Opening {
+    /// Returns the name of the opening followed by the name of the variation, if any (e.g. "Sicilian Defense: Najdorf Variation").
+    pub fn full_name(&self) -> String {
+        match self.variation {
+            Some(variation) => format!("{}: {variation}", self.name),
+            None => self.name.to_owned(),
+        }
+    }
+
+    /// Returns the moves of the line from the standard starting position.
+    pub fn line(&self) -> Vec<Move> {
+        let mut board = Board::default();
+        self.moves
+            .split(' ')
+            .map(|san| {
+                let move_ = board.san_to_move(san).unwrap();
+                board.make_move(move_).unwrap();
+                move_
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code, self.full_name())
+    }
+}
+
+/// Returns all openings of the embedded ECO table, ordered by code.
+pub fn openings() -> &'static [Opening] {
+    static OPENINGS: OnceLock<Vec<Opening>> = OnceLock::new();
+    OPENINGS.get_or_init(|| {
+        ECO_TABLE
+            .lines()
+            .map(|line| {
+                let fields: Vec<_> = line.split('\t').collect();
+                Opening {
+                    code: fields[0],
+                    name: fields[1],
+                    variation: Some(fields[2]).filter(|v| !v.is_empty()),
+                    moves: fields[3],
+                }
+            })
+            .collect()
+    })
+}
+
+/// Returns the openings indexed by the positions reached at the end of their lines. When several lines reach the same position,
+/// the longest one is kept, and the first one in the table if they are equally long.
+fn opening_index() -> &'static HashMap<Position, &'static Opening> {
+    static INDEX: OnceLock<HashMap<Position, &'static Opening>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index: HashMap<Position, &'static Opening> = HashMap::new();
+        for opening in openings() {
+            let mut position = Board::default().position().clone();
+            for move_ in opening.line() {
+                position = position.with_move_made(move_).unwrap();
+            }
+            let plies = |o: &Opening| o.moves.split(' ').count();
+            index
+                .entry(normalized(position))
+                .and_modify(|o| {
+                    if plies(opening) > plies(o) {
+                        *o = opening;
+                    }
+                })
+                .or_insert(opening);
+        }
+        index
+    })
+}
+
+/// Removes the en passant target square of a position if no en passant capture is legal,
+/// so that positions reached by different move orders compare equal.
+fn normalized(mut position: Position) -> Position {
+    if position.ep_target.is_some() && !position.gen_non_illegal_moves().iter().any(|m| m.2 == Some(SpecialMoveType::EnPassant)) {
+        position.ep_target = None;
+    }
+    position
+}
+
+/// Returns the opening whose line ends in the given position, regardless of the order in which its moves were played,
+/// or `None` if the position does not end a line of the ECO table.
+pub fn classify(position: &Position) -> Option<&'static Opening> {
+    opening_index().get(&normalized(position.clone())).copied()
+}
+
+impl Board {
+    /// Classifies the opening of the game, returning the opening whose line ends in the latest position of the game
+    /// that ends a line of the ECO table, so that transpositions are recognized. Returns `None` if no such position was reached.
+    pub fn classify_opening(&self) -> Option<&'static Opening> {
+        let mut position = Board::from_fen(self.initial_fen().clone()).position().clone();
+        let mut opening = classify(&position);
+        for &move_ in self.move_history() {
+            position = position.with_move_made(move_).unwrap();
+            opening = classify(&position).or(opening);
+        }
+        opening
+    }
+}
+
+#[cfg(feature = "pgn")]
+impl Pgn {
+    /// Classifies the opening of the game and fills in the _ECO_, _Opening_ and _Variation_ tags accordingly,
+    /// removing the _Variation_ tag if the line is not a variation. The tags are left untouched if the opening is not recognized.
+    pub fn fill_opening_tags(&mut self) -> Option<&'static Opening> {
+        let opening = self.board().classify_opening()?;
+        self.set_tag("ECO", opening.code);
+        self.set_tag("Opening", opening.name);
+        match opening.variation {
+            Some(variation) => self.set_tag("Variation", variation),
+            None => {
+                self.remove_tag("Variation");
+            }
+        }
+        Some(opening)
+    }
+}
@@ -0,0 +1,689 @@
+//! A built-in chess engine, which searches positions with iterative-deepening alpha-beta search.
+
+#[cfg(feature = "uci")]
+use super::uci;
+use super::{
+    evaluation::{piece_value, Evaluator, HandcraftedEvaluator},
+    Board, Color, Move, PieceType, Position, Score, SpecialMoveType,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+/// The score of a checkmate at the root, from which the number of plies to the mate is subtracted
+const MATE: i32 = 100_000;
+/// The scores beyond which a score is considered to be a forced mate
+const MATE_THRESHOLD: i32 = MATE - 1000;
+/// The deepest search that is ever started
+const MAX_DEPTH: u32 = 64;
+/// The maximum number of entries in the transposition table, beyond which it is cleared
+const TT_CAPACITY: usize = 1 << 20;
+/// The number of nodes searched between checks of the time and node limits
+const CHECK_INTERVAL: u64 = 256;
+/// The number of moves assumed to remain in the game when the number of moves to the next time control is unknown
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// The value of the halfmove clock at which the game can be drawn by the fifty-move rule
+const FIFTY_MOVE_PLIES: usize = 100;
+
+/// The strengths, from the weakest to the strongest
+#[cfg(feature = "uci")]
+const STRENGTHS: [Strength; 5] = [Strength::Beginner, Strength::Casual, Strength::Intermediate, Strength::Advanced, Strength::Maximum];
+
+/// Represents the strength at which the engine plays, which limits the depth of its search and adds noise to its judgement of moves.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub enum Strength {
+    Beginner,
+    Casual,
+    Intermediate,
+    Advanced,
+    /// The full strength of the engine, with no limit on depth and no noise
+    #[default]
+    Maximum,
+}
+
+impl Strength {
+    /// Returns the maximum depth of a search at this strength, in plies.
+    pub fn max_depth(self) -> u32 {
+        match self {
+            Self::Beginner => 1,
+            Self::Casual => 2,
+            Self::Intermediate => 3,
+            Self::Advanced => 5,
+            Self::Maximum => MAX_DEPTH,
+        }
+    }
+
+    /// Returns the maximum noise added to the score of each move at the root, in centipawns.
+    fn noise(self) -> i32 {
+        match self {
+            Self::Beginner => 200,
+            Self::Casual => 100,
+            Self::Intermediate => 50,
+            Self::Advanced => 15,
+            Self::Maximum => 0,
+        }
+    }
+}
+
+/// Represents the limits of a search. A search without limits goes on until the maximum depth of the strength of the engine is reached.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct Limits {
+    /// The maximum depth of the search, in plies
+    pub depth: Option<u32>,
+    /// The maximum number of nodes to search
+    pub nodes: Option<u64>,
+    /// The exact time to search for
+    pub movetime: Option<Duration>,
+    /// The time left on the clock of the side to move, of which a share is used
+    pub time_left: Option<Duration>,
+    /// The increment of the side to move
+    pub increment: Duration,
+    /// The number of moves until the next time control
+    pub moves_to_go: Option<u32>,
+}
+
+impl Limits {
+    /// Constructs `Limits` that only limit the depth of the search.
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs `Limits` that make the engine search for at most the given time.
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the time to spend on the search, if it is limited by time.
+    fn allotted_time(&self) -> Option<Duration> {
+        let from_clock = self.time_left.map(|left| {
+            let share = left / self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) + self.increment * 3 / 4;
+            share.min(left / 2)
+        });
+        match (self.movetime, from_clock) {
+            (Some(movetime), Some(from_clock)) => Some(movetime.min(from_clock)),
+            (movetime, from_clock) => movetime.or(from_clock),
+        }
+    }
+}
+
+/// Represents the outcome of an iteration of the search.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Analysis {
+    /// The best move found (`None` if there are no legal moves)
+    pub best_move: Option<Move>,
+    /// The principal variation, starting with the best move
+    pub pv: Vec<Move>,
+    /// The score from the perspective of the side to move
+    pub score: Score,
+    /// The depth which was searched completely, in plies
+    pub depth: u32,
+    /// The number of nodes searched, including those of quiescence search
+    pub nodes: u64,
+    /// The time searched
+    pub time: Duration,
+}
+
+/// Represents the kind of bound that a score stored in the transposition table is.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Represents an entry of the transposition table.
+#[derive(Copy, Clone, Debug)]
+struct Entry {
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Represents the state of an iteration of the search which is passed down the tree.
+struct Context<'a> {
+    /// The keys of the positions of the game and of those from the root to the current node, with which repetitions are detected
+    path: Vec<u64>,
+    /// The number of halfmoves played since the last capture or pawn move, with which the fifty-move rule is applied
+    halfmove_clock: usize,
+    should_stop: &'a mut dyn FnMut() -> bool,
+}
+
+/// Searches positions with iterative-deepening alpha-beta search, using a transposition table, quiescence search and move ordering.
+/// The transposition table is kept between searches, so that analysing successive positions of a game is faster.
+#[derive(Clone, Debug)]
+pub struct Searcher<E: Evaluator = HandcraftedEvaluator> {
+    strength: Strength,
+    evaluator: E,
+    tt: HashMap<u64, Entry>,
+    /// The quiet moves which caused a beta cutoff at each ply, which are tried early in sibling nodes
+    killers: Vec<[Option<Move>; 2]>,
+    /// The state of the generator of the noise added at lower strengths
+    seed: u64,
+    nodes: u64,
+    start: Instant,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    aborted: bool,
+}
+
+impl Searcher {
+    /// Constructs a `Searcher` that plays at the given strength, with the handcrafted evaluation.
+    pub fn new(strength: Strength) -> Self {
+        Self::with_evaluator(strength, HandcraftedEvaluator)
+    }
+}
+
+impl<E: Evaluator> Searcher<E> {
+    /// Constructs a `Searcher` that plays at the given strength, evaluating positions with the given evaluator.
+    pub fn with_evaluator(strength: Strength, evaluator: E) -> Self {
+        Self {
+            strength,
+            evaluator,
+            tt: HashMap::new(),
+            killers: Vec::new(),
+            seed: 0x9e37_79b9_7f4a_7c15,
+            nodes: 0,
+            start: Instant::now(),
+            deadline: None,
+            node_limit: None,
+            aborted: false,
+        }
+    }
+
+    /// Returns the strength at which the engine plays.
+    pub fn strength(&self) -> Strength {
+        self.strength
+    }
+
+    /// Sets the strength at which the engine plays.
+    pub fn set_strength(&mut self, strength: Strength) {
+        self.strength = strength;
+    }
+
+    /// Clears the transposition table, e.g. before analysing an unrelated game.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+    }
+
+    /// Searches the position within the given limits, returning the result of the deepest iteration that was completed.
+    pub fn search(&mut self, position: &Position, limits: &Limits) -> Analysis {
+        self.search_with(position, limits, |_| true, || false)
+    }
+
+    /// Searches the position within the given limits, calling `on_iteration` with the result of each iteration as it completes
+    /// (the search is stopped when it returns `false`), and regularly calling `should_stop` during iterations (the search is
+    /// aborted when it returns `true`, e.g. because the user asked it to stop).
+    pub fn search_with<F, S>(&mut self, position: &Position, limits: &Limits, on_iteration: F, should_stop: S) -> Analysis
+    where
+        F: FnMut(&Analysis) -> bool,
+        S: FnMut() -> bool,
+    {
+        self.search_from(position, Vec::new(), 0, limits, on_iteration, should_stop)
+    }
+
+    /// Searches the current position of the game on the board within the given limits, scoring repetitions of the positions of the game
+    /// and the fifty-move rule as draws.
+    pub fn search_game(&mut self, board: &Board, limits: &Limits) -> Analysis {
+        self.search_game_with(board, limits, |_| true, || false)
+    }
+
+    /// Searches the current position of the game on the board like [`Searcher::search_with`], scoring repetitions of the positions of
+    /// the game and the fifty-move rule as draws.
+    pub fn search_game_with<F, S>(&mut self, board: &Board, limits: &Limits, on_iteration: F, should_stop: S) -> Analysis
+    where
+        F: FnMut(&Analysis) -> bool,
+        S: FnMut() -> bool,
+    {
+        let history = board.position_history().iter().map(key).collect();
+        self.search_from(board.position(), history, board.halfmove_clock(), limits, on_iteration, should_stop)
+    }
+
+    /// Searches a position reached after the positions with the given keys, with the given halfmove clock.
+    fn search_from<F, S>(&mut self, position: &Position, history: Vec<u64>, halfmove_clock: usize, limits: &Limits, mut on_iteration: F, mut should_stop: S) -> Analysis
+    where
+        F: FnMut(&Analysis) -> bool,
+        S: FnMut() -> bool,
+    {
+        self.nodes = 0;
+        self.start = Instant::now();
+        self.deadline = limits.allotted_time().map(|t| self.start + t);
+        self.node_limit = limits.nodes;
+        self.aborted = false;
+        self.killers.clear();
+        if self.tt.len() > TT_CAPACITY {
+            self.tt.clear();
+        }
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(self.strength.max_depth()).max(1);
+        let mut analysis = Analysis {
+            best_move: None,
+            pv: Vec::new(),
+            score: Score::Centipawns(0),
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+        };
+        let root_moves = position.gen_non_illegal_moves();
+        if root_moves.is_empty() {
+            analysis.score = if position.is_check() { Score::Mate(0) } else { Score::Centipawns(0) };
+            return analysis;
+        }
+        let noise: Vec<_> = root_moves.iter().map(|_| self.next_noise()).collect();
+        let mut context = Context {
+            path: history,
+            halfmove_clock,
+            should_stop: &mut should_stop,
+        };
+        for depth in 1..=max_depth {
+            let Some((best_move, score)) = self.search_root(position, &root_moves, &noise, depth, &mut context) else {
+                break;
+            };
+            analysis = Analysis {
+                best_move: Some(best_move),
+                pv: self.principal_variation(position, best_move, depth),
+                score: to_score(score),
+                depth,
+                nodes: self.nodes,
+                time: self.start.elapsed(),
+            };
+            let out_of_time = self.deadline.is_some_and(|d| self.start.elapsed() * 2 > d - self.start);
+            if !on_iteration(&analysis) || out_of_time || score.abs() > MATE_THRESHOLD {
+                break;
+            }
+        }
+        if analysis.best_move.is_none() {
+            analysis.best_move = Some(root_moves[0]);
+            analysis.pv = vec![root_moves[0]];
+        }
+        analysis.nodes = self.nodes;
+        analysis.time = self.start.elapsed();
+        analysis
+    }
+
+    /// Searches the root position to the given depth, returning the best move and its score, or `None` if the search was aborted.
+    /// The move is chosen by its score with noise added, but the score returned and stored in the transposition table has none.
+    fn search_root(&mut self, position: &Position, root_moves: &[Move], noise: &[i32], depth: u32, context: &mut Context) -> Option<(Move, i32)> {
+        let mut order: Vec<_> = (0..root_moves.len()).collect();
+        let tt_move = self.tt.get(&key(position)).and_then(|e| e.best_move);
+        order.sort_by_key(|&i| -self.move_priority(position, root_moves[i], tt_move, 0));
+        let (mut alpha, beta) = (-MATE - 1, MATE + 1);
+        let mut best = None;
+        context.path.push(key(position));
+        for i in order {
+            let child = position.with_move_made(root_moves[i]).unwrap();
+            let clock = context.halfmove_clock;
+            context.halfmove_clock = if resets_clock(position, root_moves[i]) { 0 } else { clock + 1 };
+            // The noise is taken out of the bound, so that the score is exact whenever it could beat the best one once noise is added.
+            let bound = if alpha.abs() > MATE_THRESHOLD { alpha } else { alpha - noise[i] };
+            let score = -self.negamax(&child, depth - 1, 1, -beta, -bound, context);
+            context.halfmove_clock = clock;
+            if self.aborted {
+                context.path.pop();
+                return None;
+            }
+            let noisy = if score.abs() > MATE_THRESHOLD { score } else { score + noise[i] };
+            if noisy > alpha {
+                alpha = noisy;
+                best = Some((root_moves[i], score));
+            }
+        }
+        context.path.pop();
+        let (best_move, score) = best?;
+        // With noise, a better move may have been passed over, so the score is only a lower bound of that of the position.
+        self.tt.insert(
+            key(position),
+            Entry {
+                depth,
+                score,
+                bound: if noise.iter().all(|&n| n == 0) { Bound::Exact } else { Bound::Lower },
+                best_move: Some(best_move),
+            },
+        );
+        Some((best_move, score))
+    }
+
+    fn negamax(&mut self, position: &Position, depth: u32, ply: usize, mut alpha: i32, beta: i32, context: &mut Context) -> i32 {
+        if self.should_abort(context) {
+            return 0;
+        }
+        let key = key(position);
+        if context.path.contains(&key) {
+            return 0;
+        }
+        if context.halfmove_clock >= FIFTY_MOVE_PLIES {
+            return if position.is_checkmate() { -MATE + ply as i32 } else { 0 };
+        }
+        let tt_entry = self.tt.get(&key).copied();
+        if let Some(entry) = tt_entry.filter(|e| e.depth >= depth) {
+            let score = from_tt(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => (),
+            }
+        }
+        let moves = position.gen_non_illegal_moves();
+        if moves.is_empty() {
+            return if position.is_check() { -MATE + ply as i32 } else { 0 };
+        }
+        if position.is_insufficient_material() {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(position, alpha, beta, context);
+        }
+        let tt_move = tt_entry.and_then(|e| e.best_move);
+        let mut moves: Vec<_> = moves.into_iter().map(|m| (self.move_priority(position, m, tt_move, ply), m)).collect();
+        moves.sort_by_key(|&(priority, _)| -priority);
+        let original_alpha = alpha;
+        let (mut best_score, mut best_move) = (-MATE - 1, None);
+        context.path.push(key);
+        for (_, move_) in moves {
+            let child = position.with_move_made(move_).unwrap();
+            let clock = context.halfmove_clock;
+            context.halfmove_clock = if resets_clock(position, move_) { 0 } else { clock + 1 };
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, context);
+            context.halfmove_clock = clock;
+            if self.aborted {
+                context.path.pop();
+                return 0;
+            }
+            if score > best_score {
+                (best_score, best_move) = (score, Some(move_));
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                if !is_tactical(position, move_) {
+                    self.add_killer(move_, ply);
+                }
+                break;
+            }
+        }
+        context.path.pop();
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.insert(
+            key,
+            Entry {
+                depth,
+                score: to_tt(best_score, ply),
+                bound,
+                best_move,
+            },
+        );
+        best_score
+    }
+
+    /// Searches only captures and promotions until the position is quiet, so that the evaluation is not taken in the middle of an exchange.
+    fn quiescence(&mut self, position: &Position, mut alpha: i32, beta: i32, context: &mut Context) -> i32 {
+        if self.should_abort(context) {
+            return 0;
+        }
+        let stand_pat = match position.side {
+            Color::White => self.evaluator.evaluate(position),
+            Color::Black => -self.evaluator.evaluate(position),
+        };
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        let mut moves: Vec<_> = position
+            .gen_non_illegal_moves()
+            .into_iter()
+            .filter(|&m| is_tactical(position, m))
+            .map(|m| (mvv_lva(position, m), m))
+            .collect();
+        moves.sort_by_key(|&(priority, _)| -priority);
+        for (_, move_) in moves {
+            let child = position.with_move_made(move_).unwrap();
+            let score = -self.quiescence(&child, -beta, -alpha, context);
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    /// Returns the priority of a move in move ordering: the move from the transposition table comes first,
+    /// followed by captures and promotions (most valuable victim, least valuable attacker first), killer moves and the remaining moves.
+    fn move_priority(&self, position: &Position, move_: Move, tt_move: Option<Move>, ply: usize) -> i32 {
+        if Some(move_) == tt_move {
+            1_000_000
+        } else if is_tactical(position, move_) {
+            100_000 + mvv_lva(position, move_)
+        } else if self.killers.get(ply).is_some_and(|k| k.contains(&Some(move_))) {
+            50_000
+        } else {
+            0
+        }
+    }
+
+    fn add_killer(&mut self, move_: Move, ply: usize) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(move_) {
+            killers[1] = killers[0];
+            killers[0] = Some(move_);
+        }
+    }
+
+    /// Follows the best moves stored in the transposition table from the root, stopping at a repetition or after `depth` moves.
+    fn principal_variation(&self, position: &Position, best_move: Move, depth: u32) -> Vec<Move> {
+        let mut pv = vec![best_move];
+        let mut position = position.with_move_made(best_move).unwrap();
+        let mut seen = vec![key(&position)];
+        while pv.len() < depth as usize {
+            let Some(move_) = self.tt.get(&key(&position)).and_then(|e| e.best_move) else { break };
+            let Ok(next) = position.with_move_made(move_) else { break };
+            if seen.contains(&key(&next)) {
+                break;
+            }
+            seen.push(key(&next));
+            pv.push(move_);
+            position = next;
+        }
+        pv
+    }
+
+    /// Counts a node, and checks (every few nodes) whether the time or node limit has been reached or the search should stop.
+    fn should_abort(&mut self, context: &mut Context) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.aborted |= self.deadline.is_some_and(|d| Instant::now() >= d) || self.node_limit.is_some_and(|n| self.nodes >= n) || (context.should_stop)();
+        }
+        self.aborted
+    }
+
+    /// Returns the next value of the noise added to the scores of root moves, between plus and minus the noise of the strength.
+    fn next_noise(&mut self) -> i32 {
+        let range = self.strength.noise();
+        if range == 0 {
+            return 0;
+        }
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed % (2 * range as u64 + 1)) as i32 - range
+    }
+}
+
+impl Default for Searcher {
+    /// Constructs a `Searcher` that plays at full strength.
+    fn default() -> Self {
+        Self::new(Strength::Maximum)
+    }
+}
+
+/// Returns the key of a position in the transposition table.
+fn key(position: &Position) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    position.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Converts a mate score relative to the root into one relative to the current node, so that it can be stored in the transposition table.
+fn to_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        s if s > MATE_THRESHOLD => s + ply as i32,
+        s if s < -MATE_THRESHOLD => s - ply as i32,
+        s => s,
+    }
+}
+
+/// Converts a mate score read from the transposition table into one relative to the root.
+fn from_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        s if s > MATE_THRESHOLD => s - ply as i32,
+        s if s < -MATE_THRESHOLD => s + ply as i32,
+        s => s,
+    }
+}
+
+/// Converts an internal score into a `Score`, with mates given in moves.
+fn to_score(score: i32) -> Score {
+    match score {
+        s if s > MATE_THRESHOLD => Score::Mate((MATE - s + 1) / 2),
+        s if s < -MATE_THRESHOLD => Score::Mate(-((MATE + s + 1) / 2)),
+        s => Score::Centipawns(s),
+    }
+}
+
+/// Checks whether a move is a capture or a promotion.
+fn is_tactical(position: &Position, move_: Move) -> bool {
+    match move_.2 {
+        Some(SpecialMoveType::EnPassant | SpecialMoveType::Promotion(_)) => true,
+        Some(SpecialMoveType::CastlingKingside | SpecialMoveType::CastlingQueenside) => false,
+        _ => position.content[move_.1].is_some(),
+    }
+}
+
+/// Checks whether a move resets the halfmove clock, i.e. whether it is a capture or a pawn move.
+fn resets_clock(position: &Position, move_: Move) -> bool {
+    position.content[move_.1].is_some() || position.content[move_.0].is_some_and(|p| p.piece_type() == PieceType::P)
+}
+
+/// Returns the priority of a capture or promotion: the value of the captured piece (and of the promoted piece) minus a tenth of the value of the capturing piece.
+fn mvv_lva(position: &Position, move_: Move) -> i32 {
+    let victim = match move_.2 {
+        Some(SpecialMoveType::EnPassant) => piece_value(PieceType::P),
+        _ => position.content[move_.1].map_or(0, |p| piece_value(p.piece_type())),
+    };
+    let promotion = match move_.2 {
+        Some(SpecialMoveType::Promotion(piece_type)) => piece_value(piece_type),
+        _ => 0,
+    };
+    let attacker = position.content[move_.0].map_or(0, |p| piece_value(p.piece_type()));
+    victim + promotion - attacker / 10
+}
+
+#[cfg(feature = "uci")]
+impl<E: Evaluator> uci::Engine for Searcher<E> {
+    fn name(&self) -> String {
+        format!("rschess {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn author(&self) -> String {
+        "the rschess developers".to_owned()
+    }
+
+    /// Declares the strength of the engine as a combo option named _Strength_.
+    fn options(&self) -> Vec<uci::EngineOption> {
+        vec![uci::EngineOption {
+            name: "Strength".to_owned(),
+            option_type: uci::OptionType::Combo {
+                default: format!("{:?}", Strength::Maximum),
+                choices: STRENGTHS.iter().map(|s| format!("{s:?}")).collect(),
+            },
+        }]
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) {
+        if let (true, Some(value)) = (name.eq_ignore_ascii_case("Strength"), value) {
+            if let Some(&strength) = STRENGTHS.iter().find(|s| format!("{s:?}").eq_ignore_ascii_case(value)) {
+                self.strength = strength;
+            }
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.clear();
+    }
+
+    /// Searches the position with the time of the side to move, without time limits when searching infinitely.
+    /// While pondering, the clock only starts to run once the GUI sends `ponderhit`.
+    fn search(&mut self, board: &Board, limits: &uci::SearchLimits, control: &uci::SearchControl) -> uci::BestMove {
+        let (time_left, increment) = match board.side_to_move() {
+            Color::White => (limits.wtime, limits.winc),
+            Color::Black => (limits.btime, limits.binc),
+        };
+        let clock_limits = Limits {
+            depth: limits.depth,
+            nodes: limits.nodes,
+            movetime: limits.movetime.filter(|_| !limits.infinite),
+            time_left: time_left.filter(|_| !limits.infinite),
+            increment: increment.unwrap_or_default(),
+            moves_to_go: limits.movestogo,
+        };
+        let (engine_limits, ponder_time) = if limits.ponder {
+            (
+                Limits {
+                    movetime: None,
+                    time_left: None,
+                    ..clock_limits
+                },
+                clock_limits.allotted_time(),
+            )
+        } else {
+            (clock_limits, None)
+        };
+        let mut ponderhit = None;
+        let should_stop = || {
+            if control.should_stop() {
+                return true;
+            }
+            match ponder_time {
+                Some(time) if !control.is_pondering() => ponderhit.get_or_insert_with(Instant::now).elapsed() >= time,
+                _ => false,
+            }
+        };
+        let report = |analysis: &Analysis| {
+            let info = uci::SearchInfo {
+                depth: Some(analysis.depth),
+                score: Some(analysis.score),
+                nodes: Some(analysis.nodes),
+                nps: Some((analysis.nodes as f64 / analysis.time.as_secs_f64().max(0.001)) as u64),
+                time: Some(analysis.time),
+                pv: analysis.pv.clone(),
+                ..Default::default()
+            };
+            control.report(&info).is_ok()
+        };
+        let analysis = self.search_game_with(board, &engine_limits, report, should_stop);
+        uci::BestMove {
+            best_move: analysis.best_move,
+            ponder: analysis.pv.get(1).copied(),
+        }
+    }
+}
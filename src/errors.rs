@@ -38,6 +38,106 @@ pub enum InvalidUciError {
     InvalidPieceType(char),
 }
 
+/// Conveys that the given ICCF numeric text is invalid.
+#[derive(Error, Debug)]
+pub enum InvalidIccfError {
+    #[error("Invalid ICCF: expected ICCF to be 4 to 5 digits long")]
+    Length,
+    #[error("Invalid ICCF: '{0}{1}' is not a valid square; files and ranks must be digits 1 to 8")]
+    InvalidSquare(char, char),
+    #[error("Invalid ICCF: '{0}' is not a valid promotion digit; it must be 1 (queen), 2 (rook), 3 (bishop) or 4 (knight)")]
+    InvalidPromotion(char),
+}
+
+/// Conveys that communication with a UCI engine failed.
+#[derive(Error, Debug)]
+pub enum UciError {
+    #[error("UCI error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("UCI error: the engine closed its output")]
+    Disconnected,
+    #[error("UCI error: the engine sent '{0}', which is not a legal move in the position")]
+    InvalidMove(String),
+}
+
+/// Conveys that communication with a CECP (XBoard) engine failed.
+#[derive(Error, Debug)]
+pub enum CecpError {
+    #[error("CECP error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CECP error: the engine closed its output")]
+    Disconnected,
+    #[error("CECP error: '{0}' is not a legal move in the position")]
+    InvalidMove(String),
+    #[error("CECP error: the engine does not support the '{0}' feature")]
+    Unsupported(String),
+    #[error("CECP error: the engine replied '{0}'")]
+    Engine(String),
+}
+
+/// Conveys that a Polyglot opening book could not be read.
+#[derive(Error, Debug)]
+pub enum PolyglotError {
+    #[error("Polyglot error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Polyglot error: the book is {0} bytes long, which is not a multiple of the 16-byte entry size")]
+    Length(usize),
+}
+
+/// Conveys that the given endgame is invalid or has too many pieces to be generated.
+#[cfg(feature = "tablebase")]
+#[derive(Error, Debug)]
+#[error("Invalid endgame: '{0}' must be written like KQvK, with a king on each side and at most 4 pieces in total")]
+pub struct InvalidEndgameError(pub String);
+
+/// Conveys that an endgame tablebase could not be saved or loaded.
+#[cfg(feature = "tablebase")]
+#[derive(Error, Debug)]
+pub enum TablebaseError {
+    #[error("Tablebase error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Tablebase error: the data does not begin with the rschess tablebase header")]
+    Header,
+    #[error("Tablebase error: {0}")]
+    InvalidEndgame(#[from] InvalidEndgameError),
+    #[error("Tablebase error: the table of {0} has {1} bytes of entries, expected {2}")]
+    Length(String, usize, usize),
+}
+
+/// Conveys that a position could not be probed in Syzygy tablebases.
+#[cfg(feature = "tablebase")]
+#[derive(Error, Debug)]
+pub enum SyzygyError {
+    #[error("Syzygy error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Syzygy error: {0} is not a valid Syzygy table")]
+    InvalidTable(String),
+    #[error("Syzygy error: no table was found for the material {0}")]
+    MissingTable(String),
+    #[error("Syzygy error: positions with castling rights cannot be probed")]
+    CastlingRights,
+    #[error("Syzygy error: the position has {0} pieces, but the tables found have at most {1}")]
+    TooManyPieces(usize, usize),
+}
+
+/// Conveys that the given puzzle is invalid.
+#[cfg(feature = "puzzle")]
+#[derive(Error, Debug)]
+pub enum InvalidPuzzleError {
+    #[error("Invalid puzzle: {0}")]
+    InvalidFen(#[from] InvalidFenError),
+    #[error("Invalid puzzle: {0}")]
+    InvalidMove(#[from] InvalidUciMoveError),
+    #[error("Invalid puzzle: the solution has no moves")]
+    EmptySolution,
+    #[error("Invalid puzzle: the record has no '{0}' field")]
+    MissingField(&'static str),
+    #[error("Invalid puzzle: '{1}' is not a valid value of the '{0}' field")]
+    InvalidField(&'static str, String),
+    #[error("Invalid puzzle: {0}")]
+    Io(#[from] std::io::Error),
+}
+
 /// Conveys that the given color character is invalid.
 #[derive(Error, Debug)]
 #[error("Invalid color character: '{0}', a valid color character must be 'w' or 'b'")]
@@ -62,10 +162,56 @@ pub enum InvalidUciMoveError {
     IllegalMove(String),
 }
 
-/// Conveys that the given SAN move is either invalid or illegal.
+/// Conveys that the given ICCF move is either invalid or illegal.
 #[derive(Error, Debug)]
-#[error("Invalid SAN move: '{0}' is either invalid or illegal in this position")]
-pub struct InvalidSanMoveError(pub String);
+pub enum InvalidIccfMoveError {
+    #[error("Invalid ICCF move: '{0}' is not valid ICCF")]
+    InvalidIccf(String),
+    #[error("Invalid ICCF move: '{0}' is illegal in this position")]
+    IllegalMove(String),
+}
+
+/// Conveys that the given SAN move is either invalid, illegal or ambiguous.
+#[derive(Error, Debug)]
+pub enum InvalidSanMoveError {
+    #[error("Invalid SAN move: '{0}' could not be parsed")]
+    Unparseable(String),
+    #[error("Invalid SAN move: '{0}' is illegal in this position")]
+    Illegal(String),
+    #[error("Invalid SAN move: '{0}' is ambiguous in this position")]
+    Ambiguous(String),
+}
+
+/// Conveys that the given LAN move is either invalid, illegal or ambiguous.
+#[derive(Error, Debug)]
+pub enum InvalidLanMoveError {
+    #[error("Invalid LAN move: '{0}' could not be parsed")]
+    Unparseable(String),
+    #[error("Invalid LAN move: '{0}' is illegal in this position")]
+    Illegal(String),
+    #[error("Invalid LAN move: '{0}' is ambiguous in this position")]
+    Ambiguous(String),
+}
+
+/// Conveys that the given move in descriptive notation is either invalid, illegal or ambiguous.
+#[derive(Error, Debug)]
+pub enum InvalidDescriptiveMoveError {
+    #[error("Invalid descriptive move: '{0}' could not be parsed")]
+    Unparseable(String),
+    #[error("Invalid descriptive move: '{0}' is illegal in this position")]
+    Illegal(String),
+    #[error("Invalid descriptive move: '{0}' is ambiguous in this position")]
+    Ambiguous(String),
+}
+
+/// Conveys that the given piece letters cannot be used as a notation locale.
+#[derive(Error, Debug)]
+pub enum InvalidNotationLocaleError {
+    #[error("Invalid notation locale: '{0}' could be confused with other parts of a move")]
+    InvalidLetter(char),
+    #[error("Invalid notation locale: '{0}' is used for more than one type of piece")]
+    DuplicateLetter(char),
+}
 
 /// Conveys that the given square name is invalid.
 #[derive(Error, Debug)]
@@ -87,21 +233,40 @@ pub enum GameOverError {
 }
 
 /// Conveys that the given PGN text is invalid.
+/// Errors encountered while reading PGN text carry the [`Location`](super::pgn::Location) in the text where the problem was found.
 #[cfg(feature = "pgn")]
 #[derive(Error, Debug)]
 pub enum InvalidPgnError {
-    #[error("Invalid PGN: the elements are incorrectly organized, {0}")]
-    OrderOfElements(String),
-    #[error("Invalid PGN: move numbers cannot be less than 1, and successive move numbers must differ by 1")]
-    InvalidMoveNumber,
-    #[error("Invalid PGN: variations (and annotations) are not yet supported; all movetext must include only fullmoves and a halfmove is only allowed on the last move")]
-    NoAnnotations,
-    #[error("Invalid PGN: tag pairs must follow the Seven Tag Roster (https://en.wikipedia.org/wiki/Portable_Game_Notation#Seven_Tag_Roster)")]
-    SevenTagRoster,
-    #[error("Invalid PGN: {0}")]
-    InvalidMove(InvalidSanMoveError),
-    #[error("Invalid PGN: invalid result, {0}")]
-    InvalidResult(String),
+    #[error("Invalid PGN at {0}: '{1}' cannot begin a token")]
+    UnexpectedCharacter(super::pgn::Location, char),
+    #[error("Invalid PGN at {0}: {1}")]
+    UnexpectedToken(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: this {1} is never closed")]
+    Unterminated(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: '{1}' is not a valid NAG")]
+    InvalidNag(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: the elements are incorrectly organized, {1}")]
+    OrderOfElements(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: the move number does not match the number of the move that follows it")]
+    InvalidMoveNumber(super::pgn::Location),
+    #[error("Invalid PGN at {0}: expected a move number before this move")]
+    MissingMoveNumber(super::pgn::Location),
+    #[error("Invalid PGN at {0}: the '{1}' tag appears more than once")]
+    DuplicateTag(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: the '{1}' tag is missing, tag pairs must follow the Seven Tag Roster (https://en.wikipedia.org/wiki/Portable_Game_Notation#Seven_Tag_Roster)")]
+    MissingTag(super::pgn::Location, String),
+    #[error("Invalid PGN: the '{0}' tag is missing, tag pairs must follow the Seven Tag Roster (https://en.wikipedia.org/wiki/Portable_Game_Notation#Seven_Tag_Roster)")]
+    MissingRosterTag(String),
+    #[error("Invalid PGN at {0}: {1}")]
+    InvalidFen(super::pgn::Location, InvalidFenError),
+    #[error("Invalid PGN at {0}: {1}")]
+    InvalidMove(super::pgn::Location, InvalidSanMoveError),
+    #[error("Invalid PGN at {0}: {1}")]
+    InvalidDescriptiveMove(super::pgn::Location, InvalidDescriptiveMoveError),
+    #[error("Invalid PGN at {0}: invalid result, {1}")]
+    InvalidResult(super::pgn::Location, String),
+    #[error("Invalid PGN at {0}: expected a game termination marker (1-0, 0-1, 1/2-1/2 or *)")]
+    MissingResult(super::pgn::Location),
 }
 
 /// Conveys that the given hex color is invalid.
@@ -0,0 +1,369 @@
+//! Handles the static evaluation of positions, with a pluggable `Evaluator` and a handcrafted default.
+
+use super::{Color, Move, Piece, PieceType, Position};
+use std::fmt;
+
+/// The game phase of a position with all of the pieces on the board (pawns and kings excluded)
+const MAX_PHASE: i32 = 24;
+
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Represents a term of an evaluation, scored in centipawns for each side.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Term {
+    pub name: String,
+    pub white: i32,
+    pub black: i32,
+}
+
+impl Term {
+    /// Constructs a `Term` with the given name and scores.
+    pub fn new(name: &str, white: i32, black: i32) -> Self {
+        Self { name: name.to_owned(), white, black }
+    }
+
+    /// Returns the score of the term from White's perspective (White's score minus Black's).
+    pub fn score(&self) -> i32 {
+        self.white - self.black
+    }
+}
+
+/// Represents an evaluation broken down into terms, which explains why a position is better for one side.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+pub struct Breakdown {
+    pub terms: Vec<Term>,
+}
+
+impl Breakdown {
+    /// Returns the total score from White's perspective, in centipawns.
+    pub fn total(&self) -> i32 {
+        self.terms.iter().map(Term::score).sum()
+    }
+
+    /// Returns the term with the given name (`None` if there is no such term).
+    pub fn term(&self, name: &str) -> Option<&Term> {
+        self.terms.iter().find(|t| t.name == name)
+    }
+}
+
+impl fmt::Display for Breakdown {
+    /// Represents the breakdown as a table with a row for each term, followed by the total.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.terms.iter().map(|t| t.name.chars().count()).max().unwrap_or(0).max(5);
+        writeln!(f, "{:width$} {:>7} {:>7} {:>7}", "Term", "White", "Black", "Total")?;
+        for term in &self.terms {
+            writeln!(f, "{:width$} {:>7} {:>7} {:>7}", term.name, term.white, term.black, term.score())?;
+        }
+        write!(f, "{:width$} {:>7} {:>7} {:>7}", "Total", "", "", self.total())
+    }
+}
+
+/// Represents a static evaluation function over positions.
+pub trait Evaluator {
+    /// Evaluates the position term by term, in centipawns for each side.
+    fn breakdown(&self, position: &Position) -> Breakdown;
+
+    /// Evaluates the position in centipawns from White's perspective (positive when White is better).
+    fn evaluate(&self, position: &Position) -> i32 {
+        self.breakdown(position).total()
+    }
+}
+
+/// A handcrafted evaluation with the terms _Material_, _Piece-square tables_, _Pawn structure_, _Mobility_ and _King safety_.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct HandcraftedEvaluator;
+
+impl Evaluator for HandcraftedEvaluator {
+    fn breakdown(&self, position: &Position) -> Breakdown {
+        let phase = game_phase(position);
+        let mut terms = vec![
+            Term::new("Material", 0, 0),
+            Term::new("Piece-square tables", 0, 0),
+            Term::new("Pawn structure", 0, 0),
+            Term::new("Mobility", 0, 0),
+            Term::new("King safety", 0, 0),
+        ];
+        for color in [Color::White, Color::Black] {
+            for (term, score) in terms.iter_mut().zip(term_scores(position, color, phase)) {
+                *if color == Color::White { &mut term.white } else { &mut term.black } = score;
+            }
+        }
+        Breakdown { terms }
+    }
+
+    /// Sums the terms directly, without building a [`Breakdown`], as the evaluation is called at every leaf of a search.
+    fn evaluate(&self, position: &Position) -> i32 {
+        let phase = game_phase(position);
+        let white: i32 = term_scores(position, Color::White, phase).iter().sum();
+        let black: i32 = term_scores(position, Color::Black, phase).iter().sum();
+        white - black
+    }
+}
+
+/// Returns the scores of a side for each term of the handcrafted evaluation, in the order of its breakdown.
+fn term_scores(position: &Position, color: Color, phase: i32) -> [i32; 5] {
+    let side = Sides::of(position, color);
+    [material(&side), piece_squares(&side, phase), pawn_structure(&side), mobility(&side), king_safety(&side, phase)]
+}
+
+/// Returns the value of a piece in centipawns, the king being worth nothing.
+pub(crate) fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::P => 100,
+        PieceType::N => 320,
+        PieceType::B => 330,
+        PieceType::R => 500,
+        PieceType::Q => 900,
+        PieceType::K => 0,
+    }
+}
+
+/// Represents a side of a position along with its opponent, as seen by the terms of the handcrafted evaluation.
+struct Sides<'a> {
+    position: &'a Position,
+    color: Color,
+    /// The pseudolegal moves of the side, as if it were to move
+    moves: Vec<Move>,
+    /// The squares attacked by the opponent
+    enemy_attacks: [bool; 64],
+}
+
+impl<'a> Sides<'a> {
+    fn of(position: &'a Position, color: Color) -> Self {
+        let mut enemy_attacks = [false; 64];
+        // Pawns do not attack the squares they can push to, so their attacks are added separately.
+        for Move(src, dest, _) in moves_of(position, !color) {
+            if position.content[src] != Some(Piece(PieceType::P, !color)) {
+                enemy_attacks[dest] = true;
+            }
+        }
+        for (sq, piece) in position.content.iter().enumerate() {
+            if *piece == Some(Piece(PieceType::P, !color)) {
+                for dest in pawn_attacks(sq, !color) {
+                    enemy_attacks[dest] = true;
+                }
+            }
+        }
+        Self {
+            position,
+            color,
+            moves: moves_of(position, color),
+            enemy_attacks,
+        }
+    }
+
+    /// Returns the squares of the pieces of the given type belonging to the side (`own`) or to its opponent.
+    fn squares(&self, piece_type: PieceType, own: bool) -> impl Iterator<Item = usize> + '_ {
+        let piece = Piece(piece_type, if own { self.color } else { !self.color });
+        (0..64).filter(move |&sq| self.position.content[sq] == Some(piece))
+    }
+
+    /// Returns the rank of a square as seen from the side, 0 being its back rank.
+    fn relative_rank(&self, sq: usize) -> usize {
+        if self.color == Color::White {
+            sq / 8
+        } else {
+            7 - sq / 8
+        }
+    }
+}
+
+/// Generates the pseudolegal moves of a side, as if it were to move, without castling or en passant.
+fn moves_of(position: &Position, color: Color) -> Vec<Move> {
+    Position {
+        side: color,
+        castling_rights: [None; 4],
+        ep_target: None,
+        ..position.clone()
+    }
+    .gen_pseudolegal_moves()
+}
+
+/// Returns the squares attacked by a pawn of the given color.
+fn pawn_attacks(sq: usize, color: Color) -> impl Iterator<Item = usize> {
+    let (file, rank) = ((sq % 8) as isize, (sq / 8) as isize);
+    let rank = rank + if color == Color::White { 1 } else { -1 };
+    [file - 1, file + 1]
+        .into_iter()
+        .filter(move |f| (0..8).contains(f) && (0..8).contains(&rank))
+        .map(move |f| (rank * 8 + f) as usize)
+}
+
+/// Returns the phase of the game, from [`MAX_PHASE`] in the opening down to 0 when only kings and pawns are left.
+fn game_phase(position: &Position) -> i32 {
+    let phase = position
+        .content
+        .iter()
+        .flatten()
+        .map(|p| match p.0 {
+            PieceType::N | PieceType::B => 1,
+            PieceType::R => 2,
+            PieceType::Q => 4,
+            _ => 0,
+        })
+        .sum::<i32>();
+    phase.min(MAX_PHASE)
+}
+
+fn material(side: &Sides) -> i32 {
+    let value: i32 = side.position.content.iter().flatten().filter(|p| p.1 == side.color).map(|p| piece_value(p.0)).sum();
+    let bishop_pair = if side.squares(PieceType::B, true).count() >= 2 { 30 } else { 0 };
+    value + bishop_pair
+}
+
+/// Scores the placement of the pieces, blending the middlegame and endgame tables of the king by the phase of the game.
+fn piece_squares(side: &Sides, phase: i32) -> i32 {
+    let mut score = 0;
+    for (sq, piece) in side.position.content.iter().enumerate() {
+        let Some(Piece(piece_type, color)) = *piece else { continue };
+        if color != side.color {
+            continue;
+        }
+        let index = (7 - side.relative_rank(sq)) * 8 + sq % 8;
+        score += match piece_type {
+            PieceType::P => PAWN_TABLE[index],
+            PieceType::N => KNIGHT_TABLE[index],
+            PieceType::B => BISHOP_TABLE[index],
+            PieceType::R => ROOK_TABLE[index],
+            PieceType::Q => QUEEN_TABLE[index],
+            PieceType::K => (KING_MIDDLEGAME_TABLE[index] * phase + KING_ENDGAME_TABLE[index] * (MAX_PHASE - phase)) / MAX_PHASE,
+        };
+    }
+    score
+}
+
+/// Penalizes doubled and isolated pawns, and rewards passed pawns by how far they have advanced.
+fn pawn_structure(side: &Sides) -> i32 {
+    let own: Vec<_> = side.squares(PieceType::P, true).collect();
+    let enemy: Vec<_> = side.squares(PieceType::P, false).collect();
+    let mut files = [0; 8];
+    for &sq in &own {
+        files[sq % 8] += 1;
+    }
+    let mut score: i32 = files.iter().filter(|&&n| n > 1).map(|n| -15 * (n - 1)).sum();
+    for &sq in &own {
+        let file = sq % 8;
+        let adjacent = [file.wrapping_sub(1), file + 1];
+        if adjacent.iter().all(|&f| f >= 8 || files[f] == 0) {
+            score -= 15;
+        }
+        let passed = !enemy.iter().any(|&e| (e % 8).abs_diff(file) <= 1 && side.relative_rank(e) > side.relative_rank(sq));
+        if passed {
+            score += 10 + 10 * side.relative_rank(sq) as i32;
+        }
+    }
+    score
+}
+
+/// Rewards the number of pseudolegal moves of the knights, bishops, rooks and queens.
+fn mobility(side: &Sides) -> i32 {
+    side.moves
+        .iter()
+        .map(|m| match side.position.content[m.0].map(|p| p.0) {
+            Some(PieceType::N) => 4,
+            Some(PieceType::B) => 5,
+            Some(PieceType::R) => 2,
+            Some(PieceType::Q) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Rewards pawns sheltering the king and penalizes attacked squares around it, in proportion to the phase of the game.
+fn king_safety(side: &Sides, phase: i32) -> i32 {
+    let Some(king) = side.squares(PieceType::K, true).next() else { return 0 };
+    let (file, rank) = ((king % 8) as isize, (king / 8) as isize);
+    let forward = if side.color == Color::White { 1 } else { -1 };
+    let mut score = 0;
+    for f in (file - 1..=file + 1).filter(|f| (0..8).contains(f)) {
+        for (distance, bonus) in [(1, 10), (2, 5)] {
+            let r = rank + forward * distance;
+            if (0..8).contains(&r) && side.position.content[(r * 8 + f) as usize] == Some(Piece(PieceType::P, side.color)) {
+                score += bonus;
+                break;
+            }
+        }
+        for r in (rank - 1..=rank + 1).filter(|r| (0..8).contains(r)) {
+            if side.enemy_attacks[(r * 8 + f) as usize] {
+                score -= 10;
+            }
+        }
+    }
+    score * phase / MAX_PHASE
+}
@@ -159,3 +159,14 @@ pub fn as_legal(move_: Move, legal: &[Move]) -> Option<Move> {
         None
     }
 }
+
+/// Returns the rook involved in a castling move, or `None` if the move is not castling.
+#[cfg(any(feature = "uci", feature = "polyglot"))]
+pub fn castling_rook(move_: Move, position: &Position) -> Option<usize> {
+    let offset = if position.side.is_white() { 0 } else { 2 };
+    match move_.2 {
+        Some(SpecialMoveType::CastlingKingside) => position.castling_rights[offset],
+        Some(SpecialMoveType::CastlingQueenside) => position.castling_rights[offset + 1],
+        _ => None,
+    }
+}
@@ -3,19 +3,39 @@
 //! Examples are available on the [GitHub repository page](https://github.com/Python3-8/rschess).
 
 mod board;
+#[cfg(feature = "cecp")]
+pub mod cecp;
+mod descriptive;
+#[cfg(feature = "eco")]
+pub mod eco;
+#[cfg(feature = "engine")]
+pub mod engine;
 pub mod errors;
+pub mod evaluation;
 mod fen;
 mod helpers;
 #[cfg(feature = "img")]
 pub mod img;
 #[cfg(feature = "pgn")]
 pub mod pgn;
+#[cfg(feature = "polyglot")]
+pub mod polyglot;
 mod position;
+#[cfg(feature = "problem")]
+pub mod problem;
+#[cfg(feature = "puzzle")]
+pub mod puzzle;
+mod retro;
+#[cfg(feature = "tablebase")]
+pub mod tablebase;
+#[cfg(feature = "uci")]
+pub mod uci;
 
 pub use board::Board;
 pub(crate) use errors::*;
 pub use fen::Fen;
 pub use position::Position;
+pub use retro::Unmove;
 use std::{collections::HashMap, fmt, ops::Not};
 
 /// Converts a square index (`0..64`) to a square name, returning an error if the square index is invalid.
@@ -204,6 +224,40 @@ impl Move {
             }
         )
     }
+
+    /// Creates a `Move` object from its ICCF numeric representation (e.g. `5254` for e2e4, or `57581` for e7e8 promoting to a queen).
+    pub fn from_iccf(iccf: &str) -> Result<Self, InvalidIccfError> {
+        let digits: Vec<_> = iccf.chars().collect();
+        if ![4, 5].contains(&digits.len()) {
+            return Err(InvalidIccfError::Length);
+        }
+        let square = |file: char, rank: char| match (file.to_digit(10), rank.to_digit(10)) {
+            (Some(f @ 1..=8), Some(r @ 1..=8)) => Ok((r as usize - 1) * 8 + f as usize - 1),
+            _ => Err(InvalidIccfError::InvalidSquare(file, rank)),
+        };
+        let (src, dest) = (square(digits[0], digits[1])?, square(digits[2], digits[3])?);
+        let spec = match digits.get(4) {
+            Some('1') => SpecialMoveType::Promotion(PieceType::Q),
+            Some('2') => SpecialMoveType::Promotion(PieceType::R),
+            Some('3') => SpecialMoveType::Promotion(PieceType::B),
+            Some('4') => SpecialMoveType::Promotion(PieceType::N),
+            Some(&c) => return Err(InvalidIccfError::InvalidPromotion(c)),
+            None => SpecialMoveType::Unclear,
+        };
+        Ok(Self(src, dest, Some(spec)))
+    }
+
+    /// Returns the ICCF numeric representation of the move, in which castling is written as the move of the king.
+    pub fn to_iccf(&self) -> String {
+        let promotion = match self.2 {
+            Some(SpecialMoveType::Promotion(PieceType::Q)) => "1",
+            Some(SpecialMoveType::Promotion(PieceType::R)) => "2",
+            Some(SpecialMoveType::Promotion(PieceType::B)) => "3",
+            Some(SpecialMoveType::Promotion(PieceType::N)) => "4",
+            _ => "",
+        };
+        format!("{}{}{}{}{promotion}", self.0 % 8 + 1, self.0 / 8 + 1, self.1 % 8 + 1, self.1 / 8 + 1)
+    }
 }
 
 impl fmt::Display for Move {
@@ -213,6 +267,92 @@ impl fmt::Display for Move {
     }
 }
 
+/// Represents the notations in which movetext can be generated.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub enum MoveNotation {
+    /// Standard algebraic notation (e.g. `Nf3`)
+    #[default]
+    San,
+    /// Long algebraic notation (e.g. `Ng1-f3`)
+    Lan,
+    /// English descriptive notation (e.g. `N-KB3`), in which piece letters are not localized
+    Descriptive,
+    /// ICCF numeric notation (e.g. `7163`)
+    Iccf,
+}
+
+/// Represents the piece letters (or symbols) used for kings, queens, rooks, bishops and knights when writing and reading moves in
+/// algebraic notation. Pawns are never written with a letter.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct NotationLocale([char; 5]);
+
+impl NotationLocale {
+    /// English piece letters (K, Q, R, B, N)
+    pub const ENGLISH: Self = Self(['K', 'Q', 'R', 'B', 'N']);
+    /// German piece letters (K, D, T, L, S)
+    pub const GERMAN: Self = Self(['K', 'D', 'T', 'L', 'S']);
+    /// French piece letters (R, D, T, F, C)
+    pub const FRENCH: Self = Self(['R', 'D', 'T', 'F', 'C']);
+    /// Spanish piece letters (R, D, T, A, C)
+    pub const SPANISH: Self = Self(['R', 'D', 'T', 'A', 'C']);
+    /// Italian piece letters (R, D, T, A, C)
+    pub const ITALIAN: Self = Self(['R', 'D', 'T', 'A', 'C']);
+    /// Portuguese piece letters (R, D, T, B, C)
+    pub const PORTUGUESE: Self = Self(['R', 'D', 'T', 'B', 'C']);
+    /// Dutch piece letters (K, D, T, L, P)
+    pub const DUTCH: Self = Self(['K', 'D', 'T', 'L', 'P']);
+    /// Swedish piece letters (K, D, T, L, S)
+    pub const SWEDISH: Self = Self(['K', 'D', 'T', 'L', 'S']);
+    /// Polish piece letters (K, H, W, G, S)
+    pub const POLISH: Self = Self(['K', 'H', 'W', 'G', 'S']);
+    /// Unicode figurines (♔, ♕, ♖, ♗, ♘), which are used for both sides. When reading moves, the black figurines are accepted as well.
+    pub const FIGURINE: Self = Self(['\u{2654}', '\u{2655}', '\u{2656}', '\u{2657}', '\u{2658}']);
+
+    /// Constructs a `NotationLocale` from the letters for kings, queens, rooks, bishops and knights, returning an error if the letters
+    /// are not distinct, or could be confused with other parts of a move (lowercase ASCII letters, digits, `O` and punctuation).
+    pub fn new(king: char, queen: char, rook: char, bishop: char, knight: char) -> Result<Self, InvalidNotationLocaleError> {
+        let letters = [king, queen, rook, bishop, knight];
+        for (i, &c) in letters.iter().enumerate() {
+            if c.is_ascii_lowercase() || c.is_ascii_digit() || c.is_ascii_punctuation() || c.is_whitespace() || c == 'O' {
+                return Err(InvalidNotationLocaleError::InvalidLetter(c));
+            }
+            if letters[..i].contains(&c) {
+                return Err(InvalidNotationLocaleError::DuplicateLetter(c));
+            }
+        }
+        Ok(Self(letters))
+    }
+
+    /// Returns the letter used for the given type of piece (`None` for pawns).
+    pub fn letter(&self, piece_type: PieceType) -> Option<char> {
+        match piece_type {
+            PieceType::K => Some(self.0[0]),
+            PieceType::Q => Some(self.0[1]),
+            PieceType::R => Some(self.0[2]),
+            PieceType::B => Some(self.0[3]),
+            PieceType::N => Some(self.0[4]),
+            PieceType::P => None,
+        }
+    }
+
+    /// Returns the type of piece represented by the given letter (`None` if the letter is not used in this locale).
+    pub fn piece_type(&self, letter: char) -> Option<PieceType> {
+        let letter = match letter {
+            '\u{265A}'..='\u{265E}' if self == &Self::FIGURINE => char::from_u32(letter as u32 - 6).unwrap(),
+            _ => letter,
+        };
+        let idx = self.0.iter().position(|&c| c == letter)?;
+        Some([PieceType::K, PieceType::Q, PieceType::R, PieceType::B, PieceType::N][idx])
+    }
+}
+
+impl Default for NotationLocale {
+    /// Returns the English piece letters.
+    fn default() -> Self {
+        Self::ENGLISH
+    }
+}
+
 /// Represents game results.
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
 pub enum GameResult {
@@ -259,6 +399,15 @@ pub enum DrawType {
     Agreement,
 }
 
+/// Represents an evaluation score, either in centipawns or as a number of moves to checkmate.
+/// The side from whose perspective the score is given depends on where it is used.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Score {
+    Centipawns(i32),
+    /// Represents a forced checkmate in the given number of moves, where a negative number means the side is getting mated.
+    Mate(i32),
+}
+
 /// Represents a side/color.
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
 pub enum Color {
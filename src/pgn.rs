@@ -1,230 +1,1418 @@
 //! Handles PGN generation and manipulation.
 
-use super::{Board, Color, Fen, GameResult, InvalidPgnError};
-use regex::Regex;
-use std::{collections::HashMap, fmt};
+use super::{helpers, Board, Color, Fen, GameResult, IllegalMoveError, InvalidPgnError, Move, Position, Score};
+use std::{
+    fmt,
+    io::{self, Write},
+    iter::Peekable,
+    str::Chars,
+    time::Duration,
+};
 
 const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const STANDARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const EXPORT_LINE_WIDTH: usize = 80;
+
+/// The result of parsing a single game of a PGN text containing multiple games
+pub type ParsedGame = Result<(Pgn, Vec<PgnWarning>), InvalidPgnError>;
 
 /// Represents PGN (Portable Game Notation).
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Pgn {
-    tag_pairs: HashMap<String, String>,
+    /// The tag pairs, in the order in which they were read or added
+    tag_pairs: Vec<(String, String)>,
     board: Board,
+    /// The comment that precedes the first move, along with the commands embedded in it
+    initial_annotation: Annotation,
+    /// The annotations of each move of the game, in order
+    annotations: Vec<Annotation>,
 }
 
 impl Pgn {
-    /// Tokenizes PGN text.
-    fn tokenize(text: &str) -> Vec<Token> {
-        let tag_pair_regex = Regex::new(r#"\[(?<name>[A-Za-z]+)\s*"(?<value>((\\\\)|(\\")|[^"\\])*)"\]"#).unwrap();
-        let fullmove_san_regex = Regex::new(r"(?<move_number>\d+)\.\s*(?<white_move>((O-O(-O)?)|(0-0(-0)?)|([a-h]((x[a-h][1-8])|[1-8])(=[QRBN])?)|([QRBN](([a-h][1-8]x?[a-h][1-8])|([1-8]x?[a-h][1-8])|([a-h]x?[a-h][1-8])|(x?[a-h][1-8])))|(Kx?[a-h][1-8]))\+?)\s+(?<black_move>((O-O(-O)?)|(0-0(-0)?)|([a-h]((x[a-h][1-8])|[1-8])(=[QRBN])?)|([QRBN](([a-h][1-8]x?[a-h][1-8])|([1-8]x?[a-h][1-8])|([a-h]x?[a-h][1-8])|(x?[a-h][1-8])))|(Kx?[a-h][1-8]))[+#]?)").unwrap();
-        let halfmove_san_regex = Regex::new(r"(?<move_number>\d+)\.\s*(?<halfmove>((O-O(-O)?)|(0-0(-0)?)|([a-h]((x[a-h][1-8])|[1-8])(=[QRBN])?)|([QRBN](([a-h][1-8]x?[a-h][1-8])|([1-8]x?[a-h][1-8])|([a-h]x?[a-h][1-8])|(x?[a-h][1-8])))|(Kx?[a-h][1-8]))[+#]?)(\s*$|\s+\d)").unwrap();
-        let result_regex = Regex::new(r"^(\n|.)*(?<white_score>0|1\/2|1)-(?<black_score>0|1\/2|1)\s*$").unwrap();
+    /// Tokenizes PGN text, returning the tokens along with the location of the end of the text. If `descriptive` is `true`,
+    /// parenthesized qualifiers of moves in descriptive notation (`R(1)-Q1`, `P-K8(Q)`) are lexed as parts of symbols.
+    /// An error is returned if the text contains a character that cannot begin a token, or a string or comment that is never closed.
+    fn tokenize(text: &str, descriptive: bool) -> Result<(Vec<Token>, Location), InvalidPgnError> {
+        let mut lexer = Lexer::new(text, descriptive);
         let mut tokens = Vec::new();
-        for caps in tag_pair_regex.captures_iter(text) {
-            tokens.push(Token::TagPair(caps["name"].to_string(), caps["value"].replace(r"\\", r"\").replace(r#"\""#, r#"""#).to_string()));
-        }
-        for caps in fullmove_san_regex.captures_iter(text) {
-            tokens.push(Token::FullmoveSan(caps["move_number"].parse().unwrap(), caps["white_move"].to_string(), caps["black_move"].to_string()));
-        }
-        for caps in halfmove_san_regex.captures_iter(text) {
-            tokens.push(Token::HalfmoveSan(caps["move_number"].parse().unwrap(), caps["halfmove"].to_string()));
-        }
-        for caps in result_regex.captures_iter(text) {
-            tokens.push(Token::Result(caps["white_score"].to_string(), caps["black_score"].to_string()));
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
         }
-        tokens
+        Ok((tokens, lexer.location()))
     }
 
-    /// Parses PGN from a collection of PGN tokens.
-    /// This function currently does **not** support PGN annotations.
-    fn parse(tokens: Vec<Token>) -> Result<Pgn, InvalidPgnError> {
-        let mut tag_pairs_done = false;
-        let mut fullmove_san_done = false;
-        let mut halfmove_san_done = false;
-        let mut result_done = false;
-        let mut tag_pairs = HashMap::new();
-        let mut moves = Vec::new();
-        let mut result = None;
+    /// Parses PGN from a collection of PGN tokens, interpreting them according to the given import mode, and reading moves in SAN
+    /// or (if `descriptive` is `true`) in English descriptive notation.
+    /// Variations are accepted but discarded, as only the main line is played on the board.
+    fn parse(tokens: Vec<Token>, end: Location, mode: ImportMode, descriptive: bool) -> Result<(Pgn, Vec<PgnWarning>), InvalidPgnError> {
+        Parser::new(tokens, end, mode, descriptive).parse_game()
+    }
+
+    /// Attempts to parse a PGN text using the given import mode, returning an error if it is invalid.
+    /// In [`ImportMode::Lenient`], the problems that were fixed up while reading the text are returned as warnings.
+    pub fn parse_with_mode(text: &str, mode: ImportMode) -> Result<(Pgn, Vec<PgnWarning>), InvalidPgnError> {
+        let (tokens, end) = Self::tokenize(text, false)?;
+        Self::parse(tokens, end, mode, false)
+    }
+
+    /// Parses a PGN text containing any number of games (such as a database export) using the given import mode, returning the result of parsing each game.
+    /// Games end at their game termination marker, or (in lenient mode, for games without one) where the tag pairs of the next game begin.
+    /// An error is returned instead if the text cannot be tokenized, as the games following the problem could not be found.
+    pub fn parse_games(text: &str, mode: ImportMode) -> Result<Vec<ParsedGame>, InvalidPgnError> {
+        let (tokens, end) = Self::tokenize(text, false)?;
+        let mut games = Vec::new();
+        let mut game: Vec<Token> = Vec::new();
+        let mut depth = 0usize;
         for token in tokens {
-            match token {
-                Token::TagPair(name, value) => {
-                    if tag_pairs_done || fullmove_san_done || halfmove_san_done || result_done {
-                        return Err(InvalidPgnError::OrderOfElements("all tag pairs must be in the beginning of the text".to_owned()));
-                    }
-                    tag_pairs.insert(name, value);
-                }
-                Token::FullmoveSan(n, w, b) => {
-                    if n < 1 {
-                        return Err(InvalidPgnError::InvalidMoveNumber);
-                    }
-                    if fullmove_san_done || halfmove_san_done || result_done {
-                        return Err(InvalidPgnError::NoAnnotations);
-                    }
-                    if !tag_pairs_done {
-                        tag_pairs_done = true;
-                    }
-                    if let Some((prevn, _, _)) = moves.last() {
-                        if *prevn != n - 1 {
-                            return Err(InvalidPgnError::InvalidMoveNumber);
-                        }
-                    }
-                    moves.push((n, Some(w), Some(b)));
-                }
-                Token::HalfmoveSan(n, w) => {
-                    if n < 1 {
-                        return Err(InvalidPgnError::InvalidMoveNumber);
-                    }
-                    if halfmove_san_done || result_done {
-                        return Err(InvalidPgnError::NoAnnotations);
-                    }
-                    if !fullmove_san_done {
-                        fullmove_san_done = true;
-                    }
-                    if let Some((prevn, _, _)) = moves.last() {
-                        if *prevn != n - 1 {
-                            return Err(InvalidPgnError::InvalidMoveNumber);
-                        }
-                    }
-                    moves.push((n, Some(w), None));
-                }
-                Token::Result(w, b) => {
-                    if !halfmove_san_done {
-                        halfmove_san_done = true;
-                    }
-                    if result_done {
-                        return Err(InvalidPgnError::OrderOfElements("there can only be one game result".to_owned()));
-                    }
-                    result_done = true;
-                    result = Some((w, b));
-                }
+            let starts_tags = token.kind == TokenKind::LeftBracket && depth == 0 && game.last().is_some_and(|t| t.kind != TokenKind::RightBracket);
+            if starts_tags {
+                let location = token.location;
+                games.push(Self::parse(std::mem::take(&mut game), location, mode, false));
             }
-        }
-        if SEVEN_TAG_ROSTER.iter().any(|&k| !tag_pairs.contains_key(k)) {
-            return Err(InvalidPgnError::SevenTagRoster);
-        }
-        let mut board = match tag_pairs.get("FEN") {
-            Some(fen) => Board::from_fen(Fen::try_from(fen.as_str()).unwrap()),
-            _ => Board::default(),
-        };
-        for (_, w, b) in moves {
-            if let Some(m) = w {
-                board.make_move_san(&m).map_err(InvalidPgnError::InvalidMove)?;
+            let terminates = depth == 0
+                && match &token.kind {
+                    TokenKind::Asterisk => true,
+                    TokenKind::Symbol(s) => ["1-0", "0-1", "1/2-1/2"].contains(&s.as_str()),
+                    _ => false,
+                };
+            match token.kind {
+                TokenKind::LeftParenthesis => depth += 1,
+                TokenKind::RightParenthesis => depth = depth.saturating_sub(1),
+                _ => (),
             }
-            if let Some(m) = b {
-                board.make_move_san(&m).map_err(InvalidPgnError::InvalidMove)?;
+            game.push(token);
+            if terminates {
+                let location = game.last().unwrap().location;
+                games.push(Self::parse(std::mem::take(&mut game), location, mode, false));
             }
         }
-        match board.game_result() {
-            Some(GameResult::Wins(Color::White, _)) => {
-                if result != Some(("1".to_owned(), "0".to_owned())) {
-                    return Err(InvalidPgnError::InvalidResult("white has won on the board but the result is not 1-0".to_owned()));
-                }
-            }
-            Some(GameResult::Wins(Color::Black, _)) => {
-                if result != Some(("0".to_owned(), "1".to_owned())) {
-                    return Err(InvalidPgnError::InvalidResult("black has won on the board but the result is not 0-1".to_owned()));
-                }
-            }
-            Some(GameResult::Draw(_)) => {
-                if result != Some(("1/2".to_owned(), "1/2".to_owned())) {
-                    return Err(InvalidPgnError::InvalidResult("the game has been drawn but the result is not 1/2-1/2".to_owned()));
-                }
-            }
-            None => {
-                if let Some(res) = result {
-                    match (res.0.as_str(), res.1.as_str()) {
-                        ("1", "0") => board.resign(Color::Black).unwrap(),
-                        ("0", "1") => board.resign(Color::White).unwrap(),
-                        ("1/2", "1/2") => board.agree_draw().unwrap(),
-                        _ => return Err(InvalidPgnError::InvalidResult(format!("{}-{} is not a valid result", res.0, res.1))),
-                    }
-                }
-            }
+        if !game.is_empty() {
+            games.push(Self::parse(game, end, mode, false));
         }
-        Ok(Self { tag_pairs, board })
+        Ok(games)
+    }
+
+    /// Attempts to parse a PGN text whose movetext is written in English descriptive notation (e.g. `1. P-K4 P-K4 2. N-KB3`)
+    /// using the given import mode, returning an error if it is invalid. The moves are converted, so that the resulting `Pgn` is
+    /// exported in SAN. Parenthesized qualifiers such as `R(1)-Q1` and `P-K8(Q)` must not be preceded by whitespace, so that
+    /// they are not mistaken for variations.
+    pub fn parse_descriptive(text: &str, mode: ImportMode) -> Result<(Pgn, Vec<PgnWarning>), InvalidPgnError> {
+        let (tokens, end) = Self::tokenize(text, true)?;
+        Self::parse(join_descriptive_tokens(tokens), end, mode, true)
     }
 
     /// Constructs a `Pgn` object from a `Board`.
     /// Tag pairs must be provided following the [Seven Tag Roster](https://en.wikipedia.org/wiki/Portable_Game_Notation#Seven_Tag_Roster>),
     /// except the _Result_ tag which will be retrieved from the game state.
     pub fn from_board(board: Board, tag_pairs: Vec<(String, String)>) -> Result<Self, InvalidPgnError> {
-        let tag_pair_names = tag_pairs.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>();
-        let mut required_tags = SEVEN_TAG_ROSTER.iter().take(6);
-        if required_tags.any(|tag| !tag_pair_names.contains(tag)) {
-            return Err(InvalidPgnError::SevenTagRoster);
-        }
-        let mut tag_pairs_hm = HashMap::new();
+        Self::from_board_with_mode(board, tag_pairs, ImportMode::Strict).map(|(pgn, _)| pgn)
+    }
+
+    /// Constructs a `Pgn` object from a `Board` using the given import mode.
+    /// In [`ImportMode::Lenient`], tags of the [Seven Tag Roster](https://en.wikipedia.org/wiki/Portable_Game_Notation#Seven_Tag_Roster>)
+    /// that are not provided are filled in with unknown values, and reported as warnings.
+    pub fn from_board_with_mode(board: Board, tag_pairs: Vec<(String, String)>, mode: ImportMode) -> Result<(Self, Vec<PgnWarning>), InvalidPgnError> {
+        let mut warnings = Vec::new();
+        let annotations = vec![Annotation::default(); board.move_history().len()];
+        let mut pgn = Self {
+            board,
+            tag_pairs: Vec::new(),
+            initial_annotation: Annotation::default(),
+            annotations,
+        };
         for (name, value) in tag_pairs.into_iter() {
-            tag_pairs_hm.insert(name, value);
+            pgn.set_tag(&name, &value);
         }
-        tag_pairs_hm.insert(
-            "Result".to_owned(),
-            match board.game_result() {
-                Some(res) => res.to_string(),
-                None => "*".to_owned(),
-            },
-        );
-        Ok(Self { board, tag_pairs: tag_pairs_hm })
+        for &name in SEVEN_TAG_ROSTER.iter().take(6) {
+            if pgn.tag(name).is_none() {
+                if mode == ImportMode::Strict {
+                    return Err(InvalidPgnError::MissingRosterTag(name.to_owned()));
+                }
+                pgn.set_tag(name, unknown_tag_value(name));
+                warnings.push(PgnWarning::MissingTag(None, name.to_owned()));
+            }
+        }
+        let result = match pgn.board.game_result() {
+            Some(res) => res.to_string(),
+            None => "*".to_owned(),
+        };
+        pgn.set_tag("Result", &result);
+        Ok((pgn, warnings))
     }
 
-    /// Returns the PGN's tag pairs.
-    pub fn tag_pairs(&self) -> &HashMap<String, String> {
+    /// Returns the PGN's tag pairs, in the order in which they were read or added.
+    pub fn tag_pairs(&self) -> &[(String, String)] {
         &self.tag_pairs
     }
 
+    /// Returns the value of the tag with the given name (`None` if there is no such tag).
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tag_pairs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the value of a tag, keeping its position if it already exists, or adding it after all other tags otherwise.
+    /// Note that the _FEN_ and _SetUp_ tags are always generated from the initial position of the game when the PGN is exported.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tag_pairs.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_owned(),
+            None => self.tag_pairs.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Removes a tag, returning its value (`None` if there was no such tag).
+    /// Tags of the Seven Tag Roster that have been removed are exported with unknown values.
+    pub fn remove_tag(&mut self, name: &str) -> Option<String> {
+        let idx = self.tag_pairs.iter().position(|(n, _)| n == name)?;
+        Some(self.tag_pairs.remove(idx).1)
+    }
+
     /// Returns the game that the PGN represents.
     pub fn board(&self) -> &Board {
         &self.board
     }
+
+    /// Returns the text of the comment that precedes the first move of the game, excluding any recognized commands (`None` if there is no such text).
+    pub fn comment(&self) -> Option<&str> {
+        self.initial_annotation.comment.as_deref()
+    }
+
+    /// Sets the text of the comment that precedes the first move of the game.
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.initial_annotation.comment = comment;
+    }
+
+    /// Returns the annotation that precedes the first move of the game: the text of its comment and the commands embedded in it
+    /// (such as the clock times at the start of the game). It has no NAGs, as they must follow a move.
+    pub fn initial_annotation(&self) -> &Annotation {
+        &self.initial_annotation
+    }
+
+    /// Returns the annotation that precedes the first move of the game mutably.
+    pub fn initial_annotation_mut(&mut self) -> &mut Annotation {
+        &mut self.initial_annotation
+    }
+
+    /// Returns the annotations of the moves of the game, where the annotation at index `i` belongs to the move at index `i` of [`Board::move_history`].
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Returns the annotations of the moves of the game mutably, so that comments, NAGs, clock times, evaluations and markup can be added to them.
+    pub fn annotations_mut(&mut self) -> &mut [Annotation] {
+        &mut self.annotations
+    }
 }
 
 impl TryFrom<&str> for Pgn {
     type Error = InvalidPgnError;
 
-    /// Attempts to parse a PGN text, returning an error if it is invalid.
-    /// This function does **not** support PGN annotations.
+    /// Attempts to parse a PGN text in [`ImportMode::Strict`], returning an error if it is invalid.
+    /// Comments, NAGs and variations are accepted but discarded.
     /// Note that this function is not a PGN validator, meaning it may sometimes accept invalid PGN as valid.
     fn try_from(text: &str) -> Result<Pgn, Self::Error> {
-        Self::parse(Self::tokenize(text))
+        Self::parse_with_mode(text, ImportMode::Strict).map(|(pgn, _)| pgn)
     }
 }
 
 impl fmt::Display for Pgn {
-    /// Represents the `Pgn` object as PGN text.
+    /// Represents the `Pgn` object as PGN text in export format: the Seven Tag Roster comes first, followed by the _SetUp_ and _FEN_ tags
+    /// if the game does not start from the standard position, and then the remaining tags in order. The movetext is wrapped at 80 columns.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let game = GameTree::from(self);
+        let tokens = movetext_tokens(&game, &WriterOptions::default()).map_err(|_| fmt::Error)?;
+        ExportedGame::new(&self.tag_pairs, &game, tokens, Some(EXPORT_LINE_WIDTH)).fmt(f)
+    }
+}
+
+/// Represents a game as a tree of moves, in which any move may be followed by alternative moves (variations).
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct GameTree {
+    /// The tag pairs of the game
+    pub tag_pairs: Vec<(String, String)>,
+    /// The initial position of the game
+    pub initial_fen: Fen,
+    /// The comment that precedes the first move along with its commands, whose NAGs are not written
+    pub initial_annotation: Annotation,
+    /// The possible first moves, the first of which begins the main line
+    pub moves: Vec<GameNode>,
+    /// The result of the game (`None` if it is ongoing or unknown)
+    pub result: Option<GameResult>,
+}
+
+impl GameTree {
+    /// Constructs an empty `GameTree` starting from the given position.
+    pub fn new(initial_fen: Fen) -> Self {
+        Self {
+            tag_pairs: Vec::new(),
+            initial_fen,
+            initial_annotation: Annotation::default(),
+            moves: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Returns the moves of the main line, in order.
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut nodes = &self.moves;
+        while let Some(node) = nodes.first() {
+            moves.push(node.move_);
+            nodes = &node.continuations;
+        }
+        moves
+    }
+
+    /// Builds a tree consisting of a single line of annotated moves.
+    fn from_line(initial_fen: Fen, line: impl DoubleEndedIterator<Item = (Move, Annotation)>) -> Self {
+        let mut moves = Vec::new();
+        for (move_, annotation) in line.rev() {
+            moves = vec![GameNode {
+                move_,
+                annotation,
+                continuations: moves,
+            }];
+        }
+        Self { moves, ..Self::new(initial_fen) }
+    }
+}
+
+impl From<&Board> for GameTree {
+    /// Constructs a `GameTree` consisting of the moves played on a `Board`, without any tags or annotations.
+    fn from(board: &Board) -> Self {
+        let line = board.move_history().iter().map(|&m| (m, Annotation::default()));
+        Self {
+            result: board.game_result(),
+            ..Self::from_line(board.initial_fen().clone(), line)
+        }
+    }
+}
+
+impl From<&Pgn> for GameTree {
+    /// Constructs a `GameTree` consisting of the main line of a `Pgn`, along with its tags and annotations.
+    fn from(pgn: &Pgn) -> Self {
+        let line = pgn.board.move_history().iter().copied().zip(pgn.annotations.iter().cloned());
+        Self {
+            tag_pairs: pgn.tag_pairs.clone(),
+            initial_annotation: pgn.initial_annotation.clone(),
+            result: pgn.board.game_result(),
+            ..Self::from_line(pgn.board.initial_fen().clone(), line)
+        }
+    }
+}
+
+/// Represents a move in a `GameTree`, along with its annotation and the moves that may follow it.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct GameNode {
+    /// The move
+    pub move_: Move,
+    /// The annotation of the move
+    pub annotation: Annotation,
+    /// The possible replies to the move, the first of which continues the current line
+    pub continuations: Vec<GameNode>,
+}
+
+impl GameNode {
+    /// Constructs a `GameNode` for a move without any annotation or continuations.
+    pub fn new(move_: Move) -> Self {
+        Self {
+            move_,
+            annotation: Annotation::default(),
+            continuations: Vec::new(),
+        }
+    }
+}
+
+/// Represents the style in which move numbers are written in movetext.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub enum MoveNumberStyle {
+    /// Move numbers are written before white's moves, and before black's moves that begin a line or follow a comment or variation,
+    /// as required by the PGN export format.
+    #[default]
+    Standard,
+    /// Move numbers are written before every move.
+    EveryMove,
+    /// No move numbers are written (such movetext can only be read in [`ImportMode::Lenient`]).
+    Omitted,
+}
+
+/// Represents the options of a `PgnWriter`.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct WriterOptions {
+    /// The maximum length of a line of movetext (`None` to write the movetext of each game on a single line)
+    pub line_width: Option<usize>,
+    /// Whether comments (including embedded commands such as `[%clk ...]`) are written
+    pub comments: bool,
+    /// Whether variations are written
+    pub variations: bool,
+    /// The style in which move numbers are written
+    pub move_numbers: MoveNumberStyle,
+}
+
+impl Default for WriterOptions {
+    /// Constructs `WriterOptions` for the PGN export format: lines of at most 80 characters, with comments and variations.
+    fn default() -> Self {
+        Self {
+            line_width: Some(EXPORT_LINE_WIDTH),
+            comments: true,
+            variations: true,
+            move_numbers: MoveNumberStyle::Standard,
+        }
+    }
+}
+
+/// Writes games as PGN text to a `std::io::Write`, one after the other, separated by blank lines.
+#[derive(Debug)]
+pub struct PgnWriter<W: Write> {
+    writer: W,
+    options: WriterOptions,
+    /// The number of games written so far
+    games_written: usize,
+}
+
+impl<W: Write> PgnWriter<W> {
+    /// Constructs a `PgnWriter` that uses the default options.
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, WriterOptions::default())
+    }
+
+    /// Constructs a `PgnWriter` that uses the given options.
+    pub fn with_options(writer: W, options: WriterOptions) -> Self {
+        Self { writer, options, games_written: 0 }
+    }
+
+    /// Returns the options of the writer.
+    pub fn options(&self) -> &WriterOptions {
+        &self.options
+    }
+
+    /// Returns the number of games written so far.
+    pub fn games_written(&self) -> usize {
+        self.games_written
+    }
+
+    /// Writes the game played on a `Board` with the given tag pairs. Tags of the Seven Tag Roster that are not provided
+    /// are written with unknown values, and the _Result_ tag is always retrieved from the game state.
+    pub fn write_board(&mut self, board: &Board, tag_pairs: &[(String, String)]) -> io::Result<()> {
+        self.write_game(tag_pairs, &GameTree::from(board))
+    }
+
+    /// Writes the game represented by a `Pgn`, including its annotations.
+    pub fn write_pgn(&mut self, pgn: &Pgn) -> io::Result<()> {
+        self.write_game(&pgn.tag_pairs, &GameTree::from(pgn))
+    }
+
+    /// Writes the game represented by a `GameTree`, returning an error of kind [`io::ErrorKind::InvalidInput`] if the tree contains an illegal move.
+    pub fn write_game_tree(&mut self, game: &GameTree) -> io::Result<()> {
+        self.write_game(&game.tag_pairs, game)
+    }
+
+    /// Flushes the underlying writer and returns it.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_game(&mut self, tag_pairs: &[(String, String)], game: &GameTree) -> io::Result<()> {
+        let tokens = movetext_tokens(game, &self.options).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if self.games_written > 0 {
+            writeln!(self.writer)?;
+        }
+        writeln!(self.writer, "{}", ExportedGame::new(tag_pairs, game, tokens, self.options.line_width))?;
+        self.games_written += 1;
+        Ok(())
+    }
+}
+
+/// A game that is ready to be written as PGN text: its tag pair section followed by its movetext tokens, which are wrapped when written.
+struct ExportedGame<'a> {
+    tag_pairs: &'a [(String, String)],
+    initial_fen: &'a Fen,
+    result: String,
+    tokens: Vec<String>,
+    line_width: Option<usize>,
+}
+
+impl<'a> ExportedGame<'a> {
+    fn new(tag_pairs: &'a [(String, String)], game: &'a GameTree, tokens: Vec<String>, line_width: Option<usize>) -> Self {
+        Self {
+            tag_pairs,
+            initial_fen: &game.initial_fen,
+            result: result_marker(game.result),
+            tokens,
+            line_width,
+        }
+    }
+}
+
+impl fmt::Display for ExportedGame<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut pgn = String::new();
-        let mut tag_pairs = self.tag_pairs.clone();
-        tag_pairs.insert("FEN".to_owned(), self.board.initial_fen().to_string());
         for &name in &SEVEN_TAG_ROSTER {
-            tag_pairs.remove(name);
-            let line = format!(r#"[{name} "{}"]{}"#, self.tag_pairs.get(name).unwrap(), "\n");
-            pgn.push_str(&line);
-        }
-        let mut names: Vec<_> = tag_pairs.keys().collect();
-        names.sort();
-        for name in names {
-            let line = format!(r#"[{name} "{}"]{}"#, tag_pairs.get(name).unwrap(), "\n");
-            pgn.push_str(&line);
-        }
-        pgn.push('\n');
-        pgn.push_str(&self.board.gen_movetext());
-        pgn.push_str(&format!(
-            " {}",
-            match self.board.game_result() {
-                Some(res) => res.to_string(),
-                None => "*".to_owned(),
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag_pairs.iter().find(|(n, _)| n == name).map_or(unknown_tag_value(name), |(_, v)| v.as_str()),
+            };
+            write_tag_pair(f, name, value)?;
+        }
+        let initial_fen = self.initial_fen.to_string();
+        if initial_fen != STANDARD_START_FEN {
+            write_tag_pair(f, "SetUp", "1")?;
+            write_tag_pair(f, "FEN", &initial_fen)?;
+        }
+        for (name, value) in self.tag_pairs {
+            if !(SEVEN_TAG_ROSTER.contains(&name.as_str()) || name == "SetUp" || name == "FEN") {
+                write_tag_pair(f, name, value)?;
+            }
+        }
+        writeln!(f)?;
+        let mut line_len = 0;
+        for token in &self.tokens {
+            let token_len = token.chars().count();
+            if line_len > 0 {
+                if self.line_width.is_some_and(|w| line_len + 1 + token_len > w) {
+                    writeln!(f)?;
+                    line_len = 0;
+                } else {
+                    write!(f, " ")?;
+                    line_len += 1;
+                }
             }
-        ));
-        write!(f, "{pgn}")
+            write!(f, "{token}")?;
+            line_len += token_len;
+        }
+        Ok(())
     }
 }
 
-/// Represents a PGN token.
+/// Writes a tag pair as a line of PGN text, escaping backslashes and quotes in the value.
+fn write_tag_pair(f: &mut fmt::Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+    writeln!(f, "[{name} \"{}\"]", value.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+/// Returns the game termination marker for a game result.
+fn result_marker(result: Option<GameResult>) -> String {
+    match result {
+        Some(res) => res.to_string(),
+        None => "*".to_owned(),
+    }
+}
+
+/// Generates the tokens of the movetext of a game according to the given options, including the game termination marker.
+/// Tokens are joined with spaces when written, except that the parentheses of variations are attached to the tokens they enclose.
+fn movetext_tokens(game: &GameTree, options: &WriterOptions) -> Result<Vec<String>, IllegalMoveError> {
+    let mut tokens = Vec::new();
+    let comment = game.initial_annotation.to_comment();
+    if options.comments && !comment.is_empty() {
+        push_comment_tokens(&mut tokens, &comment);
+    }
+    let (position, fullmove_number) = (game.initial_fen.position().clone(), game.initial_fen.fullmove_number());
+    push_line_tokens(&mut tokens, &game.moves, position, fullmove_number, options)?;
+    tokens.push(result_marker(game.result));
+    Ok(tokens)
+}
+
+/// Generates the tokens of a line of moves starting from the given position, in which the first node continues the line
+/// and the rest are written as variations.
+fn push_line_tokens(tokens: &mut Vec<String>, mut nodes: &[GameNode], mut position: Position, mut fullmove_number: usize, options: &WriterOptions) -> Result<(), IllegalMoveError> {
+    let mut needs_number = true;
+    while let Some((node, alternatives)) = nodes.split_first() {
+        let move_ = helpers::as_legal(node.move_, &position.gen_non_illegal_moves()).ok_or(IllegalMoveError(node.move_))?;
+        let white = position.side_to_move().is_white();
+        match options.move_numbers {
+            MoveNumberStyle::Standard | MoveNumberStyle::EveryMove if white => tokens.push(format!("{fullmove_number}.")),
+            MoveNumberStyle::Standard if !needs_number => (),
+            MoveNumberStyle::Standard | MoveNumberStyle::EveryMove => tokens.push(format!("{fullmove_number}...")),
+            MoveNumberStyle::Omitted => (),
+        }
+        tokens.push(position.move_to_san(move_)?);
+        tokens.extend(node.annotation.nags.iter().map(|n| format!("${n}")));
+        needs_number = false;
+        if options.comments {
+            let comment = node.annotation.to_comment();
+            if !comment.is_empty() {
+                push_comment_tokens(tokens, &comment);
+                needs_number = true;
+            }
+        }
+        if options.variations {
+            for alternative in alternatives {
+                let start = tokens.len();
+                push_line_tokens(tokens, std::slice::from_ref(alternative), position.clone(), fullmove_number, options)?;
+                tokens[start].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+                needs_number = true;
+            }
+        }
+        position = position.with_move_made(move_)?;
+        if !white {
+            fullmove_number += 1;
+        }
+        nodes = &node.continuations;
+    }
+    Ok(())
+}
+
+/// Splits a brace comment into tokens at whitespace, so that it can be wrapped across lines.
+/// Closing braces are removed from the text, as a brace comment cannot contain them.
+fn push_comment_tokens(tokens: &mut Vec<String>, comment: &str) {
+    tokens.push("{".to_owned());
+    tokens.extend(comment.replace('}', " ").split_whitespace().map(str::to_owned));
+    tokens.push("}".to_owned());
+}
+
+/// Represents how strictly PGN text (or a `Board` with tag pairs) is interpreted when constructing a `Pgn`.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub enum ImportMode {
+    /// Follows the PGN specification, returning an error for any deviation from it.
+    #[default]
+    Strict,
+    /// Accepts the sloppy PGN commonly produced by websites and OCR tools, such as missing tags, missing or wrong move numbers,
+    /// lowercase piece letters, incomplete FEN tags and missing or inconsistent game termination markers.
+    /// Each problem that is fixed up is reported as a [`PgnWarning`].
+    Lenient,
+}
+
+/// Represents a problem that was fixed up while constructing a `Pgn` in [`ImportMode::Lenient`].
 #[derive(Eq, PartialEq, Clone, Debug)]
-enum Token {
-    TagPair(String, String),
-    FullmoveSan(usize, String, String),
-    HalfmoveSan(usize, String),
-    Result(String, String),
+pub enum PgnWarning {
+    /// A tag of the Seven Tag Roster (or the _FEN_ tag required by `[SetUp "1"]`) is missing; Seven Tag Roster tags are filled in with unknown values.
+    /// The location is `None` when the `Pgn` was not constructed from text.
+    MissingTag(Option<Location>, String),
+    /// The _FEN_ tag only contains the first four fields, so the halfmove clock and fullmove number were assumed to be 0 and 1.
+    IncompleteFen(Location),
+    /// A move was not preceded by a move number indication.
+    MissingMoveNumber(Location),
+    /// A move number indication did not match the number of the move that follows it.
+    InvalidMoveNumber(Location),
+    /// A move was written with a lowercase piece letter, and was interpreted as if the letter were uppercase.
+    LowercasePieceLetter(Location, String),
+    /// The movetext has no game termination marker, so the _Result_ tag was used instead.
+    MissingResult(Location),
+    /// The game termination marker contradicts the outcome of the game on the board, so it was ignored.
+    InconsistentResult(Location, String),
+}
+
+impl fmt::Display for PgnWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTag(Some(location), name) => write!(f, "at {location}: the '{name}' tag is missing"),
+            Self::MissingTag(None, name) => write!(f, "the '{name}' tag is missing"),
+            Self::IncompleteFen(location) => write!(f, "at {location}: the FEN is missing the halfmove clock and fullmove number"),
+            Self::MissingMoveNumber(location) => write!(f, "at {location}: expected a move number before this move"),
+            Self::InvalidMoveNumber(location) => write!(f, "at {location}: the move number does not match the number of the move that follows it"),
+            Self::LowercasePieceLetter(location, san) => write!(f, "at {location}: '{san}' is written with a lowercase piece letter"),
+            Self::MissingResult(location) => write!(f, "at {location}: expected a game termination marker"),
+            Self::InconsistentResult(location, msg) => write!(f, "at {location}: {msg}"),
+        }
+    }
+}
+
+/// Represents the annotations of a move: its NAGs, the text of the comments following it, and the commands embedded in those comments
+/// (`[%clk ...]`, `[%emt ...]`, `[%eval ...]`, `[%csl ...]` and `[%cal ...]`). Unrecognized commands are kept in the comment text.
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct Annotation {
+    /// The numeric annotation glyphs of the move
+    pub nags: Vec<u8>,
+    /// The comment text, excluding any recognized commands
+    pub comment: Option<String>,
+    /// The time left on the clock of the side that made the move (`%clk`)
+    pub clock: Option<Duration>,
+    /// The time spent on the move (`%emt`)
+    pub elapsed_move_time: Option<Duration>,
+    /// The evaluation of the position after the move, from white's perspective (`%eval`)
+    pub eval: Option<Evaluation>,
+    /// The squares highlighted after the move (`%csl`)
+    pub highlights: Vec<SquareHighlight>,
+    /// The arrows drawn after the move (`%cal`)
+    pub arrows: Vec<Arrow>,
+}
+
+impl Annotation {
+    /// Adds the text and commands of a comment to the annotation.
+    fn add_comment(&mut self, comment: &str) {
+        let mut text = String::new();
+        let mut rest = comment;
+        while let Some(start) = rest.find("[%") {
+            let len = match rest[start..].find(']') {
+                Some(len) => len,
+                None => break,
+            };
+            text.push_str(&rest[..start]);
+            if !self.apply_command(&rest[start + 2..start + len]) {
+                text.push_str(&rest[start..=start + len]);
+            }
+            text.push(' ');
+            rest = &rest[start + len + 1..];
+        }
+        text.push_str(rest);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            self.comment = Some(join_comments(self.comment.take(), &text));
+        }
+    }
+
+    /// Applies a comment command (without the surrounding `[%` and `]`), returning whether it was recognized and valid.
+    fn apply_command(&mut self, command: &str) -> bool {
+        let (name, args) = match command.trim().split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => return false,
+        };
+        match name {
+            "clk" => parse_duration(args).map(|d| self.clock = Some(d)).is_some(),
+            "emt" => parse_duration(args).map(|d| self.elapsed_move_time = Some(d)).is_some(),
+            "eval" => Evaluation::parse(args).map(|e| self.eval = Some(e)).is_some(),
+            "csl" => match args.split(',').map(|h| SquareHighlight::parse(h.trim())).collect::<Option<Vec<_>>>() {
+                Some(highlights) => {
+                    self.highlights.extend(highlights);
+                    true
+                }
+                None => false,
+            },
+            "cal" => match args.split(',').map(|a| Arrow::parse(a.trim())).collect::<Option<Vec<_>>>() {
+                Some(arrows) => {
+                    self.arrows.extend(arrows);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Generates the text of a comment representing the annotation (excluding NAGs), which is empty if there is nothing to write.
+    fn to_comment(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(comment) = &self.comment {
+            parts.push(comment.clone());
+        }
+        if !self.highlights.is_empty() {
+            parts.push(format!("[%csl {}]", self.highlights.iter().map(|h| h.to_string()).collect::<Vec<_>>().join(",")));
+        }
+        if !self.arrows.is_empty() {
+            parts.push(format!("[%cal {}]", self.arrows.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",")));
+        }
+        if let Some(eval) = &self.eval {
+            parts.push(format!("[%eval {eval}]"));
+        }
+        if let Some(emt) = self.elapsed_move_time {
+            parts.push(format!("[%emt {}]", format_duration(emt)));
+        }
+        if let Some(clock) = self.clock {
+            parts.push(format!("[%clk {}]", format_duration(clock)));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Represents an engine evaluation embedded in a PGN comment, from white's perspective.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Evaluation {
+    /// The score of the position
+    pub score: Score,
+    /// The depth at which the position was evaluated, if given
+    pub depth: Option<u32>,
+}
+
+impl Evaluation {
+    /// Parses the arguments of an `%eval` command, such as `0.31`, `-1.5,22` or `#-3`.
+    fn parse(args: &str) -> Option<Self> {
+        let (score, depth) = match args.split_once(',') {
+            Some((score, depth)) => (score.trim(), Some(depth.trim().parse().ok()?)),
+            None => (args, None),
+        };
+        let score = match score.strip_prefix('#') {
+            Some(mate) => Score::Mate(mate.parse().ok()?),
+            None => Score::Centipawns((score.parse::<f64>().ok()? * 100.).round() as i32),
+        };
+        Some(Self { score, depth })
+    }
+}
+
+impl fmt::Display for Evaluation {
+    /// Formats the evaluation as the arguments of an `%eval` command.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.score {
+            Score::Centipawns(cp) => write!(f, "{}{}.{:02}", if cp < 0 { "-" } else { "" }, cp.abs() / 100, cp.abs() % 100)?,
+            Score::Mate(n) => write!(f, "#{n}")?,
+        }
+        match self.depth {
+            Some(depth) => write!(f, ",{depth}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Represents the colors available for board markup in PGN comments.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum MarkupColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl TryFrom<char> for MarkupColor {
+    type Error = ();
+
+    /// Attempts to convert a markup color character (`R`, `G`, `B` or `Y`) to a `MarkupColor`.
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'R' => Ok(Self::Red),
+            'G' => Ok(Self::Green),
+            'B' => Ok(Self::Blue),
+            'Y' => Ok(Self::Yellow),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<MarkupColor> for char {
+    /// Converts a `MarkupColor` to a markup color character.
+    fn from(color: MarkupColor) -> char {
+        match color {
+            MarkupColor::Red => 'R',
+            MarkupColor::Green => 'G',
+            MarkupColor::Blue => 'B',
+            MarkupColor::Yellow => 'Y',
+        }
+    }
+}
+
+/// Represents a colored square highlight (`%csl`), with the square in the format (_file_, _rank_).
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct SquareHighlight {
+    pub color: MarkupColor,
+    pub square: (char, char),
+}
+
+impl SquareHighlight {
+    /// Parses a square highlight such as `Ge4`.
+    fn parse(text: &str) -> Option<Self> {
+        let mut chars = text.chars();
+        let color = MarkupColor::try_from(chars.next()?).ok()?;
+        let square = parse_square(&mut chars)?;
+        chars.next().is_none().then_some(Self { color, square })
+    }
+}
+
+impl fmt::Display for SquareHighlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", char::from(self.color), self.square.0, self.square.1)
+    }
+}
+
+/// Represents a colored arrow (`%cal`), with the squares in the format (_file_, _rank_).
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Arrow {
+    pub color: MarkupColor,
+    pub from: (char, char),
+    pub to: (char, char),
+}
+
+impl Arrow {
+    /// Parses an arrow such as `Ge2e4`.
+    fn parse(text: &str) -> Option<Self> {
+        let mut chars = text.chars();
+        let color = MarkupColor::try_from(chars.next()?).ok()?;
+        let from = parse_square(&mut chars)?;
+        let to = parse_square(&mut chars)?;
+        chars.next().is_none().then_some(Self { color, from, to })
+    }
+}
+
+impl fmt::Display for Arrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}{}{}", char::from(self.color), self.from.0, self.from.1, self.to.0, self.to.1)
+    }
+}
+
+/// Reads a square name from the given characters.
+fn parse_square(chars: &mut Chars) -> Option<(char, char)> {
+    let (file, rank) = (chars.next()?, chars.next()?);
+    (('a'..='h').contains(&file) && ('1'..='8').contains(&rank)).then_some((file, rank))
+}
+
+/// Parses a duration in the format `H:MM:SS`, optionally with a fractional part for the seconds.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let parts: Vec<_> = text.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let (hours, minutes): (u64, u64) = (parts[0].parse().ok()?, parts[1].parse().ok()?);
+    let seconds: f64 = parts[2].parse().ok()?;
+    if minutes >= 60 || !(0. ..60.).contains(&seconds) {
+        return None;
+    }
+    Some(Duration::from_secs(hours * 3600 + minutes * 60) + Duration::from_millis((seconds * 1000.).round() as u64))
+}
+
+/// Formats a duration in the format `H:MM:SS`, with a fractional part for the seconds only if necessary.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let mut text = format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    let millis = duration.subsec_millis();
+    if millis > 0 {
+        text.push_str(format!(".{millis:03}").trim_end_matches('0'));
+    }
+    text
+}
+
+/// Appends a comment to an existing comment, separating them with a space.
+fn join_comments(existing: Option<String>, comment: &str) -> String {
+    match existing {
+        Some(existing) => format!("{existing} {comment}"),
+        None => comment.to_owned(),
+    }
+}
+
+/// Returns the value used for a Seven Tag Roster tag whose actual value is unknown.
+fn unknown_tag_value(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
+
+/// Represents a location in PGN text, with line and column numbers starting from 1.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Location {
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    /// Returns the line number of the location.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column number of the location.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Joins `ch` and `mate` suffixes written apart from moves in descriptive notation to the moves, and drops `e.p.` suffixes.
+fn join_descriptive_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut joined: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let kinds: Vec<_> = tokens[i..].iter().take(4).map(|t| &t.kind).collect();
+        match kinds[..] {
+            [TokenKind::Symbol(e), TokenKind::Period, TokenKind::Symbol(p), TokenKind::Period] if e == "e" && p == "p" => i += 4,
+            [TokenKind::Symbol(suffix), ..] if (suffix == "ch" || suffix == "mate") && matches!(joined.last(), Some(Token { kind: TokenKind::Symbol(_), .. })) => {
+                if let Some(Token { kind: TokenKind::Symbol(s), .. }) = joined.last_mut() {
+                    s.push_str(suffix);
+                }
+                i += 1;
+            }
+            _ => {
+                joined.push(tokens[i].clone());
+                i += 1;
+            }
+        }
+    }
+    joined
+}
+
+/// Represents a PGN token along with its location in the text.
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    location: Location,
+}
+
+/// Represents the types of tokens described in section 7 of the PGN specification.
+#[derive(Eq, PartialEq, Clone, Debug)]
+enum TokenKind {
+    /// A string, with escape sequences already resolved
+    String(String),
+    /// A symbol, which includes integers, SAN moves, tag names and game termination markers (other than `*`)
+    Symbol(String),
+    /// A numeric annotation glyph, either written as `$n` or as one of the traditional suffix annotations (`!`, `?!`, etc.)
+    Nag(u8),
+    /// A brace comment or a rest-of-line comment
+    Comment(String),
+    Period,
+    Asterisk,
+    LeftBracket,
+    RightBracket,
+    LeftParenthesis,
+    RightParenthesis,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => write!(f, r#"string "{s}""#),
+            Self::Symbol(s) => write!(f, "'{s}'"),
+            Self::Nag(n) => write!(f, "NAG ${n}"),
+            Self::Comment(_) => write!(f, "comment"),
+            Self::Period => write!(f, "'.'"),
+            Self::Asterisk => write!(f, "'*'"),
+            Self::LeftBracket => write!(f, "'['"),
+            Self::RightBracket => write!(f, "']'"),
+            Self::LeftParenthesis => write!(f, "'('"),
+            Self::RightParenthesis => write!(f, "')'"),
+        }
+    }
+}
+
+/// Splits PGN text into tokens, keeping track of the line and column of each character.
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    /// Whether parenthesized qualifiers of moves in descriptive notation are lexed as parts of symbols
+    descriptive: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str, descriptive: bool) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            descriptive,
+        }
+    }
+
+    /// Returns the location of the next character.
+    fn location(&self) -> Location {
+        Location { line: self.line, column: self.column }
+    }
+
+    /// Consumes a character, updating the line and column numbers.
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    /// Consumes and returns a parenthesized qualifier of a move in descriptive notation, such as `(1)`, `(KB3)` or `(Q)`,
+    /// if one comes next (`None` otherwise, in which case nothing is consumed).
+    fn descriptive_qualifier(&mut self) -> Option<String> {
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+        let inner: String = lookahead.clone().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        if !(1..=3).contains(&inner.len()) || lookahead.nth(inner.len()) != Some(')') {
+            return None;
+        }
+        let qualifier = format!("({inner})");
+        for _ in 0..qualifier.len() {
+            self.bump();
+        }
+        Some(qualifier)
+    }
+
+    /// Consumes characters until the end of the current line, returning them (excluding the newline).
+    fn rest_of_line(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if ch == '\n' {
+                break;
+            }
+            text.push(ch);
+            self.bump();
+        }
+        text
+    }
+
+    /// Returns the next token, `None` at the end of the text, or an error if the text cannot be tokenized.
+    fn next_token(&mut self) -> Result<Option<Token>, InvalidPgnError> {
+        loop {
+            let location = self.location();
+            let ch = match self.chars.peek() {
+                Some(&ch) => ch,
+                None => return Ok(None),
+            };
+            let kind = match ch {
+                c if c.is_whitespace() => {
+                    self.bump();
+                    continue;
+                }
+                '%' if location.column == 1 => {
+                    self.rest_of_line();
+                    continue;
+                }
+                ';' => {
+                    self.bump();
+                    TokenKind::Comment(self.rest_of_line().trim().to_owned())
+                }
+                '{' => {
+                    self.bump();
+                    let mut comment = String::new();
+                    loop {
+                        match self.bump() {
+                            Some('}') => break,
+                            Some(c) => comment.push(c),
+                            None => return Err(InvalidPgnError::Unterminated(location, "comment".to_owned())),
+                        }
+                    }
+                    TokenKind::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                '"' => {
+                    self.bump();
+                    let mut string = String::new();
+                    loop {
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('\\') => match self.bump() {
+                                Some(c @ ('\\' | '"')) => string.push(c),
+                                Some(c) => {
+                                    string.push('\\');
+                                    string.push(c);
+                                }
+                                None => return Err(InvalidPgnError::Unterminated(location, "string".to_owned())),
+                            },
+                            Some('\n') | None => return Err(InvalidPgnError::Unterminated(location, "string".to_owned())),
+                            Some(c) => string.push(c),
+                        }
+                    }
+                    TokenKind::String(string)
+                }
+                '$' => {
+                    self.bump();
+                    let mut digits = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(c);
+                        self.bump();
+                    }
+                    TokenKind::Nag(digits.parse().map_err(|_| InvalidPgnError::InvalidNag(location, format!("${digits}")))?)
+                }
+                '!' | '?' => {
+                    let mut glyph = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if !['!', '?'].contains(&c) {
+                            break;
+                        }
+                        glyph.push(c);
+                        self.bump();
+                    }
+                    TokenKind::Nag(match glyph.as_str() {
+                        "!" => 1,
+                        "?" => 2,
+                        "!!" => 3,
+                        "??" => 4,
+                        "!?" => 5,
+                        "?!" => 6,
+                        _ => return Err(InvalidPgnError::InvalidNag(location, glyph)),
+                    })
+                }
+                c if c.is_ascii_alphanumeric() => {
+                    let mut symbol = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if c == '(' && self.descriptive {
+                            match self.descriptive_qualifier() {
+                                Some(qualifier) => {
+                                    symbol.push_str(&qualifier);
+                                    continue;
+                                }
+                                None => break,
+                            }
+                        }
+                        if !(c.is_ascii_alphanumeric() || "_+#=:-/".contains(c)) {
+                            break;
+                        }
+                        symbol.push(c);
+                        self.bump();
+                    }
+                    TokenKind::Symbol(symbol)
+                }
+                _ => {
+                    self.bump();
+                    match ch {
+                        '.' => TokenKind::Period,
+                        '*' => TokenKind::Asterisk,
+                        '[' => TokenKind::LeftBracket,
+                        ']' => TokenKind::RightBracket,
+                        '(' => TokenKind::LeftParenthesis,
+                        ')' => TokenKind::RightParenthesis,
+                        _ => return Err(InvalidPgnError::UnexpectedCharacter(location, ch)),
+                    }
+                }
+            };
+            return Ok(Some(Token { kind, location }));
+        }
+    }
+}
+
+/// Builds a `Pgn` from a sequence of tokens, playing the main line on a `Board` as it is read.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The location just past the end of the text
+    end: Location,
+    mode: ImportMode,
+    /// The problems that have been fixed up so far in lenient mode
+    warnings: Vec<PgnWarning>,
+    /// The comment that precedes the first move, along with its commands
+    initial_annotation: Annotation,
+    /// The annotations of the main line moves read so far
+    annotations: Vec<Annotation>,
+    /// Whether moves are written in English descriptive notation rather than SAN
+    descriptive: bool,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>, end: Location, mode: ImportMode, descriptive: bool) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            end,
+            mode,
+            warnings: Vec::new(),
+            initial_annotation: Annotation::default(),
+            annotations: Vec::new(),
+            descriptive,
+        }
+    }
+
+    /// Returns the given error in strict mode, or records the given warning in lenient mode.
+    fn tolerate(&mut self, warning: PgnWarning, error: InvalidPgnError) -> Result<(), InvalidPgnError> {
+        match self.mode {
+            ImportMode::Strict => Err(error),
+            ImportMode::Lenient => {
+                self.warnings.push(warning);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the next token without consuming it.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Consumes and returns the next token.
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Returns the location of the next token, or the end of the text if there are no more tokens.
+    fn location(&self) -> Location {
+        self.peek().map_or(self.end, |t| t.location)
+    }
+
+    /// Consumes the next token, returning an error if it is not of the expected kind.
+    fn expect(&mut self, kind: TokenKind, description: &str) -> Result<(), InvalidPgnError> {
+        match self.advance() {
+            Some(Token { kind: k, .. }) if k == kind => Ok(()),
+            Some(Token { kind: k, location }) => Err(InvalidPgnError::UnexpectedToken(location, format!("expected {description}, found {k}"))),
+            None => Err(InvalidPgnError::UnexpectedToken(self.end, format!("expected {description}, found the end of the text"))),
+        }
+    }
+
+    /// Parses the tag pair section, returning the tag pairs along with the locations of their values.
+    fn parse_tag_pairs(&mut self) -> Result<Vec<(String, String, Location)>, InvalidPgnError> {
+        let mut tag_pairs: Vec<(String, String, Location)> = Vec::new();
+        while let Some(Token { kind: TokenKind::LeftBracket, .. }) = self.peek() {
+            self.advance();
+            let name_location = self.location();
+            let name = match self.advance() {
+                Some(Token { kind: TokenKind::Symbol(s), .. }) => s,
+                Some(Token { kind, location }) => return Err(InvalidPgnError::UnexpectedToken(location, format!("expected a tag name, found {kind}"))),
+                None => return Err(InvalidPgnError::Unterminated(name_location, "tag pair".to_owned())),
+            };
+            let value_location = self.location();
+            let value = match self.advance() {
+                Some(Token { kind: TokenKind::String(s), .. }) => s,
+                Some(Token { kind, location }) => return Err(InvalidPgnError::UnexpectedToken(location, format!("expected a tag value, found {kind}"))),
+                None => return Err(InvalidPgnError::Unterminated(name_location, "tag pair".to_owned())),
+            };
+            self.expect(TokenKind::RightBracket, "']'")?;
+            if tag_pairs.iter().any(|(n, _, _)| n == &name) {
+                return Err(InvalidPgnError::DuplicateTag(name_location, name));
+            }
+            tag_pairs.push((name, value, value_location));
+        }
+        Ok(tag_pairs)
+    }
+
+    /// Parses a complete game: the tag pair section, the movetext, and the game termination marker.
+    fn parse_game(mut self) -> Result<(Pgn, Vec<PgnWarning>), InvalidPgnError> {
+        let mut tags = self.parse_tag_pairs()?;
+        let movetext_location = self.location();
+        for &name in &SEVEN_TAG_ROSTER {
+            if !tags.iter().any(|(n, _, _)| n == name) {
+                self.tolerate(
+                    PgnWarning::MissingTag(Some(movetext_location), name.to_owned()),
+                    InvalidPgnError::MissingTag(movetext_location, name.to_owned()),
+                )?;
+                tags.push((name.to_owned(), unknown_tag_value(name).to_owned(), movetext_location));
+            }
+        }
+        let mut board = match tags.iter().find(|(n, _, _)| n == "FEN") {
+            Some((_, fen, location)) => Board::from_fen(self.parse_fen(fen, *location)?),
+            _ => {
+                if let Some((_, _, location)) = tags.iter().find(|(n, v, _)| n == "SetUp" && v == "1") {
+                    self.tolerate(PgnWarning::MissingTag(Some(*location), "FEN".to_owned()), InvalidPgnError::MissingTag(*location, "FEN".to_owned()))?;
+                }
+                Board::default()
+            }
+        };
+        let (result, result_location) = match self.parse_movetext(&mut board)? {
+            Some(r) => r,
+            None => {
+                self.tolerate(PgnWarning::MissingResult(self.end), InvalidPgnError::MissingResult(self.end))?;
+                let tag = tags.iter().find(|(n, _, _)| n == "Result").map(|(_, v, _)| v.clone()).unwrap();
+                (if ["1-0", "0-1", "1/2-1/2"].contains(&tag.as_str()) { tag } else { "*".to_owned() }, self.end)
+            }
+        };
+        if let Some(token) = self.peek() {
+            return Err(InvalidPgnError::OrderOfElements(token.location, "nothing may follow the game termination marker".to_owned()));
+        }
+        let inconsistency = match board.game_result() {
+            Some(GameResult::Wins(Color::White, _)) if result != "1-0" => Some("white has won on the board but the result is not 1-0"),
+            Some(GameResult::Wins(Color::Black, _)) if result != "0-1" => Some("black has won on the board but the result is not 0-1"),
+            Some(GameResult::Draw(_)) if result != "1/2-1/2" => Some("the game has been drawn but the result is not 1/2-1/2"),
+            Some(_) => None,
+            None => {
+                match result.as_str() {
+                    "1-0" => board.resign(Color::Black).unwrap(),
+                    "0-1" => board.resign(Color::White).unwrap(),
+                    "1/2-1/2" => board.agree_draw().unwrap(),
+                    _ => (),
+                }
+                None
+            }
+        };
+        if let Some(msg) = inconsistency {
+            self.tolerate(
+                PgnWarning::InconsistentResult(result_location, msg.to_owned()),
+                InvalidPgnError::InvalidResult(result_location, msg.to_owned()),
+            )?;
+        }
+        let mut pgn = Pgn {
+            tag_pairs: tags.into_iter().map(|(n, v, _)| (n, v)).collect(),
+            board,
+            initial_annotation: std::mem::take(&mut self.initial_annotation),
+            annotations: std::mem::take(&mut self.annotations),
+        };
+        if self.mode == ImportMode::Lenient {
+            let result = match pgn.board.game_result() {
+                Some(res) => res.to_string(),
+                None => "*".to_owned(),
+            };
+            pgn.set_tag("Result", &result);
+        }
+        Ok((pgn, self.warnings))
+    }
+
+    /// Parses the value of a _FEN_ tag located at `location`.
+    fn parse_fen(&mut self, fen: &str, location: Location) -> Result<Fen, InvalidPgnError> {
+        match Fen::try_from(fen) {
+            Ok(fen) => Ok(fen),
+            Err(e) => {
+                if self.mode == ImportMode::Lenient && fen.split(' ').count() == 4 {
+                    if let Ok(fen) = Fen::try_from(format!("{fen} 0 1").as_str()) {
+                        self.warnings.push(PgnWarning::IncompleteFen(location));
+                        return Ok(fen);
+                    }
+                }
+                Err(InvalidPgnError::InvalidFen(location, e))
+            }
+        }
+    }
+
+    /// Parses the main line of the movetext, playing each move on the board, until the game termination marker,
+    /// which is returned along with its location (`None` if the text ends without one).
+    fn parse_movetext(&mut self, board: &mut Board) -> Result<Option<(String, Location)>, InvalidPgnError> {
+        let mut numbered = false;
+        let mut first_move = true;
+        loop {
+            let Token { kind, location } = match self.advance() {
+                Some(t) => t,
+                None => return Ok(None),
+            };
+            match kind {
+                TokenKind::Asterisk => return Ok(Some(("*".to_owned(), location))),
+                TokenKind::Symbol(s) if ["1-0", "0-1", "1/2-1/2"].contains(&s.as_str()) => return Ok(Some((s, location))),
+                TokenKind::Symbol(s) if s.chars().all(|c| c.is_ascii_digit()) => {
+                    if s.parse::<usize>().ok() != Some(board.fullmove_number()) {
+                        self.tolerate(PgnWarning::InvalidMoveNumber(location), InvalidPgnError::InvalidMoveNumber(location))?;
+                    }
+                    while let Some(Token { kind: TokenKind::Period, .. }) = self.peek() {
+                        self.advance();
+                    }
+                    numbered = true;
+                }
+                TokenKind::Symbol(san) => {
+                    if !numbered && (board.side_to_move().is_white() || first_move) {
+                        self.tolerate(PgnWarning::MissingMoveNumber(location), InvalidPgnError::MissingMoveNumber(location))?;
+                    }
+                    if self.descriptive {
+                        let move_ = board.descriptive_to_move(&san).map_err(|e| InvalidPgnError::InvalidDescriptiveMove(location, e))?;
+                        board.make_move(move_).unwrap();
+                    } else if let Err(e) = board.make_move_san(&san) {
+                        let mut chars = san.chars();
+                        let capitalized = match chars.next() {
+                            Some(c @ ('k' | 'q' | 'r' | 'b' | 'n')) if self.mode == ImportMode::Lenient => format!("{}{}", c.to_ascii_uppercase(), chars.as_str()),
+                            _ => return Err(InvalidPgnError::InvalidMove(location, e)),
+                        };
+                        board.make_move_san(&capitalized).map_err(|_| InvalidPgnError::InvalidMove(location, e))?;
+                        self.warnings.push(PgnWarning::LowercasePieceLetter(location, san));
+                    }
+                    self.annotations.push(Annotation::default());
+                    numbered = false;
+                    first_move = false;
+                }
+                TokenKind::Nag(nag) => match self.annotations.last_mut() {
+                    Some(annotation) => annotation.nags.push(nag),
+                    None => return Err(InvalidPgnError::OrderOfElements(location, "a NAG must follow a move".to_owned())),
+                },
+                TokenKind::Comment(comment) => match self.annotations.last_mut() {
+                    Some(annotation) => annotation.add_comment(&comment),
+                    None => self.initial_annotation.add_comment(&comment),
+                },
+                TokenKind::LeftParenthesis => {
+                    if first_move {
+                        return Err(InvalidPgnError::OrderOfElements(location, "a variation must follow a move".to_owned()));
+                    }
+                    self.skip_variation(location)?;
+                    numbered = false;
+                }
+                TokenKind::LeftBracket => {
+                    return Err(InvalidPgnError::OrderOfElements(location, "all tag pairs must be in the beginning of the text".to_owned()));
+                }
+                kind => return Err(InvalidPgnError::UnexpectedToken(location, format!("unexpected {kind} in movetext"))),
+            }
+        }
+    }
+
+    /// Skips over a (possibly nested) variation whose opening parenthesis is at `start`, which has already been consumed.
+    fn skip_variation(&mut self, start: Location) -> Result<(), InvalidPgnError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some(Token { kind: TokenKind::LeftParenthesis, .. }) => depth += 1,
+                Some(Token {
+                    kind: TokenKind::RightParenthesis, ..
+                }) => depth -= 1,
+                Some(Token {
+                    kind: TokenKind::LeftBracket | TokenKind::RightBracket | TokenKind::String(_),
+                    location,
+                }) => return Err(InvalidPgnError::OrderOfElements(location, "variations may only contain movetext".to_owned())),
+                Some(_) => (),
+                None => return Err(InvalidPgnError::Unterminated(start, "variation".to_owned())),
+            }
+        }
+        Ok(())
+    }
 }
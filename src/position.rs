@@ -1,4 +1,4 @@
-use super::{helpers, Color, IllegalMoveError, InvalidSanMoveError, Move, Piece, PieceType, SpecialMoveType};
+use super::{helpers, Color, IllegalMoveError, InvalidLanMoveError, InvalidSanMoveError, Move, NotationLocale, Piece, PieceType, SpecialMoveType};
 use std::{
     collections::HashMap,
     fmt,
@@ -104,6 +104,11 @@ impl Position {
 
     /// Converts a `Move` to SAN, returning an error if the move is illegal.
     pub fn move_to_san(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        self.move_to_san_in(move_, &NotationLocale::ENGLISH)
+    }
+
+    /// Converts a `Move` to SAN using the piece letters of the given locale, returning an error if the move is illegal.
+    pub fn move_to_san_in(&self, move_: Move, locale: &NotationLocale) -> Result<String, IllegalMoveError> {
         let legal = self.gen_non_illegal_moves();
         let move_ = match helpers::as_legal(move_, &legal) {
             Some(m) => m,
@@ -114,14 +119,7 @@ impl Position {
         let Self { content, .. } = self;
         let (src_occ, dest_occ) = (content[src], content[dest]);
         let ((srcf, srcr), (destf, destr)) = (helpers::idx_to_sq(src), helpers::idx_to_sq(dest));
-        let new_content = self.with_move_made(move_).unwrap();
-        let suffix = if new_content.is_checkmate() {
-            "#"
-        } else if new_content.is_check() {
-            "+"
-        } else {
-            ""
-        };
+        let suffix = self.check_suffix(move_);
         let piece_type;
         match src_occ {
             Some(Piece(pt, _)) => match pt {
@@ -137,7 +135,7 @@ impl Position {
                                     None => format!("{destf}{destr}"),
                                 },
                                 match spec {
-                                    Some(SpecialMoveType::Promotion(piece_type)) => format!("={}", char::from(piece_type)),
+                                    Some(SpecialMoveType::Promotion(piece_type)) => format!("={}", locale.letter(piece_type).unwrap()),
                                     _ => String::new(),
                                 }
                             ),
@@ -151,7 +149,8 @@ impl Position {
                             Some(SpecialMoveType::CastlingKingside) => "O-O".to_owned(),
                             Some(SpecialMoveType::CastlingQueenside) => "O-O-O".to_owned(),
                             _ => format!(
-                                "K{}{destf}{destr}",
+                                "{}{}{destf}{destr}",
+                                locale.letter(PieceType::K).unwrap(),
                                 match dest_occ {
                                     Some(_) => "x",
                                     None => "",
@@ -161,7 +160,7 @@ impl Position {
                     ))
                 }
                 pt => {
-                    san.push(char::from(pt));
+                    san.push(locale.letter(pt).unwrap());
                     piece_type = pt;
                 }
             },
@@ -231,21 +230,114 @@ impl Position {
         ))
     }
 
-    /// Constructs a `Move` from a SAN representation, returning an error if it is invalid or illegal.
-    pub fn san_to_move(&self, san: &str) -> Result<Move, InvalidSanMoveError> {
-        let san = san.trim().replace('0', "O").replace(['+', '#'], "");
-        self.gen_non_illegal_moves()
-            .into_iter()
-            .find(|&m| self.move_to_san(m).unwrap().replace(['+', '#'], "") == san)
-            .ok_or(InvalidSanMoveError(san.to_owned()))
+    /// Converts a `Move` to long algebraic notation (e.g. `Ng1-f3`, `e4xd5` or `e7-e8=Q`), returning an error if the move is illegal.
+    pub fn move_to_lan(&self, move_: Move) -> Result<String, IllegalMoveError> {
+        self.move_to_lan_in(move_, &NotationLocale::ENGLISH)
     }
 
-    /// Returns the position which would occur if the given move were played, returning an error if the move is illegal.
-    pub fn with_move_made(&self, move_: Move) -> Result<Self, IllegalMoveError> {
+    /// Converts a `Move` to long algebraic notation using the piece letters of the given locale, returning an error if the move is illegal.
+    pub fn move_to_lan_in(&self, move_: Move, locale: &NotationLocale) -> Result<String, IllegalMoveError> {
         let move_ = match helpers::as_legal(move_, &self.gen_non_illegal_moves()) {
             Some(m) => m,
             _ => return Err(IllegalMoveError(move_)),
         };
+        let Move(src, dest, spec) = move_;
+        let suffix = self.check_suffix(move_);
+        let lan = match spec {
+            Some(SpecialMoveType::CastlingKingside) => "O-O".to_owned(),
+            Some(SpecialMoveType::CastlingQueenside) => "O-O-O".to_owned(),
+            _ => {
+                let ((srcf, srcr), (destf, destr)) = (helpers::idx_to_sq(src), helpers::idx_to_sq(dest));
+                let piece = match self.content[src] {
+                    Some(Piece(pt, _)) => locale.letter(pt).map_or(String::new(), String::from),
+                    None => panic!("the universe is malfunctioning"),
+                };
+                let separator = if self.is_capture(move_).unwrap() { 'x' } else { '-' };
+                let promotion = match spec {
+                    Some(SpecialMoveType::Promotion(pt)) => format!("={}", locale.letter(pt).unwrap()),
+                    _ => String::new(),
+                };
+                format!("{piece}{srcf}{srcr}{separator}{destf}{destr}{promotion}")
+            }
+        };
+        Ok(format!("{lan}{suffix}"))
+    }
+
+    /// Constructs a `Move` from a long algebraic notation representation, returning an error if it cannot be parsed, or is illegal or ambiguous.
+    /// The capture marker and hyphen are optional, and the promotion, check and annotation glyph forms accepted by [`Position::san_to_move`]
+    /// are accepted as well.
+    pub fn lan_to_move(&self, lan: &str) -> Result<Move, InvalidLanMoveError> {
+        self.lan_to_move_in(lan, &NotationLocale::ENGLISH)
+    }
+
+    /// Constructs a `Move` from a long algebraic notation representation that uses the piece letters of the given locale,
+    /// returning an error if it cannot be parsed, or is illegal or ambiguous.
+    pub fn lan_to_move_in(&self, lan: &str, locale: &NotationLocale) -> Result<Move, InvalidLanMoveError> {
+        let text = strip_move_suffixes(lan);
+        let legal = self.gen_non_illegal_moves();
+        let candidates: Vec<_> = match castling_type(text) {
+            Some(castling) => legal.into_iter().filter(|m| m.2 == Some(castling)).collect(),
+            None => match SanComponents::parse(text, locale) {
+                Some(components) if components.src_file.is_some() && components.src_rank.is_some() => legal.into_iter().filter(|&m| components.matches(m, &self.content)).collect(),
+                _ => return Err(InvalidLanMoveError::Unparseable(lan.to_owned())),
+            },
+        };
+        match candidates[..] {
+            [move_] => Ok(move_),
+            [] => Err(InvalidLanMoveError::Illegal(lan.to_owned())),
+            _ => Err(InvalidLanMoveError::Ambiguous(lan.to_owned())),
+        }
+    }
+
+    /// Returns the suffix (`+`, `#` or nothing) which indicates whether the given legal move gives check or checkmate.
+    fn check_suffix(&self, move_: Move) -> &'static str {
+        let new_content = self.with_move_made(move_).unwrap();
+        if new_content.is_checkmate() {
+            "#"
+        } else if new_content.is_check() {
+            "+"
+        } else {
+            ""
+        }
+    }
+
+    /// Constructs a `Move` from a SAN representation, returning an error if it cannot be parsed, or is illegal or ambiguous.
+    /// Common deviations from strict SAN are accepted: over-disambiguation (`Ng1f3`), missing or superfluous capture markers,
+    /// the promotion forms `e8Q`, `e8=Q`, `e8(Q)` and `e8/Q`, an `e.p.` suffix, check and annotation glyphs (`+`, `#`, `!?`),
+    /// and castling written with zeros (`0-0`).
+    pub fn san_to_move(&self, san: &str) -> Result<Move, InvalidSanMoveError> {
+        self.san_to_move_in(san, &NotationLocale::ENGLISH)
+    }
+
+    /// Constructs a `Move` from a SAN representation that uses the piece letters of the given locale,
+    /// returning an error if it cannot be parsed, or is illegal or ambiguous.
+    pub fn san_to_move_in(&self, san: &str, locale: &NotationLocale) -> Result<Move, InvalidSanMoveError> {
+        let text = strip_move_suffixes(san);
+        let legal = self.gen_non_illegal_moves();
+        let candidates: Vec<_> = match castling_type(text) {
+            Some(castling) => legal.into_iter().filter(|m| m.2 == Some(castling)).collect(),
+            None => {
+                let components = SanComponents::parse(text, locale).ok_or(InvalidSanMoveError::Unparseable(san.to_owned()))?;
+                legal.into_iter().filter(|&m| components.matches(m, &self.content)).collect()
+            }
+        };
+        match candidates[..] {
+            [move_] => Ok(move_),
+            [] => Err(InvalidSanMoveError::Illegal(san.to_owned())),
+            _ => Err(InvalidSanMoveError::Ambiguous(san.to_owned())),
+        }
+    }
+
+    /// Returns the position which would occur if the given move were played, returning an error if the move is illegal.
+    pub fn with_move_made(&self, move_: Move) -> Result<Self, IllegalMoveError> {
+        match helpers::as_legal(move_, &self.gen_non_illegal_moves()) {
+            Some(m) => Ok(self.with_legal_move_made(m)),
+            _ => Err(IllegalMoveError(move_)),
+        }
+    }
+
+    /// Returns a new `Position` in which a move known to be legal has been played, updating the castling rights and en passant target square.
+    pub(crate) fn with_legal_move_made(&self, move_: Move) -> Self {
         let castling_rights_idx_offset = if self.side.is_white() { 0 } else { 2 };
         let Self {
             content,
@@ -273,12 +365,12 @@ impl Position {
         }
         side = !side;
         let new_content = helpers::change_content(content, &move_, &self.castling_rights);
-        Ok(Self {
+        Self {
             content: new_content,
             side,
             castling_rights,
             ep_target,
-        })
+        }
     }
 
     /// Pretty-prints the position to a string, from the perspective of the side `perspective`.
@@ -676,6 +768,98 @@ impl fmt::Display for Position {
     }
 }
 
+/// Removes surrounding whitespace, check and annotation glyphs, and `e.p.` suffixes from a move in algebraic notation.
+fn strip_move_suffixes(text: &str) -> &str {
+    let mut text = text.trim();
+    loop {
+        let trimmed = text.trim_end_matches(['+', '#', '!', '?']).trim_end();
+        let trimmed = trimmed.strip_suffix("e.p.").unwrap_or(trimmed).trim_end();
+        if trimmed == text {
+            return text;
+        }
+        text = trimmed;
+    }
+}
+
+/// Returns the type of castling written in algebraic notation (`None` if the text is not a castling move).
+fn castling_type(text: &str) -> Option<SpecialMoveType> {
+    match text {
+        "O-O" | "0-0" => Some(SpecialMoveType::CastlingKingside),
+        "O-O-O" | "0-0-0" => Some(SpecialMoveType::CastlingQueenside),
+        _ => None,
+    }
+}
+
+/// The components of a move in algebraic notation (SAN or LAN) other than castling.
+struct SanComponents {
+    piece_type: PieceType,
+    src_file: Option<char>,
+    src_rank: Option<char>,
+    dest: usize,
+    promotion: Option<PieceType>,
+}
+
+impl SanComponents {
+    /// Parses a move in algebraic notation other than castling, from which check and annotation glyphs have been removed.
+    fn parse(text: &str, locale: &NotationLocale) -> Option<Self> {
+        let chars: Vec<char> = text.chars().collect();
+        let (piece_type, chars) = match chars.split_first() {
+            Some((&c, rest)) if locale.piece_type(c).is_some() => (locale.piece_type(c).unwrap(), rest),
+            Some(('P', rest)) => (PieceType::P, rest),
+            _ => (PieceType::P, &chars[..]),
+        };
+        let (chars, promotion) = match chars {
+            [rest @ .., '(', p, ')'] | [rest @ .., '=' | '/', p] => (rest, Some(*p)),
+            [rest @ .., p] if locale.piece_type(*p).or(locale.piece_type(p.to_ascii_uppercase())).is_some() => (rest, Some(*p)),
+            _ => (chars, None),
+        };
+        let promotion = match promotion {
+            Some(p) if piece_type == PieceType::P => match locale.piece_type(p).or(locale.piece_type(p.to_ascii_uppercase())) {
+                Some(pt @ (PieceType::Q | PieceType::R | PieceType::B | PieceType::N)) => Some(pt),
+                _ => return None,
+            },
+            Some(_) => return None,
+            None => None,
+        };
+        let (chars, dest) = match chars {
+            [rest @ .., f @ 'a'..='h', r @ '1'..='8'] => (rest, helpers::sq_to_idx(*f, *r)),
+            _ => return None,
+        };
+        let chars = match chars {
+            [rest @ .., 'x' | ':' | '-'] => rest,
+            _ => chars,
+        };
+        let (src_file, src_rank) = match *chars {
+            [] => (None, None),
+            [f @ 'a'..='h'] => (Some(f), None),
+            [r @ '1'..='8'] => (None, Some(r)),
+            [f @ 'a'..='h', r @ '1'..='8'] => (Some(f), Some(r)),
+            _ => return None,
+        };
+        Some(Self {
+            piece_type,
+            src_file,
+            src_rank,
+            dest,
+            promotion,
+        })
+    }
+
+    /// Checks whether a legal move (other than castling) fits the components, given the contents of the board.
+    fn matches(&self, Move(src, dest, spec): Move, content: &[Option<Piece>; 64]) -> bool {
+        let (file, rank) = helpers::idx_to_sq(src);
+        dest == self.dest
+            && !matches!(spec, Some(SpecialMoveType::CastlingKingside | SpecialMoveType::CastlingQueenside))
+            && matches!(content[src], Some(Piece(pt, _)) if pt == self.piece_type)
+            && self.src_file.is_none_or(|f| f == file)
+            && self.src_rank.is_none_or(|r| r == rank)
+            && match spec {
+                Some(SpecialMoveType::Promotion(pt)) => self.promotion.is_none_or(|p| p == pt),
+                _ => self.promotion.is_none(),
+            }
+    }
+}
+
 /// Represents a piece of material.
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum Material {
@@ -0,0 +1,531 @@
+//! Solvers for chess problems, which find every solution of a stipulation along with the full tree of play that follows it.
+//! Direct mates, helpmates and selfmates are solved by [`Position::solve_mate`], [`Position::solve_helpmate`] and [`Position::solve_selfmate`],
+//! and twins, variants of a problem set with the same stipulation, by [`Stipulation::solve_twins`].
+
+use super::{Move, Position};
+use std::collections::HashMap;
+
+/// Represents a move of the attacking side in the solution of a direct mate, along with every defence to it.
+/// A move with no defences gives checkmate.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct MateVariation {
+    /// The move of the attacking side
+    pub move_: Move,
+    /// The legal replies of the defending side, each with the moves that still force mate after it
+    pub defences: Vec<Defence>,
+}
+
+impl MateVariation {
+    /// Checks whether the move gives checkmate.
+    pub fn is_mate(&self) -> bool {
+        self.defences.is_empty()
+    }
+}
+
+/// Represents a move of the defending side in the solution of a direct mate, along with the moves of the attacking side that force mate after it.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Defence {
+    /// The move of the defending side
+    pub move_: Move,
+    /// The moves of the attacking side which force mate in the moves left, more than one of which is a dual
+    pub continuations: Vec<MateVariation>,
+}
+
+/// Represents a point in the solution of a direct mate or selfmate where the attacking side has more than one way to continue.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Dual {
+    /// The moves played from the problem position up to the point, ending with a move of the defending side
+    pub line: Vec<Move>,
+    /// The moves of the attacking side which all succeed at the point
+    pub continuations: Vec<Move>,
+}
+
+/// Represents the solution of a direct mate, in which the side to move forces checkmate in at most a given number of moves against any defence.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct MateSolution {
+    /// The number of moves of the attacking side within which mate is forced
+    pub moves: usize,
+    /// The key moves, each with the full tree of play following it
+    pub keys: Vec<MateVariation>,
+}
+
+impl MateSolution {
+    /// Checks whether the problem has a solution.
+    pub fn is_solvable(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Checks whether the problem has exactly one key move.
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    /// Checks whether the problem is cooked, that is has more than one key move.
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Finds the duals in the solution, the points after a defence where more than one move of the attacking side forces mate in the moves left.
+    /// Duals on the last move are included, as is any continuation that mates sooner than required.
+    pub fn duals(&self) -> Vec<Dual> {
+        let mut duals = Vec::new();
+        for key in &self.keys {
+            collect_duals(key, &mut vec![key.move_], &mut duals);
+        }
+        duals
+    }
+}
+
+/// Represents a move in the solution of a helpmate, along with the replies which continue the help play after it.
+/// A move with no continuations gives checkmate.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct HelpVariation {
+    /// The move, of either side
+    pub move_: Move,
+    /// The replies of the other side which lead to mate in the moves left
+    pub continuations: Vec<HelpVariation>,
+}
+
+/// Represents the solution of a helpmate, in which both sides cooperate so that the side to move, which also moves first, is checkmated
+/// on the last move of the other side after a given number of moves each.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct HelpmateSolution {
+    /// The number of moves of each side in the help play
+    pub moves: usize,
+    /// The first moves of the help play, each with the tree of replies following it
+    pub first_moves: Vec<HelpVariation>,
+}
+
+impl HelpmateSolution {
+    /// Returns every line of play which fulfils the stipulation, each being a solution.
+    pub fn lines(&self) -> Vec<Vec<Move>> {
+        let mut lines = Vec::new();
+        for variation in &self.first_moves {
+            collect_lines(variation, &mut Vec::new(), &mut lines);
+        }
+        lines
+    }
+
+    /// Checks whether the problem has a solution.
+    pub fn is_solvable(&self) -> bool {
+        !self.first_moves.is_empty()
+    }
+
+    /// Checks whether the problem has exactly the intended number of solutions, as helpmates are often set with more than one solution
+    /// (e.g. "h#2, 2 solutions").
+    pub fn is_sound(&self, intended: usize) -> bool {
+        self.lines().len() == intended
+    }
+
+    /// Checks whether the problem is cooked, that is has more solutions than the intended number.
+    pub fn is_cooked(&self, intended: usize) -> bool {
+        self.lines().len() > intended
+    }
+}
+
+/// Represents a move of the attacking side in the solution of a selfmate, along with every defence to it.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SelfmateVariation {
+    /// The move of the attacking side
+    pub move_: Move,
+    /// The legal replies of the defending side, each with the moves that still force the defending side to mate after it
+    pub defences: Vec<SelfmateDefence>,
+}
+
+/// Represents a move of the defending side in the solution of a selfmate, along with the moves of the attacking side
+/// that force the defending side to mate after it. A move with no continuations gives checkmate.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SelfmateDefence {
+    /// The move of the defending side
+    pub move_: Move,
+    /// The moves of the attacking side which force the defending side to mate in the moves left, more than one of which is a dual
+    pub continuations: Vec<SelfmateVariation>,
+}
+
+impl SelfmateDefence {
+    /// Checks whether the move gives checkmate.
+    pub fn is_mate(&self) -> bool {
+        self.continuations.is_empty()
+    }
+}
+
+/// Represents the solution of a selfmate, in which the side to move forces the other side to give checkmate in at most a given number
+/// of moves, however the other side tries to avoid it.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SelfmateSolution {
+    /// The number of moves of the attacking side within which the defending side is forced to mate
+    pub moves: usize,
+    /// The key moves, each with the full tree of play following it
+    pub keys: Vec<SelfmateVariation>,
+}
+
+impl SelfmateSolution {
+    /// Checks whether the problem has a solution.
+    pub fn is_solvable(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Checks whether the problem has exactly one key move.
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    /// Checks whether the problem is cooked, that is has more than one key move.
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Finds the duals in the solution, the points after a defence where more than one move of the attacking side forces mate in the moves left.
+    pub fn duals(&self) -> Vec<Dual> {
+        let mut duals = Vec::new();
+        for key in &self.keys {
+            collect_selfmate_duals(key, &mut vec![key.move_], &mut duals);
+        }
+        duals
+    }
+}
+
+/// Represents the stipulation of a problem, which is shared by all of its twins.
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum Stipulation {
+    /// A direct mate in the given number of moves
+    Mate(usize),
+    /// A helpmate in the given number of moves, with the intended number of solutions
+    Helpmate(usize, usize),
+    /// A selfmate in the given number of moves
+    Selfmate(usize),
+}
+
+/// Represents the solution of a problem under any stipulation.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub enum Solution {
+    /// The solution of a direct mate
+    Mate(MateSolution),
+    /// The solution of a helpmate, with the intended number of solutions
+    Helpmate(HelpmateSolution, usize),
+    /// The solution of a selfmate
+    Selfmate(SelfmateSolution),
+}
+
+impl Solution {
+    /// Checks whether the problem has a solution.
+    pub fn is_solvable(&self) -> bool {
+        match self {
+            Self::Mate(solution) => solution.is_solvable(),
+            Self::Helpmate(solution, _) => solution.is_solvable(),
+            Self::Selfmate(solution) => solution.is_solvable(),
+        }
+    }
+
+    /// Checks whether the problem is sound, that is has exactly one key move, or the intended number of solutions for a helpmate.
+    pub fn is_sound(&self) -> bool {
+        match self {
+            Self::Mate(solution) => solution.is_sound(),
+            Self::Helpmate(solution, intended) => solution.is_sound(*intended),
+            Self::Selfmate(solution) => solution.is_sound(),
+        }
+    }
+
+    /// Checks whether the problem is cooked, that is has more than one key move, or more solutions than intended for a helpmate.
+    pub fn is_cooked(&self) -> bool {
+        match self {
+            Self::Mate(solution) => solution.is_cooked(),
+            Self::Helpmate(solution, intended) => solution.is_cooked(*intended),
+            Self::Selfmate(solution) => solution.is_cooked(),
+        }
+    }
+}
+
+/// Represents a twin of a problem, a variant of the problem position (e.g. with a piece moved or removed) set with the same stipulation.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Twin {
+    /// The label of the twin, such as "b)"
+    pub label: String,
+    /// The position of the twin
+    pub position: Position,
+}
+
+/// Represents the solution of a twin, or of the problem position itself.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct TwinSolution {
+    /// The label of the twin, "a)" for the problem position
+    pub label: String,
+    /// The solution of the twin under the stipulation of the problem
+    pub solution: Solution,
+}
+
+impl Stipulation {
+    /// Solves a position under the stipulation.
+    pub fn solve(&self, position: &Position) -> Solution {
+        match *self {
+            Self::Mate(n) => Solution::Mate(position.solve_mate(n)),
+            Self::Helpmate(n, intended) => Solution::Helpmate(position.solve_helpmate(n), intended),
+            Self::Selfmate(n) => Solution::Selfmate(position.solve_selfmate(n)),
+        }
+    }
+
+    /// Solves the problem position, labelled "a)", and each of its twins under the stipulation, so that the twins which are sound
+    /// and those which are cooked can be told apart with [`Solution::is_sound`] and [`Solution::is_cooked`].
+    pub fn solve_twins(&self, position: &Position, twins: &[Twin]) -> Vec<TwinSolution> {
+        let mut solutions = vec![TwinSolution {
+            label: "a)".to_owned(),
+            solution: self.solve(position),
+        }];
+        for twin in twins {
+            solutions.push(TwinSolution {
+                label: twin.label.clone(),
+                solution: self.solve(&twin.position),
+            });
+        }
+        solutions
+    }
+}
+
+impl Position {
+    /// Solves the position as a direct mate in `n` moves, finding every move of the side to move which forces checkmate in at most `n` moves
+    /// against any defence. Each key move comes with the tree of every defence to it and every move which forces mate after each defence,
+    /// so that cooks (more than one key move) and duals can be found with [`MateSolution::is_cooked`] and [`MateSolution::duals`].
+    pub fn solve_mate(&self, n: usize) -> MateSolution {
+        let mut solver = Solver::default();
+        MateSolution {
+            moves: n,
+            keys: solver.mating_moves(self, n),
+        }
+    }
+
+    /// Solves the position as a helpmate in `n` moves, finding every line in which the side to move and the other side, moving in turn,
+    /// play `n` moves each so that the side to move is checkmated by the last move. The number of lines is compared to the intended
+    /// number of solutions by [`HelpmateSolution::is_cooked`].
+    pub fn solve_helpmate(&self, n: usize) -> HelpmateSolution {
+        let mut solver = Solver::default();
+        HelpmateSolution {
+            moves: n,
+            first_moves: solver.help_moves(self, n),
+        }
+    }
+
+    /// Solves the position as a selfmate in `n` moves, finding every move of the side to move which forces the other side to give checkmate
+    /// within `n` moves, the other side avoiding mate for as long as it can. Each key move comes with the tree of every defence to it.
+    pub fn solve_selfmate(&self, n: usize) -> SelfmateSolution {
+        let mut solver = Solver::default();
+        SelfmateSolution {
+            moves: n,
+            keys: solver.selfmating_moves(self, n),
+        }
+    }
+}
+
+/// Searches the play of a stipulation, remembering which positions have been found to be solved within a number of moves.
+/// A solver is used for a single stipulation, so that the results of different stipulations are not mixed up.
+#[derive(Default)]
+struct Solver {
+    /// Whether the stipulation is fulfilled from a position within a number of moves
+    results: HashMap<(Position, usize), bool>,
+}
+
+impl Solver {
+    /// Checks whether the side to move forces mate within `n` moves.
+    fn forces_mate(&mut self, position: &Position, n: usize) -> bool {
+        if n == 0 {
+            return false;
+        }
+        let key = (position.clone(), n);
+        if let Some(&result) = self.results.get(&key) {
+            return result;
+        }
+        let result = position.gen_non_illegal_moves().into_iter().any(|move_| self.succeeds(&position.with_legal_move_made(move_), n));
+        self.results.insert(key, result);
+        result
+    }
+
+    /// Checks whether the position reached by a move of the attacking side is checkmate, or has defences which are all met by mate within `n - 1` moves.
+    /// No move succeeds when `n` is zero, as the attacking side has no moves left.
+    fn succeeds(&mut self, position: &Position, n: usize) -> bool {
+        if n == 0 {
+            return false;
+        }
+        if position.is_checkmate() {
+            return true;
+        }
+        let defences = position.gen_non_illegal_moves();
+        n > 1 && !defences.is_empty() && defences.into_iter().all(|defence| self.forces_mate(&position.with_legal_move_made(defence), n - 1))
+    }
+
+    /// Finds the moves which force mate within `n` moves, along with the tree of play following each of them.
+    fn mating_moves(&mut self, position: &Position, n: usize) -> Vec<MateVariation> {
+        let mut variations = Vec::new();
+        for move_ in position.gen_non_illegal_moves() {
+            let next = position.with_legal_move_made(move_);
+            if !self.succeeds(&next, n) {
+                continue;
+            }
+            let defences = if next.is_checkmate() {
+                Vec::new()
+            } else {
+                next.gen_non_illegal_moves()
+                    .into_iter()
+                    .map(|defence| Defence {
+                        move_: defence,
+                        continuations: self.mating_moves(&next.with_legal_move_made(defence), n - 1),
+                    })
+                    .collect()
+            };
+            variations.push(MateVariation { move_, defences });
+        }
+        variations
+    }
+
+    /// Checks whether the side to move can be checkmated in exactly `n` moves of each side, with both sides cooperating.
+    fn helps(&mut self, position: &Position, n: usize) -> bool {
+        let key = (position.clone(), n);
+        if let Some(&result) = self.results.get(&key) {
+            return result;
+        }
+        let result = position.gen_non_illegal_moves().into_iter().any(|move_| {
+            let next = position.with_legal_move_made(move_);
+            next.gen_non_illegal_moves().into_iter().any(|reply| {
+                let after = next.with_legal_move_made(reply);
+                if n == 1 {
+                    after.is_checkmate()
+                } else {
+                    self.helps(&after, n - 1)
+                }
+            })
+        });
+        self.results.insert(key, result);
+        result
+    }
+
+    /// Finds the moves of the side to move which lead to it being checkmated in exactly `n` moves of each side, along with the tree of replies following each of them.
+    fn help_moves(&mut self, position: &Position, n: usize) -> Vec<HelpVariation> {
+        if n == 0 || !self.helps(position, n) {
+            return Vec::new();
+        }
+        let mut variations = Vec::new();
+        for move_ in position.gen_non_illegal_moves() {
+            let next = position.with_legal_move_made(move_);
+            let mut replies = Vec::new();
+            for reply in next.gen_non_illegal_moves() {
+                let after = next.with_legal_move_made(reply);
+                if n == 1 && after.is_checkmate() {
+                    replies.push(HelpVariation {
+                        move_: reply,
+                        continuations: Vec::new(),
+                    });
+                } else if n > 1 && self.helps(&after, n - 1) {
+                    replies.push(HelpVariation {
+                        move_: reply,
+                        continuations: self.help_moves(&after, n - 1),
+                    });
+                }
+            }
+            if !replies.is_empty() {
+                variations.push(HelpVariation { move_, continuations: replies });
+            }
+        }
+        variations
+    }
+
+    /// Checks whether the side to move forces the other side to give checkmate within `n` moves.
+    fn forces_selfmate(&mut self, position: &Position, n: usize) -> bool {
+        if n == 0 {
+            return false;
+        }
+        let key = (position.clone(), n);
+        if let Some(&result) = self.results.get(&key) {
+            return result;
+        }
+        let result = position
+            .gen_non_illegal_moves()
+            .into_iter()
+            .any(|move_| self.selfmate_succeeds(&position.with_legal_move_made(move_), n));
+        self.results.insert(key, result);
+        result
+    }
+
+    /// Checks whether the position reached by a move of the attacking side has defences which all give checkmate or are met by a forced selfmate
+    /// within `n - 1` moves. A position with no defences, which is checkmate or stalemate, fails, as does any position when `n` is zero.
+    fn selfmate_succeeds(&mut self, position: &Position, n: usize) -> bool {
+        let defences = position.gen_non_illegal_moves();
+        n > 0
+            && !defences.is_empty()
+            && defences.into_iter().all(|defence| {
+                let next = position.with_legal_move_made(defence);
+                next.is_checkmate() || self.forces_selfmate(&next, n - 1)
+            })
+    }
+
+    /// Finds the moves which force the other side to give checkmate within `n` moves, along with the tree of play following each of them.
+    fn selfmating_moves(&mut self, position: &Position, n: usize) -> Vec<SelfmateVariation> {
+        let mut variations = Vec::new();
+        for move_ in position.gen_non_illegal_moves() {
+            let next = position.with_legal_move_made(move_);
+            if !self.selfmate_succeeds(&next, n) {
+                continue;
+            }
+            let defences = next
+                .gen_non_illegal_moves()
+                .into_iter()
+                .map(|defence| {
+                    let after = next.with_legal_move_made(defence);
+                    SelfmateDefence {
+                        move_: defence,
+                        continuations: if after.is_checkmate() { Vec::new() } else { self.selfmating_moves(&after, n - 1) },
+                    }
+                })
+                .collect();
+            variations.push(SelfmateVariation { move_, defences });
+        }
+        variations
+    }
+}
+
+/// Adds the duals following a move of the attacking side, reached by the moves in `line`, to `duals`.
+fn collect_duals(variation: &MateVariation, line: &mut Vec<Move>, duals: &mut Vec<Dual>) {
+    for defence in &variation.defences {
+        line.push(defence.move_);
+        if defence.continuations.len() > 1 {
+            duals.push(Dual {
+                line: line.clone(),
+                continuations: defence.continuations.iter().map(|c| c.move_).collect(),
+            });
+        }
+        for continuation in &defence.continuations {
+            line.push(continuation.move_);
+            collect_duals(continuation, line, duals);
+            line.pop();
+        }
+        line.pop();
+    }
+}
+
+/// Adds the duals following a move of the attacking side in a selfmate, reached by the moves in `line`, to `duals`.
+fn collect_selfmate_duals(variation: &SelfmateVariation, line: &mut Vec<Move>, duals: &mut Vec<Dual>) {
+    for defence in &variation.defences {
+        line.push(defence.move_);
+        if defence.continuations.len() > 1 {
+            duals.push(Dual {
+                line: line.clone(),
+                continuations: defence.continuations.iter().map(|c| c.move_).collect(),
+            });
+        }
+        for continuation in &defence.continuations {
+            line.push(continuation.move_);
+            collect_selfmate_duals(continuation, line, duals);
+            line.pop();
+        }
+        line.pop();
+    }
+}
+
+/// Adds the lines of help play following a move, reached by the moves in `line`, to `lines`.
+fn collect_lines(variation: &HelpVariation, line: &mut Vec<Move>, lines: &mut Vec<Vec<Move>>) {
+    line.push(variation.move_);
+    if variation.continuations.is_empty() {
+        lines.push(line.clone());
+    }
+    for continuation in &variation.continuations {
+        collect_lines(continuation, line, lines);
+    }
+    line.pop();
+}
@@ -0,0 +1,388 @@
+//! Tactical puzzles given as a position and a solution line, with validation of the uniqueness of the solution,
+//! import from the Lichess puzzle database and interactive solving sessions.
+
+use super::{
+    errors::{IllegalMoveError, InvalidPuzzleError, InvalidSanMoveError, InvalidUciMoveError},
+    evaluation::{Evaluator, HandcraftedEvaluator},
+    helpers, Board, Color, Fen, Move, Position, SpecialMoveType,
+};
+use std::{io::BufRead, str::FromStr};
+
+/// The score of a checkmate in the search of [`EvaluationJudge`], from which the number of plies to the mate is subtracted
+const MATE: i32 = 100_000;
+
+/// Represents a tactical puzzle: a position in which the side to move, the solver, has to find the moves of a solution line,
+/// which alternate with the replies of the opponent.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    /// The board on which the solver is to move
+    pub board: Board,
+    /// The moves of the solution, starting with a move of the solver
+    pub solution: Vec<Move>,
+}
+
+impl Puzzle {
+    /// Creates a puzzle from a position in FEN and a solution line of UCI moves separated by spaces, returning an error if the FEN is invalid,
+    /// the solution is empty or any of its moves is invalid or illegal.
+    pub fn new(fen: &str, solution: &str) -> Result<Self, InvalidPuzzleError> {
+        Self::from_board(Board::from_fen(Fen::try_from(fen)?), solution)
+    }
+
+    /// Creates a puzzle from a board on which the solver is to move and a solution line of UCI moves separated by spaces,
+    /// returning an error if the solution is empty or any of its moves is invalid or illegal.
+    pub fn from_board(board: Board, solution: &str) -> Result<Self, InvalidPuzzleError> {
+        let mut replayed = board.clone();
+        replayed.make_moves_uci(solution)?;
+        let solution = replayed.move_history()[board.move_history().len()..].to_vec();
+        if solution.is_empty() {
+            return Err(InvalidPuzzleError::EmptySolution);
+        }
+        Ok(Self { board, solution })
+    }
+
+    /// Returns the side which solves the puzzle.
+    pub fn solver(&self) -> Color {
+        self.board.side_to_move()
+    }
+
+    /// Returns the number of moves the solver has to find.
+    pub fn solver_moves(&self) -> usize {
+        self.solution.len().div_ceil(2)
+    }
+
+    /// Replays the solution, asking the judge at each move of the solver which moves succeed, so that solutions
+    /// whose moves do not succeed or are not the only ones to succeed are found.
+    pub fn validate<J: Judge>(&self, judge: &J) -> Validation {
+        let mut board = self.board.clone();
+        let mut steps = Vec::new();
+        for (ply, &move_) in self.solution.iter().enumerate() {
+            if ply % 2 == 0 {
+                let successful = judge.successful_moves(board.position(), self.solver_moves() - ply / 2);
+                steps.push(Step {
+                    ply,
+                    move_,
+                    succeeds: successful.contains(&move_),
+                    alternatives: successful.into_iter().filter(|&m| m != move_).collect(),
+                });
+            }
+            board.make_move(move_).unwrap();
+        }
+        Validation { steps }
+    }
+}
+
+/// Represents the judgement of a move of the solver in the solution of a puzzle.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Step {
+    /// The index of the move in the solution
+    pub ply: usize,
+    /// The move of the solution
+    pub move_: Move,
+    /// Whether the judge found the move to succeed
+    pub succeeds: bool,
+    /// The other moves which the judge found to succeed
+    pub alternatives: Vec<Move>,
+}
+
+/// Represents the outcome of the validation of a puzzle, with a step for every move of the solver.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Validation {
+    /// The judgements of the moves of the solver, in the order of the solution
+    pub steps: Vec<Step>,
+}
+
+impl Validation {
+    /// Checks whether every move of the solver in the solution succeeds.
+    pub fn is_valid(&self) -> bool {
+        self.steps.iter().all(|s| s.succeeds)
+    }
+
+    /// Checks whether every move of the solver in the solution succeeds and is the only move to succeed.
+    pub fn is_unique(&self) -> bool {
+        self.is_valid() && self.steps.iter().all(|s| s.alternatives.is_empty())
+    }
+
+    /// Returns the steps at which another move than the one of the solution succeeds.
+    pub fn ambiguous_steps(&self) -> Vec<&Step> {
+        self.steps.iter().filter(|s| !s.alternatives.is_empty()).collect()
+    }
+}
+
+/// Represents a way of deciding which moves of the solver succeed in a position of a puzzle.
+pub trait Judge {
+    /// Returns the moves of the side to move which succeed, given the number of moves the solver has left to play, including this one.
+    fn successful_moves(&self, position: &Position, moves_left: usize) -> Vec<Move>;
+}
+
+/// Judges mating puzzles with [`Position::solve_mate`]: a move succeeds if it forces mate within the moves the solver has left.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct MateJudge;
+
+impl Judge for MateJudge {
+    fn successful_moves(&self, position: &Position, moves_left: usize) -> Vec<Move> {
+        position.solve_mate(moves_left).keys.into_iter().map(|k| k.move_).collect()
+    }
+}
+
+/// Judges puzzles with an [`Evaluator`]: a move succeeds if a search of fixed depth after it, extended by captures and promotions,
+/// gives the solver a score of at least the threshold.
+#[derive(Clone, Debug)]
+pub struct EvaluationJudge<E: Evaluator = HandcraftedEvaluator> {
+    /// The evaluator with which the positions at the end of the search are scored
+    pub evaluator: E,
+    /// The number of plies searched after each move
+    pub depth: u32,
+    /// The score in centipawns, from the perspective of the solver, from which a move is considered to succeed
+    pub threshold: i32,
+}
+
+impl Default for EvaluationJudge {
+    fn default() -> Self {
+        Self {
+            evaluator: HandcraftedEvaluator,
+            depth: 2,
+            threshold: 200,
+        }
+    }
+}
+
+impl<E: Evaluator> Judge for EvaluationJudge<E> {
+    fn successful_moves(&self, position: &Position, _: usize) -> Vec<Move> {
+        position
+            .gen_non_illegal_moves()
+            .into_iter()
+            .filter(|&move_| -self.search(&position.with_legal_move_made(move_), self.depth, -MATE, MATE, 1) >= self.threshold)
+            .collect()
+    }
+}
+
+impl<E: Evaluator> EvaluationJudge<E> {
+    /// Searches the position with alpha-beta search, returning its score from the perspective of the side to move.
+    fn search(&self, position: &Position, depth: u32, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+        let moves = position.gen_non_illegal_moves();
+        if moves.is_empty() {
+            return if position.is_check() { ply - MATE } else { 0 };
+        }
+        if depth == 0 {
+            return self.quiescence(position, alpha, beta);
+        }
+        for move_ in moves {
+            let score = -self.search(&position.with_legal_move_made(move_), depth - 1, -beta, -alpha, ply + 1);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    /// Searches the captures and promotions in the position, so that it is only scored once it is quiet.
+    fn quiescence(&self, position: &Position, mut alpha: i32, beta: i32) -> i32 {
+        let stand_pat = match position.side {
+            Color::White => self.evaluator.evaluate(position),
+            Color::Black => -self.evaluator.evaluate(position),
+        };
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        for move_ in position.gen_non_illegal_moves() {
+            if position.content[move_.1].is_none() && !matches!(move_.2, Some(SpecialMoveType::EnPassant | SpecialMoveType::Promotion(_))) {
+                continue;
+            }
+            let score = -self.quiescence(&position.with_legal_move_made(move_), -beta, -alpha);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/// Represents a puzzle of the Lichess puzzle database, whose CSV records have the fields
+/// `PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags`.
+#[derive(Clone, Debug)]
+pub struct LichessPuzzle {
+    /// The ID of the puzzle on Lichess
+    pub id: String,
+    /// The puzzle, whose board has the move of the opponent that sets it up already played
+    pub puzzle: Puzzle,
+    /// The rating of the puzzle
+    pub rating: u32,
+    /// The deviation of the rating of the puzzle
+    pub rating_deviation: u32,
+    /// The popularity of the puzzle, from -100 to 100
+    pub popularity: i32,
+    /// The number of times the puzzle has been played
+    pub plays: u32,
+    /// The themes of the puzzle, such as "mateIn2" or "fork"
+    pub themes: Vec<String>,
+    /// The URL of the game the puzzle is taken from
+    pub game_url: String,
+    /// The openings of the game the puzzle is taken from, such as "Sicilian_Defense"
+    pub opening_tags: Vec<String>,
+}
+
+impl TryFrom<&str> for LichessPuzzle {
+    type Error = InvalidPuzzleError;
+
+    /// Parses a CSV record of the Lichess puzzle database. The FEN is the position before the move of the opponent which sets up the puzzle,
+    /// which is the first of the moves. Only the ID, FEN, moves and rating are required, the other fields being empty or zero if they are missing.
+    fn try_from(record: &str) -> Result<Self, Self::Error> {
+        let fields: Vec<_> = record.trim_end().split(',').collect();
+        let field = |i: usize, name: &'static str| fields.get(i).copied().filter(|f| !f.is_empty()).ok_or(InvalidPuzzleError::MissingField(name));
+        let words = |i: usize| fields.get(i).map_or(Vec::new(), |f| f.split_ascii_whitespace().map(String::from).collect());
+        let mut board = Board::from_fen(Fen::try_from(field(1, "FEN")?)?);
+        let moves = field(2, "Moves")?;
+        let (setup, solution) = moves.split_once(' ').unwrap_or((moves, ""));
+        board.make_move_uci(setup)?;
+        Ok(Self {
+            id: field(0, "PuzzleId")?.to_owned(),
+            puzzle: Puzzle::from_board(board, solution)?,
+            rating: parse_number(Some(field(3, "Rating")?), "Rating")?,
+            rating_deviation: parse_number(fields.get(4).copied(), "RatingDeviation")?,
+            popularity: parse_number(fields.get(5).copied(), "Popularity")?,
+            plays: parse_number(fields.get(6).copied(), "NbPlays")?,
+            themes: words(7),
+            game_url: fields.get(8).map_or(String::new(), |f| f.to_string()),
+            opening_tags: words(9),
+        })
+    }
+}
+
+/// Parses a numeric field of a CSV record of the Lichess puzzle database, which is zero if it is missing or empty.
+fn parse_number<T: FromStr + Default>(field: Option<&str>, name: &'static str) -> Result<T, InvalidPuzzleError> {
+    match field {
+        Some(f) if !f.is_empty() => f.parse().map_err(|_| InvalidPuzzleError::InvalidField(name, f.to_owned())),
+        _ => Ok(T::default()),
+    }
+}
+
+/// Reads the puzzles of a CSV file of the Lichess puzzle database one record at a time, skipping the header and empty lines.
+pub struct LichessReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+}
+
+impl<R: BufRead> LichessReader<R> {
+    /// Creates a reader of the puzzles in the given CSV text.
+    pub fn new(reader: R) -> Self {
+        Self { lines: reader.lines() }
+    }
+}
+
+impl<R: BufRead> Iterator for LichessReader<R> {
+    type Item = Result<LichessPuzzle, InvalidPuzzleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if !line.trim().is_empty() && !line.starts_with("PuzzleId") {
+                return Some(LichessPuzzle::try_from(line.as_str()));
+            }
+        }
+    }
+}
+
+/// Represents the outcome of an attempt to play a move of the solution of a puzzle.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Attempt {
+    /// The move is correct and has been played, followed by the given reply of the opponent
+    Correct(Move),
+    /// The move is correct and has been played, completing the puzzle
+    Solved,
+    /// The move is not the one of the solution and has not been played
+    Incorrect,
+}
+
+/// Represents an attempt of a user to solve a puzzle, in which the moves of the user are checked against the solution
+/// and the replies of the opponent are played automatically.
+#[derive(Clone, Debug)]
+pub struct PuzzleSession {
+    puzzle: Puzzle,
+    board: Board,
+    ply: usize,
+    mistakes: usize,
+}
+
+impl PuzzleSession {
+    /// Starts a session of the given puzzle.
+    pub fn new(puzzle: Puzzle) -> Self {
+        Self {
+            board: puzzle.board.clone(),
+            puzzle,
+            ply: 0,
+            mistakes: 0,
+        }
+    }
+
+    /// Returns the puzzle being solved.
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    /// Returns the board as it currently stands, with the correct moves and the replies to them played.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Attempts to play the given move as the next move of the solution, returning an error if the move is illegal.
+    /// A move which gives checkmate is accepted as the last move in place of the one of the solution, as it completes the puzzle as well.
+    /// Once the puzzle is complete, every move is incorrect, and mistakes are no longer counted.
+    pub fn try_move(&mut self, move_: Move) -> Result<Attempt, IllegalMoveError> {
+        let move_ = helpers::as_legal(move_, &self.board.gen_legal_moves()).ok_or(IllegalMoveError(move_))?;
+        let Some(&expected) = self.puzzle.solution.get(self.ply) else {
+            return Ok(Attempt::Incorrect);
+        };
+        let mates = self.ply + 1 == self.puzzle.solution.len() && self.board.position().with_legal_move_made(move_).is_checkmate();
+        if move_ != expected && !mates {
+            self.mistakes += 1;
+            return Ok(Attempt::Incorrect);
+        }
+        self.board.make_move(move_).unwrap();
+        self.ply += 1;
+        match self.puzzle.solution.get(self.ply) {
+            Some(&reply) => {
+                self.board.make_move(reply).unwrap();
+                self.ply += 1;
+                Ok(if self.is_complete() { Attempt::Solved } else { Attempt::Correct(reply) })
+            }
+            None => Ok(Attempt::Solved),
+        }
+    }
+
+    /// Attempts to play the move given in UCI as the next move of the solution, returning an error if the move is invalid or illegal.
+    pub fn try_uci(&mut self, uci: &str) -> Result<Attempt, InvalidUciMoveError> {
+        let move_ = Move::from_uci(uci).map_err(|_| InvalidUciMoveError::InvalidUci(uci.to_owned()))?;
+        self.try_move(move_).map_err(|_| InvalidUciMoveError::IllegalMove(uci.to_owned()))
+    }
+
+    /// Attempts to play the move given in SAN as the next move of the solution, returning an error if the move is invalid, illegal or ambiguous.
+    pub fn try_san(&mut self, san: &str) -> Result<Attempt, InvalidSanMoveError> {
+        let move_ = self.board.san_to_move(san)?;
+        self.try_move(move_).map_err(|_| InvalidSanMoveError::Illegal(san.to_owned()))
+    }
+
+    /// Returns the next move of the solution, or `None` if the puzzle is complete.
+    pub fn hint(&self) -> Option<Move> {
+        self.puzzle.solution.get(self.ply).copied()
+    }
+
+    /// Checks whether every move of the solution has been played.
+    pub fn is_complete(&self) -> bool {
+        self.ply >= self.puzzle.solution.len()
+    }
+
+    /// Returns the number of incorrect moves attempted.
+    pub fn mistakes(&self) -> usize {
+        self.mistakes
+    }
+
+    /// Returns the number of moves of the solution the user has played correctly.
+    pub fn moves_solved(&self) -> usize {
+        self.ply.div_ceil(2)
+    }
+}
@@ -0,0 +1,207 @@
+use super::{helpers, Color, Move, Piece, PieceType, Position, SpecialMoveType};
+
+/// The pieces which a move that cannot be a capture can have captured, that is none
+const NO_CAPTURE: [Option<PieceType>; 1] = [None];
+/// The pieces which a move that may be a capture can have captured, `None` standing for no capture
+const ANY_CAPTURE: [Option<PieceType>; 6] = [None, Some(PieceType::Q), Some(PieceType::R), Some(PieceType::B), Some(PieceType::N), Some(PieceType::P)];
+/// The pieces which a capture can have captured
+const CAPTURE: [Option<PieceType>; 5] = [Some(PieceType::Q), Some(PieceType::R), Some(PieceType::B), Some(PieceType::N), Some(PieceType::P)];
+/// The directions in which a king or queen moves, as (file, rank) steps
+const ROYAL_DIRECTIONS: [(isize, isize); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+/// The jumps of a knight, as (file, rank) steps
+const KNIGHT_JUMPS: [(isize, isize); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+
+/// Represents a move retracted from a position: a move that could have been played to reach it, along with the position it was played from.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Unmove {
+    /// The move, as played from the previous position
+    pub move_: Move,
+    /// The type of the piece captured by the move (a pawn for an en passant capture), or `None` if the move is not a capture
+    pub uncaptured: Option<PieceType>,
+    /// The position the move was played from
+    pub position: Position,
+}
+
+impl Position {
+    /// Generates the moves that could have been played to reach the position, that is the moves of the side that is not to move
+    /// which lead to the position from a legal position, along with the positions they were played from. This includes captures of
+    /// every type of piece, promotions, en passant captures and castling.
+    ///
+    /// The previous positions have the castling rights of the position (and the right used by a castling move), and no en passant target square
+    /// unless the move is an en passant capture, as nothing more can be known from the position alone. Castling is only retracted
+    /// to the standard squares of the king and rooks. If the position has an en passant target square, only the double pawn push
+    /// that set it is retracted.
+    ///
+    /// A previous position is legal if the side that is not to move is not in check, no pawns stand on the first or last rank,
+    /// and each side has no more pawns and promoted pieces than the 8 pawns it started with.
+    pub fn gen_unmoves(&self) -> Vec<Unmove> {
+        let mover = !self.side;
+        let forward: isize = if mover.is_white() { 8 } else { -8 };
+        let step = |sq: usize, files: isize, ranks: isize| square((sq % 8) as isize + files, (sq / 8) as isize + ranks).filter(|&s| self.content[s].is_none());
+        let back = if mover.is_white() { -1 } else { 1 };
+        let mut unmoves = Vec::new();
+        for dest in 0..64 {
+            let Some(Piece(piece_type, color)) = self.content[dest] else {
+                continue;
+            };
+            if color != mover {
+                continue;
+            }
+            let mut candidates = Vec::new();
+            if piece_type == PieceType::P {
+                if let Some(src) = step(dest, 0, back) {
+                    candidates.push((src, None, &NO_CAPTURE[..]));
+                    if dest / 8 == if mover.is_white() { 3 } else { 4 } {
+                        if let Some(src) = step(src, 0, back) {
+                            candidates.push((src, None, &NO_CAPTURE[..]));
+                        }
+                    }
+                }
+                for files in [-1, 1] {
+                    if let Some(src) = step(dest, files, back) {
+                        candidates.push((src, None, &CAPTURE[..]));
+                        let (captured, origin) = ((dest as isize - forward) as usize, (dest as isize + forward) as usize);
+                        if dest / 8 == if mover.is_white() { 5 } else { 2 } && self.content[captured].is_none() && self.content[origin].is_none() {
+                            let mut content = self.content;
+                            (content[src], content[dest], content[captured]) = (content[dest], None, Some(Piece(PieceType::P, self.side)));
+                            let position = Position {
+                                content,
+                                side: mover,
+                                castling_rights: self.castling_rights,
+                                ep_target: Some(dest),
+                            };
+                            let move_ = Move(src, dest, Some(SpecialMoveType::EnPassant));
+                            if self.is_retraction(&position, move_) {
+                                unmoves.push(Unmove {
+                                    move_,
+                                    uncaptured: Some(PieceType::P),
+                                    position,
+                                });
+                            }
+                        }
+                    }
+                }
+            } else {
+                for src in origins(&self.content, piece_type, dest) {
+                    candidates.push((src, None, &ANY_CAPTURE[..]));
+                }
+                if piece_type != PieceType::K && dest / 8 == if mover.is_white() { 7 } else { 0 } {
+                    let promotion = Some(SpecialMoveType::Promotion(piece_type));
+                    if let Some(src) = step(dest, 0, back) {
+                        candidates.push((src, promotion, &NO_CAPTURE[..]));
+                    }
+                    for files in [-1, 1] {
+                        if let Some(src) = step(dest, files, back) {
+                            candidates.push((src, promotion, &CAPTURE[..4]));
+                        }
+                    }
+                }
+            }
+            for (src, spec, captures) in candidates {
+                for &uncaptured in captures {
+                    let mut content = self.content;
+                    content[src] = if spec.is_some() { Some(Piece(PieceType::P, mover)) } else { content[dest] };
+                    content[dest] = uncaptured.map(|t| Piece(t, self.side));
+                    let position = Position {
+                        content,
+                        side: mover,
+                        castling_rights: self.castling_rights,
+                        ep_target: None,
+                    };
+                    let move_ = Move(src, dest, spec);
+                    if self.is_retraction(&position, move_) {
+                        unmoves.push(Unmove { move_, uncaptured, position });
+                    }
+                }
+            }
+        }
+        unmoves.extend(self.gen_uncastlings());
+        unmoves
+    }
+
+    /// Generates the castling moves that could have been played to reach the position, from the standard squares of the king and rooks.
+    fn gen_uncastlings(&self) -> Vec<Unmove> {
+        let mover = !self.side;
+        let (rank, offset) = if mover.is_white() { (0, 0) } else { (56, 2) };
+        let king = Some(Piece(PieceType::K, mover));
+        let rook = Some(Piece(PieceType::R, mover));
+        // (king destination, rook destination, rook origin, castling right, type of castling)
+        let castlings = [(6, 5, 7, 0, SpecialMoveType::CastlingKingside), (2, 3, 0, 1, SpecialMoveType::CastlingQueenside)];
+        let mut unmoves = Vec::new();
+        for (king_dest, rook_dest, rook_src, right, spec) in castlings {
+            if self.content[rank + king_dest] != king || self.content[rank + rook_dest] != rook {
+                continue;
+            }
+            let mut content = self.content;
+            (content[rank + king_dest], content[rank + rook_dest]) = (None, None);
+            if content[rank + 4].is_some() || content[rank + rook_src].is_some() {
+                continue;
+            }
+            (content[rank + 4], content[rank + rook_src]) = (king, rook);
+            let mut castling_rights = self.castling_rights;
+            castling_rights[offset + right] = Some(rank + rook_src);
+            let position = Position {
+                content,
+                side: mover,
+                castling_rights,
+                ep_target: None,
+            };
+            let move_ = Move(rank + 4, rank + king_dest, Some(spec));
+            if self.is_retraction(&position, move_) {
+                unmoves.push(Unmove { move_, uncaptured: None, position });
+            }
+        }
+        unmoves
+    }
+
+    /// Checks whether a position is legal and a legal move from it leads to this position.
+    /// The en passant target square of this position is not compared if it has none, as it is often left out after a double pawn push.
+    fn is_retraction(&self, previous: &Position, move_: Move) -> bool {
+        let back_ranks = (0..8).chain(56..64);
+        if back_ranks.into_iter().any(|sq| matches!(previous.content[sq], Some(Piece(PieceType::P, _))))
+            || ![Color::White, Color::Black].into_iter().all(|color| material_is_possible(&previous.content, color))
+            || helpers::king_capture_pseudolegal(&previous.content, previous.side)
+            || !previous.gen_non_illegal_moves_sq(move_.0).contains(&move_)
+        {
+            return false;
+        }
+        let next = previous.with_legal_move_made(move_);
+        next.content == self.content && next.side == self.side && next.castling_rights == self.castling_rights && (self.ep_target.is_none() || next.ep_target == self.ep_target)
+    }
+}
+
+/// Returns the index of the square on the given file and rank, or `None` if they are off the board.
+fn square(file: isize, rank: isize) -> Option<usize> {
+    ((0..8).contains(&file) && (0..8).contains(&rank)).then_some((rank * 8 + file) as usize)
+}
+
+/// Returns the squares from which a piece other than a pawn could have moved to `dest`, which are the empty squares it reaches from `dest`.
+fn origins(content: &[Option<Piece>; 64], piece_type: PieceType, dest: usize) -> Vec<usize> {
+    let (directions, slides): (&[(isize, isize)], bool) = match piece_type {
+        PieceType::K => (&ROYAL_DIRECTIONS, false),
+        PieceType::N => (&KNIGHT_JUMPS, false),
+        PieceType::Q => (&ROYAL_DIRECTIONS, true),
+        PieceType::R => (&ROYAL_DIRECTIONS[..4], true),
+        PieceType::B => (&ROYAL_DIRECTIONS[4..], true),
+        PieceType::P => (&[], false),
+    };
+    let mut origins = Vec::new();
+    for &(files, ranks) in directions {
+        let (mut file, mut rank) = ((dest % 8) as isize + files, (dest / 8) as isize + ranks);
+        while let Some(sq) = square(file, rank).filter(|&sq| content[sq].is_none()) {
+            origins.push(sq);
+            if !slides {
+                break;
+            }
+            (file, rank) = (file + files, rank + ranks);
+        }
+    }
+    origins
+}
+
+/// Checks whether a side can have its material, having no more pawns and promoted pieces than the 8 pawns it started with.
+fn material_is_possible(content: &[Option<Piece>; 64], color: Color) -> bool {
+    let count = |piece_type| content.iter().flatten().filter(|&&p| p == Piece(piece_type, color)).count();
+    let promoted = count(PieceType::Q).saturating_sub(1) + count(PieceType::R).saturating_sub(2) + count(PieceType::B).saturating_sub(2) + count(PieceType::N).saturating_sub(2);
+    count(PieceType::P) + promoted <= 8
+}
@@ -0,0 +1,730 @@
+//! Endgame tablebases generated by retrograde analysis, giving the result with perfect play and the distance to mate of positions with up to four pieces.
+//!
+//! Tables are generated from the move generation of [`Position`], so no external files are needed, and can be saved to disk to avoid generating them again.
+//! The fifty-move rule is not taken into account, and positions with castling rights are not covered.
+//!
+//! The [`syzygy`] module probes Syzygy tables stored on disk instead, covering endgames of up to seven pieces.
+
+use super::{helpers, Color, InvalidEndgameError, Move, Piece, PieceType, Position, SpecialMoveType, TablebaseError};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+pub mod syzygy;
+
+/// The maximum number of pieces of an endgame, kings included
+pub const MAX_PIECES: usize = 4;
+/// The bytes a saved table begins with
+const HEADER: &[u8; 4] = b"RSTB";
+/// The extension of the files of saved tables
+const EXTENSION: &str = "rtb";
+/// The order in which pieces other than kings are listed, from the most to the least valuable
+const PIECE_ORDER: [PieceType; 5] = [PieceType::Q, PieceType::R, PieceType::B, PieceType::N, PieceType::P];
+/// The squares of the a1-d1-d4 triangle, to which the symmetries of the board bring the white king in pawnless endgames
+const PAWNLESS_KING_SQUARES: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+/// The stored value of drawn positions, which are the positions left unresolved by the generation
+const DRAW: u16 = 0;
+
+/// Represents the result of a position with perfect play, from the point of view of the side to move.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// Represents what a tablebase knows about a position.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Probe {
+    /// The result of the position with perfect play, for the side to move
+    pub wdl: Wdl,
+    /// The number of plies until mate with perfect play, or `None` if the position is drawn
+    pub dtm: Option<u32>,
+}
+
+impl Probe {
+    const DRAW: Self = Self { wdl: Wdl::Draw, dtm: None };
+
+    /// Returns the value of the position before the move that led to this one, for the side that made the move.
+    fn for_mover(self) -> Self {
+        let wdl = match self.wdl {
+            Wdl::Win => Wdl::Loss,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::Loss => Wdl::Win,
+        };
+        Self {
+            wdl,
+            dtm: self.dtm.map(|dtm| dtm + 1),
+        }
+    }
+
+    /// Scores the value for the side to move, so that quicker wins score higher than slower ones, and slower losses higher than quicker ones.
+    fn score(self) -> i64 {
+        match (self.wdl, self.dtm) {
+            (Wdl::Win, Some(dtm)) => 1_000_000 - dtm as i64,
+            (Wdl::Loss, Some(dtm)) => -1_000_000 + dtm as i64,
+            _ => 0,
+        }
+    }
+
+    /// Returns the better of two values for the side to move.
+    fn max(self, other: Self) -> Self {
+        if other.score() > self.score() {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Encodes the value as the distance to mate plus one, which is even for wins and odd for losses, or [`DRAW`] for draws.
+    fn encode(self) -> u16 {
+        self.dtm.map_or(DRAW, |dtm| dtm as u16 + 1)
+    }
+
+    /// Decodes a value encoded with [`Probe::encode`].
+    fn decode(value: u16) -> Self {
+        match value {
+            DRAW => Self::DRAW,
+            _ => Self {
+                wdl: if value.is_multiple_of(2) { Wdl::Win } else { Wdl::Loss },
+                dtm: Some(value as u32 - 1),
+            },
+        }
+    }
+}
+
+/// Represents the material of an endgame, such as KQvK. The stronger side is always White, so that `KvKQ` and `KQvK` are the same endgame.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Endgame {
+    white: Vec<PieceType>,
+    black: Vec<PieceType>,
+}
+
+impl Endgame {
+    /// Creates an endgame from the pieces other than kings of each side, swapping the sides if Black has the stronger material.
+    /// Returns an error if a king is given or there are more than [`MAX_PIECES`] pieces.
+    pub fn new(white: &[PieceType], black: &[PieceType]) -> Result<Self, InvalidEndgameError> {
+        let (mut white, mut black) = (white.to_vec(), black.to_vec());
+        let endgame_name = || {
+            format!(
+                "K{}vK{}",
+                white.iter().map(|&t| char::from(t)).collect::<String>(),
+                black.iter().map(|&t| char::from(t)).collect::<String>()
+            )
+        };
+        if white.len() + black.len() + 2 > MAX_PIECES || white.contains(&PieceType::K) || black.contains(&PieceType::K) {
+            return Err(InvalidEndgameError(endgame_name()));
+        }
+        white.sort_by_key(|&t| piece_order(t));
+        black.sort_by_key(|&t| piece_order(t));
+        if strength(&black) > strength(&white) {
+            (white, black) = (black, white);
+        }
+        Ok(Self { white, black })
+    }
+
+    /// Returns the endgame of a position, or `None` if the position has more than [`MAX_PIECES`] pieces.
+    pub fn of(position: &Position) -> Option<Self> {
+        locate(&position.content, position.side).map(|(endgame, _)| endgame)
+    }
+
+    /// Returns the pieces other than the king of the stronger side, which plays White in the table of the endgame.
+    pub fn white(&self) -> &[PieceType] {
+        &self.white
+    }
+
+    /// Returns the pieces other than the king of the weaker side, which plays Black in the table of the endgame.
+    pub fn black(&self) -> &[PieceType] {
+        &self.black
+    }
+
+    /// Returns the number of pieces of the endgame, kings included.
+    pub fn piece_count(&self) -> usize {
+        self.white.len() + self.black.len() + 2
+    }
+
+    /// Checks whether the endgame has pawns, in which case only the left-right symmetry of the board can be used.
+    fn has_pawns(&self) -> bool {
+        self.white.contains(&PieceType::P) || self.black.contains(&PieceType::P)
+    }
+
+    /// Returns the number of squares the symmetries of the board allow for the white king.
+    fn king_squares(&self) -> usize {
+        if self.has_pawns() {
+            32
+        } else {
+            PAWNLESS_KING_SQUARES.len()
+        }
+    }
+
+    /// Returns the number of entries of the table of the endgame.
+    fn size(&self) -> usize {
+        2 * self.king_squares() * 64usize.pow(self.piece_count() as u32 - 1)
+    }
+
+    /// Returns the pieces of the endgame in the order in which their squares are indexed: the kings, then the other white pieces and the other black pieces.
+    fn pieces(&self) -> Vec<Piece> {
+        [Piece(PieceType::K, Color::White), Piece(PieceType::K, Color::Black)]
+            .into_iter()
+            .chain(self.white.iter().map(|&t| Piece(t, Color::White)))
+            .chain(self.black.iter().map(|&t| Piece(t, Color::Black)))
+            .collect()
+    }
+
+    /// Returns the endgames this endgame can turn into by a capture, a promotion, or both at once.
+    fn subgames(&self) -> Vec<Self> {
+        let mut subgames = Vec::new();
+        for (movers, others, white_moves) in [(&self.white, &self.black, true), (&self.black, &self.white, false)] {
+            let mut promoted = vec![movers.clone()];
+            for (i, _) in movers.iter().enumerate().filter(|(_, &t)| t == PieceType::P) {
+                for piece_type in [PieceType::Q, PieceType::R, PieceType::B, PieceType::N] {
+                    let mut pieces = movers.clone();
+                    pieces[i] = piece_type;
+                    promoted.push(pieces);
+                }
+            }
+            let mut captured = vec![others.clone()];
+            for i in 0..others.len() {
+                let mut pieces = others.clone();
+                pieces.remove(i);
+                captured.push(pieces);
+            }
+            for movers in &promoted {
+                for others in &captured {
+                    let (white, black) = if white_moves { (movers, others) } else { (others, movers) };
+                    let subgame = Self::new(white, black).unwrap();
+                    if &subgame != self && !subgames.contains(&subgame) {
+                        subgames.push(subgame);
+                    }
+                }
+            }
+        }
+        subgames
+    }
+
+    /// Returns the position at the given index of the table of the endgame, or `None` if the index does not stand for a legal position
+    /// or is not the index [`Endgame::index`] gives to the position.
+    fn position(&self, index: usize) -> Option<Position> {
+        let side = if index.is_multiple_of(2) { Color::White } else { Color::Black };
+        let mut rest = index / 2;
+        let pieces = self.pieces();
+        let mut content = [None; 64];
+        content[king_square(rest % self.king_squares(), self.has_pawns())] = Some(pieces[0]);
+        rest /= self.king_squares();
+        for &piece in &pieces[1..] {
+            let sq = rest % 64;
+            rest /= 64;
+            if content[sq].is_some() || piece.0 == PieceType::P && !(8..56).contains(&sq) {
+                return None;
+            }
+            content[sq] = Some(piece);
+        }
+        if king_attacked(&content, !side) || self.index(&content, side) != index {
+            return None;
+        }
+        Some(Position {
+            content,
+            side,
+            castling_rights: [None; 4],
+            ep_target: None,
+        })
+    }
+
+    /// Returns the index in the table of the endgame of a position of the endgame with the stronger side as White.
+    /// The position is first brought by the symmetries of the board to a position with the white king on an allowed square,
+    /// and when two symmetries do so, the one giving the lower index is used, so that all symmetric positions share the same index.
+    fn index(&self, content: &[Option<Piece>; 64], side: Color) -> usize {
+        let pawns = self.has_pawns();
+        let mut pieces = [(false, false, 0, 0); MAX_PIECES];
+        for (i, (sq, Piece(t, c))) in content.iter().enumerate().filter_map(|(sq, p)| p.map(|p| (sq, p))).enumerate() {
+            pieces[i] = (t != PieceType::K, c.is_black(), piece_order(t), sq);
+        }
+        let pieces = &mut pieces[..self.piece_count()];
+        pieces.sort_unstable();
+        let king = pieces[0].3;
+        let mut symmetry = (king % 8 >= 4) as usize;
+        if !pawns {
+            symmetry |= ((king / 8 >= 4) as usize) << 1;
+        }
+        let (file, rank) = (transform(king, symmetry) % 8, transform(king, symmetry) / 8);
+        let symmetries = match rank.cmp(&file) {
+            _ if pawns => [symmetry, symmetry],
+            Ordering::Less => [symmetry, symmetry],
+            Ordering::Equal => [symmetry, symmetry | 4],
+            Ordering::Greater => [symmetry | 4, symmetry | 4],
+        };
+        symmetries
+            .into_iter()
+            .map(|symmetry| {
+                let mut squares = [(false, false, 0, 0); MAX_PIECES];
+                for (square, &(not_king, black, order, sq)) in squares.iter_mut().zip(pieces.iter()) {
+                    *square = (not_king, black, order, transform(sq, symmetry));
+                }
+                let squares = &mut squares[..pieces.len()];
+                squares.sort_unstable();
+                let king = king_index(squares[0].3, pawns).unwrap();
+                let rest = squares[1..].iter().rev().fold(0, |index, s| index * 64 + s.3);
+                (rest * self.king_squares() + king) * 2 + side.is_black() as usize
+            })
+            .min()
+            .unwrap()
+    }
+}
+
+impl TryFrom<&str> for Endgame {
+    type Error = InvalidEndgameError;
+
+    /// Attempts to parse an endgame written like KQvK or KRPvKR.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || InvalidEndgameError(value.to_owned());
+        let pieces = |side: &str| {
+            side.strip_prefix('K')
+                .ok_or_else(invalid)?
+                .chars()
+                .map(|c| match PieceType::try_from(c) {
+                    Ok(piece_type) if c.is_ascii_uppercase() && piece_type != PieceType::K => Ok(piece_type),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let (white, black) = value.split_once('v').ok_or_else(invalid)?;
+        Self::new(&pieces(white)?, &pieces(black)?).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for Endgame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pieces = |pieces: &[PieceType]| pieces.iter().map(|&t| char::from(t)).collect::<String>();
+        write!(f, "K{}vK{}", pieces(&self.white), pieces(&self.black))
+    }
+}
+
+/// Represents the generated table of an endgame, holding the value of every position of the endgame.
+#[derive(Clone, Debug)]
+pub struct Table {
+    endgame: Endgame,
+    values: Vec<u16>,
+}
+
+impl Table {
+    /// Returns the endgame of the table.
+    pub fn endgame(&self) -> &Endgame {
+        &self.endgame
+    }
+
+    /// Reads a table written with [`Table::write_to`].
+    pub fn read_from(mut reader: impl Read) -> Result<Self, TablebaseError> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != HEADER {
+            return Err(TablebaseError::Header);
+        }
+        let mut name = vec![0; header[4] as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        let endgame = Endgame::try_from(name.as_str())?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() != 2 * endgame.size() {
+            return Err(TablebaseError::Length(name, bytes.len(), 2 * endgame.size()));
+        }
+        let values = bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+        Ok(Self { endgame, values })
+    }
+
+    /// Opens a table saved with [`Table::save`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TablebaseError> {
+        Self::read_from(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Writes the table: a header, the name of the endgame, then the value of every position.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let name = self.endgame.to_string();
+        writer.write_all(HEADER)?;
+        writer.write_all(&[name.len() as u8])?;
+        writer.write_all(name.as_bytes())?;
+        for value in &self.values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Saves the table to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Returns the value of the position at the given index.
+    fn value(&self, index: usize) -> Probe {
+        Probe::decode(self.values[index])
+    }
+}
+
+/// Represents a collection of endgame tables, which can be generated, saved, loaded and probed.
+#[derive(Clone, Debug, Default)]
+pub struct Tablebase {
+    tables: HashMap<Endgame, Table>,
+}
+
+impl Tablebase {
+    /// Creates a tablebase with no tables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every table saved in a directory with [`Tablebase::save`].
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, TablebaseError> {
+        let mut tablebase = Self::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == EXTENSION) {
+                tablebase.insert(Table::open(path)?);
+            }
+        }
+        Ok(tablebase)
+    }
+
+    /// Saves every table to a directory, in a file named after its endgame (e.g. `KQvK.rtb`).
+    pub fn save(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        for table in self.tables.values() {
+            table.save(dir.as_ref().join(format!("{}.{EXTENSION}", table.endgame)))?;
+        }
+        Ok(())
+    }
+
+    /// Adds a table to the tablebase, replacing the table of the same endgame if there is one.
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.endgame.clone(), table);
+    }
+
+    /// Returns the table of an endgame, if the tablebase has it.
+    pub fn table(&self, endgame: &Endgame) -> Option<&Table> {
+        self.tables.get(endgame)
+    }
+
+    /// Returns all tables of the tablebase, in no particular order.
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values()
+    }
+
+    /// Generates the table of an endgame, along with the tables of all the endgames it can turn into by captures and promotions.
+    /// Tables which the tablebase already has are not generated again.
+    ///
+    /// Tables of three pieces take seconds to generate, while tables of four pieces can take minutes, so they are best saved once generated.
+    pub fn generate(&mut self, endgame: &Endgame) {
+        if self.tables.contains_key(endgame) {
+            return;
+        }
+        for subgame in endgame.subgames() {
+            self.generate(&subgame);
+        }
+        let table = self.build(endgame.clone());
+        self.insert(table);
+    }
+
+    /// Probes the tablebase for the value of a position, returning `None` if the tablebase does not have the table of its endgame
+    /// or the position has castling rights.
+    pub fn probe(&self, position: &Position) -> Option<Probe> {
+        if position.castling_rights.iter().any(Option::is_some) {
+            return None;
+        }
+        let (endgame, index) = locate(&position.content, position.side)?;
+        let mut probe = self.tables.get(&endgame)?.value(index);
+        if position.ep_target.is_some() {
+            for move_ in legal_moves(position).into_iter().filter(|m| m.2 == Some(SpecialMoveType::EnPassant)) {
+                probe = probe.max(self.probe(&make_move(position, move_))?.for_mover());
+            }
+        }
+        Some(probe)
+    }
+
+    /// Returns a move that keeps the result of the position with perfect play: the quickest mate when winning, the slowest one when losing,
+    /// and any drawing move otherwise. Returns `None` if the position cannot be probed or there are no legal moves.
+    pub fn best_move(&self, position: &Position) -> Option<Move> {
+        self.probe(position)?;
+        let mut best: Option<(Move, Probe)> = None;
+        for move_ in legal_moves(position) {
+            let value = self.probe(&make_move(position, move_))?.for_mover();
+            if best.is_none_or(|(_, b)| value.score() > b.score()) {
+                best = Some((move_, value));
+            }
+        }
+        best.map(|(move_, _)| move_)
+    }
+
+    /// Generates the table of an endgame by retrograde analysis, assuming the tables of the endgames it can turn into are present.
+    ///
+    /// Positions are resolved in order of distance to mate. A position is examined at the plies at which its captures and promotions mate,
+    /// and again after each ply in which one of the positions its other moves lead to was resolved. It is resolved as soon as its best move
+    /// is known to win, or all of its moves are known to lose. The positions never resolved are draws.
+    fn build(&self, endgame: Endgame) -> Table {
+        let mut values = vec![DRAW; endgame.size()];
+        let mut queues: Vec<Vec<usize>> = Vec::new();
+        let schedule = |queues: &mut Vec<Vec<usize>>, ply: usize, index: usize| {
+            if queues.len() <= ply {
+                queues.resize(ply + 1, Vec::new());
+            }
+            queues[ply].push(index);
+        };
+        for index in 0..values.len() {
+            if let Some(position) = endgame.position(index) {
+                for ply in self.static_plies(&position) {
+                    schedule(&mut queues, ply as usize, index);
+                }
+            }
+        }
+        let mut ply = 0;
+        while ply < queues.len() {
+            let mut queue = std::mem::take(&mut queues[ply]);
+            queue.sort_unstable();
+            queue.dedup();
+            let mut resolved = Vec::new();
+            for index in queue {
+                if values[index] != DRAW {
+                    continue;
+                }
+                let Some(position) = endgame.position(index) else { continue };
+                if let Some(probe) = self.evaluate(&endgame, &values, &position, ply as u32) {
+                    values[index] = probe.encode();
+                    resolved.push(position);
+                }
+            }
+            for position in resolved {
+                for index in predecessors(&endgame, &position) {
+                    schedule(&mut queues, ply + 1, index);
+                }
+            }
+            ply += 1;
+        }
+        Table { endgame, values }
+    }
+
+    /// Returns the plies at which a position may be resolved regardless of the other positions of its table: 0 if it is checkmate,
+    /// the distances to mate of its captures and promotions, and the plies after the en passant captures its double pawn pushes allow.
+    fn static_plies(&self, position: &Position) -> Vec<u32> {
+        let moves = legal_moves(position);
+        if moves.is_empty() && king_attacked(&position.content, position.side) {
+            return vec![0];
+        }
+        let mut plies = Vec::new();
+        for move_ in moves {
+            let child = make_move(position, move_);
+            let value = if changes_material(position, move_) {
+                self.probe(&child).map(Probe::for_mover)
+            } else {
+                self.en_passant_value(&child).map(Probe::for_mover)
+            };
+            plies.extend(value.and_then(|v| v.dtm));
+        }
+        plies.sort_unstable();
+        plies.dedup();
+        plies
+    }
+
+    /// Evaluates a position of the table being generated when examined at the given ply, returning its value if it is resolved.
+    fn evaluate(&self, endgame: &Endgame, values: &[u16], position: &Position, ply: u32) -> Option<Probe> {
+        let move_values = self.move_values(endgame, values, position, ply);
+        if move_values.is_empty() {
+            return king_attacked(&position.content, position.side).then_some(Probe { wdl: Wdl::Loss, dtm: Some(0) });
+        }
+        let best = move_values.iter().flatten().copied().max_by_key(|v| v.score())?;
+        let resolved = best.dtm.is_some_and(|dtm| dtm <= ply) && (best.wdl == Wdl::Win || move_values.iter().all(Option::is_some));
+        resolved.then_some(best)
+    }
+
+    /// Returns the value of each legal move in a position of the table being generated, for the side to move,
+    /// or `None` for the moves whose value is not known yet. All positions at a distance to mate below `ply` are assumed to be resolved.
+    fn move_values(&self, endgame: &Endgame, values: &[u16], position: &Position, ply: u32) -> Vec<Option<Probe>> {
+        legal_moves(position)
+            .into_iter()
+            .map(|move_| {
+                let child = make_move(position, move_);
+                let value = if changes_material(position, move_) {
+                    self.probe(&child)
+                } else {
+                    Some(values[endgame.index(&child.content, child.side)]).filter(|&v| v != DRAW).map(Probe::decode)
+                };
+                // a double pawn push which allows an en passant capture leads to the same position as in the table, with an extra move for the opponent
+                let value = match (value, self.en_passant_value(&child)) {
+                    (Some(value), Some(reply)) => Some(value.max(reply)),
+                    (None, Some(reply)) if reply.wdl == Wdl::Win && reply.dtm.unwrap() < ply => Some(reply),
+                    (value, None) => value,
+                    (None, Some(_)) => None,
+                };
+                value.map(Probe::for_mover)
+            })
+            .collect()
+    }
+
+    /// Returns the value of the best en passant capture in a position, for the side to move, or `None` if there is no legal en passant capture.
+    fn en_passant_value(&self, position: &Position) -> Option<Probe> {
+        position.ep_target?;
+        legal_moves(position)
+            .into_iter()
+            .filter(|m| m.2 == Some(SpecialMoveType::EnPassant))
+            .map(|move_| self.probe(&make_move(position, move_)).unwrap().for_mover())
+            .max_by_key(|v| v.score())
+    }
+}
+
+/// Returns the index of a piece type in [`PIECE_ORDER`], with kings first.
+fn piece_order(piece_type: PieceType) -> usize {
+    PIECE_ORDER.iter().position(|&t| t == piece_type).map_or(0, |i| i + 1)
+}
+
+/// Returns a key comparing the material of a side, for the pieces other than the king sorted from the most to the least valuable.
+fn strength(pieces: &[PieceType]) -> (usize, Vec<usize>) {
+    (pieces.len(), pieces.iter().map(|&t| PIECE_ORDER.len() + 1 - piece_order(t)).collect())
+}
+
+/// Applies one of the 8 symmetries of the board to a square: bit 0 of `symmetry` mirrors the files, bit 1 the ranks, and bit 2 swaps files and ranks.
+fn transform(sq: usize, symmetry: usize) -> usize {
+    let (mut file, mut rank) = (sq % 8, sq / 8);
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        (file, rank) = (rank, file);
+    }
+    rank * 8 + file
+}
+
+/// Returns the index of a square among the squares allowed for the white king, or `None` if the white king cannot stand there.
+fn king_index(sq: usize, pawns: bool) -> Option<usize> {
+    if pawns {
+        (sq % 8 < 4).then_some(sq / 8 * 4 + sq % 8)
+    } else {
+        PAWNLESS_KING_SQUARES.iter().position(|&s| s == sq)
+    }
+}
+
+/// Returns the square allowed for the white king at the given index.
+fn king_square(index: usize, pawns: bool) -> usize {
+    if pawns {
+        index / 4 * 8 + index % 4
+    } else {
+        PAWNLESS_KING_SQUARES[index]
+    }
+}
+
+/// Returns the endgame of a board along with the index of the position in the table of the endgame, or `None` if there are more than [`MAX_PIECES`] pieces.
+/// Colors are swapped if Black has the stronger material.
+fn locate(content: &[Option<Piece>; 64], side: Color) -> Option<(Endgame, usize)> {
+    if content.iter().flatten().count() > MAX_PIECES {
+        return None;
+    }
+    let of_color = |color| content.iter().flatten().filter(|p| p.0 != PieceType::K && p.1 == color).map(|p| p.0).collect::<Vec<_>>();
+    let (white, black) = (of_color(Color::White), of_color(Color::Black));
+    let endgame = Endgame::new(&white, &black).ok()?;
+    if strength(&black) > strength(&white) {
+        let mut flipped = [None; 64];
+        for (sq, piece) in content.iter().enumerate() {
+            flipped[sq ^ 56] = piece.map(|Piece(t, c)| Piece(t, !c));
+        }
+        let index = endgame.index(&flipped, !side);
+        Some((endgame, index))
+    } else {
+        let index = endgame.index(content, side);
+        Some((endgame, index))
+    }
+}
+
+/// Returns the indices of the positions from which a move that is neither a capture nor a promotion leads to the given position.
+/// Some of them may not be legal positions, which the generation skips.
+fn predecessors(endgame: &Endgame, position: &Position) -> Vec<usize> {
+    let mover = !position.side;
+    let retracting = Position { side: mover, ..position.clone() };
+    let mut indices = Vec::new();
+    for (sq, piece) in position.content.iter().enumerate() {
+        let Some(piece @ Piece(piece_type, color)) = *piece else { continue };
+        if color != mover {
+            continue;
+        }
+        let origins = match piece_type {
+            PieceType::P => {
+                let (back, rank) = if color.is_white() { (-8, sq / 8) } else { (8, 7 - sq / 8) };
+                let behind = (sq as isize + back) as usize;
+                let mut origins = Vec::new();
+                if rank >= 2 && position.content[behind].is_none() {
+                    origins.push(behind);
+                    let start = (behind as isize + back) as usize;
+                    if rank == 3 && position.content[start].is_none() {
+                        origins.push(start);
+                    }
+                }
+                origins
+            }
+            _ => retracting
+                .gen_pseudolegal_moves_sq(sq)
+                .into_iter()
+                .filter(|&Move(_, dest, _)| position.content[dest].is_none())
+                .map(|Move(_, dest, _)| dest)
+                .collect(),
+        };
+        for origin in origins {
+            let mut content = position.content;
+            (content[sq], content[origin]) = (None, Some(piece));
+            indices.push(endgame.index(&content, mover));
+        }
+    }
+    indices
+}
+
+/// Generates the legal moves of a position without castling rights. Unlike [`Position::gen_non_illegal_moves`], this does not
+/// fill the cache of legal moves, which would otherwise end up holding every position of a table.
+fn legal_moves(position: &Position) -> Vec<Move> {
+    (0..64)
+        .filter(|&sq| position.content[sq].is_some_and(|p| p.1 == position.side))
+        .flat_map(|sq| position.gen_pseudolegal_moves_sq(sq))
+        .filter(|move_| !king_attacked(&helpers::change_content(&position.content, move_, &position.castling_rights), position.side))
+        .collect()
+}
+
+/// Makes a move in a position without castling rights, setting the en passant target square after a double pawn push.
+fn make_move(position: &Position, move_: Move) -> Position {
+    let Move(src, dest, _) = move_;
+    let double_push = position.content[src].is_some_and(|p| p.0 == PieceType::P) && src.abs_diff(dest) == 16;
+    Position {
+        content: helpers::change_content(&position.content, &move_, &position.castling_rights),
+        side: !position.side,
+        castling_rights: [None; 4],
+        ep_target: double_push.then_some((src + dest) / 2),
+    }
+}
+
+/// Checks whether a move is a capture or a promotion, and therefore leads to another endgame.
+fn changes_material(position: &Position, Move(_, dest, spec): Move) -> bool {
+    position.content[dest].is_some() || matches!(spec, Some(SpecialMoveType::Promotion(_) | SpecialMoveType::EnPassant))
+}
+
+/// Checks whether the king of the given color is attacked, looking at each of the few pieces on the board in turn.
+fn king_attacked(content: &[Option<Piece>; 64], color: Color) -> bool {
+    let king = helpers::find_king(color, content);
+    content.iter().enumerate().any(|(sq, piece)| match piece {
+        Some(Piece(piece_type, c)) if *c != color => attacks(content, *piece_type, *c, sq, king),
+        _ => false,
+    })
+}
+
+/// Checks whether a piece of the given type and color standing on the square `from` attacks the square `to`.
+fn attacks(content: &[Option<Piece>; 64], piece_type: PieceType, color: Color, from: usize, to: usize) -> bool {
+    let (files, ranks) = ((to % 8) as isize - (from % 8) as isize, (to / 8) as isize - (from / 8) as isize);
+    let (straight, diagonal) = ((files == 0) != (ranks == 0), files != 0 && files.abs() == ranks.abs());
+    let slides = match piece_type {
+        PieceType::K => return files.abs().max(ranks.abs()) == 1,
+        PieceType::N => return files.abs() * ranks.abs() == 2,
+        PieceType::P => return files.abs() == 1 && ranks == if color.is_white() { 1 } else { -1 },
+        PieceType::Q => straight || diagonal,
+        PieceType::R => straight,
+        PieceType::B => diagonal,
+    };
+    let step = ranks.signum() * 8 + files.signum();
+    slides && (1..files.abs().max(ranks.abs())).all(|i| content[(from as isize + i * step) as usize].is_none())
+}
@@ -0,0 +1,1038 @@
+//! Probing of Syzygy tablebases stored on disk, giving the result (WDL) and the distance to zeroing (DTZ) of positions with up to seven pieces,
+//! and ranking the legal moves of a game according to the fifty-move rule.
+//!
+//! WDL tables (`.rtbw` files) give the result of a position with perfect play, distinguishing the wins and losses that the fifty-move rule
+//! turns into draws. DTZ tables (`.rtbz` files) give the number of plies until the next capture or pawn move (which resets the halfmove clock)
+//! with perfect play. A table is opened the first time a position of its endgame is probed, and only the parts of the file needed by each probe
+//! are read, so large collections of tables can be used without loading them into memory.
+
+use super::{king_attacked, legal_moves, make_move, PIECE_ORDER};
+use crate::{Board, Color, Move, Piece, PieceType, Position, SpecialMoveType, SyzygyError};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    iter,
+    ops::Neg,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+/// The maximum number of pieces of a Syzygy table, kings included
+pub const MAX_PIECES: usize = 7;
+/// The extension of the files of WDL tables
+const WDL_EXTENSION: &str = "rtbw";
+/// The extension of the files of DTZ tables
+const DTZ_EXTENSION: &str = "rtbz";
+/// The bytes the files of WDL tables begin with
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+/// The bytes the files of DTZ tables begin with
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+/// The flag of the header of a table whose endgame has different material for both sides, so that both sides to move are stored
+const SPLIT: u8 = 1;
+/// The flag of the header of a table whose endgame has pawns, so that the positions are split by the file of the leading pawn
+const HAS_PAWNS: u8 = 2;
+/// The flag of a DTZ table telling that the stored side to move is Black
+const STM: u8 = 1;
+/// The flag of a DTZ table whose values are indices into a map of values
+const MAPPED: u8 = 2;
+/// The flag of a DTZ table whose winning values are stored in plies rather than moves
+const WIN_PLIES: u8 = 4;
+/// The flag of a DTZ table whose losing values are stored in plies rather than moves
+const LOSS_PLIES: u8 = 8;
+/// The flag of a DTZ table whose map holds 16-bit values
+const WIDE: u8 = 16;
+/// The flag of a table in which all positions have the same value
+const SINGLE_VALUE: u8 = 128;
+/// The rank of root moves that win without the fifty-move rule getting in the way
+const MAX_RANK: i32 = 1 << 18;
+
+/// Represents the result of a position with perfect play, from the point of view of the side to move, ordered from the worst to the best.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug)]
+pub enum Wdl {
+    /// The side to move loses
+    Loss,
+    /// The side to move loses, but the fifty-move rule lets it claim a draw first
+    BlessedLoss,
+    /// The game is drawn
+    Draw,
+    /// The side to move wins, but the fifty-move rule lets the opponent claim a draw first
+    CursedWin,
+    /// The side to move wins
+    Win,
+}
+
+impl Wdl {
+    /// Converts a value from -2 (loss) to 2 (win) to a `Wdl`.
+    fn from_value(value: i32) -> Self {
+        match value {
+            ..=-2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            _ => Self::Win,
+        }
+    }
+
+    /// Returns the value of the result, from -2 (loss) to 2 (win).
+    fn value(self) -> i32 {
+        self as i32 - 2
+    }
+
+    /// Returns the DTZ of a position whose best move is a capture or a pawn move leading to this result.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Self::Loss => -1,
+            Self::BlessedLoss => -101,
+            Self::Draw => 0,
+            Self::CursedWin => 101,
+            Self::Win => 1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    /// Returns the result for the other side.
+    fn neg(self) -> Self {
+        Self::from_value(-self.value())
+    }
+}
+
+/// Represents a legal move of a game along with what the tablebase says about it.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct RootMove {
+    /// The move
+    pub move_: Move,
+    /// The result of the game after the move with perfect play, for the side making the move, taking the fifty-move rule into account
+    pub wdl: Wdl,
+    /// The number of plies from before the move until the next capture or pawn move with perfect play,
+    /// positive if the side making the move wins, negative if it loses, and 0 if the game is drawn
+    pub dtz: i32,
+    /// The rank of the move, higher for better moves: wins first (the quickest ones first), then draws, then losses (the slowest ones first)
+    pub rank: i32,
+}
+
+/// Represents a collection of Syzygy tables found on disk.
+#[derive(Debug, Default)]
+pub struct SyzygyTablebase {
+    /// The WDL tables, by name of endgame (e.g. KRvK)
+    wdl: HashMap<String, TableFile>,
+    /// The DTZ tables, by name of endgame
+    dtz: HashMap<String, TableFile>,
+    /// The number of pieces of the largest endgame with a WDL table
+    max_pieces: usize,
+}
+
+impl SyzygyTablebase {
+    /// Creates a tablebase with no tables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a tablebase with the tables of a directory, as with [`SyzygyTablebase::add_directory`].
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, SyzygyError> {
+        let mut tablebase = Self::new();
+        tablebase.add_directory(dir)?;
+        Ok(tablebase)
+    }
+
+    /// Adds the tables of a directory, which are the files named after their endgame (e.g. `KRvK.rtbw` and `KRvK.rtbz`),
+    /// replacing the tables of the same endgames if there are any. Returns the number of tables found.
+    ///
+    /// The files are not read until a position of their endgame is probed, so invalid tables are only reported then.
+    pub fn add_directory(&mut self, dir: impl AsRef<Path>) -> Result<usize, SyzygyError> {
+        let mut found = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let (Some(name), Some(extension)) = (path.file_stem().and_then(|s| s.to_str()), path.extension().and_then(|e| e.to_str())) else {
+                continue;
+            };
+            let Some(pieces) = piece_count(name) else {
+                continue;
+            };
+            let tables = match extension {
+                WDL_EXTENSION => {
+                    self.max_pieces = self.max_pieces.max(pieces);
+                    &mut self.wdl
+                }
+                DTZ_EXTENSION => &mut self.dtz,
+                _ => continue,
+            };
+            let name = name.to_owned();
+            tables.insert(name.clone(), TableFile { path, name, table: OnceLock::new() });
+            found += 1;
+        }
+        Ok(found)
+    }
+
+    /// Returns the number of pieces, kings included, of the largest endgame with a WDL table.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Probes the WDL tables for the result of a position with perfect play, for the side to move.
+    ///
+    /// The position is assumed to have just been reached by a capture or a pawn move, so a win is only cursed if it cannot be completed
+    /// within the fifty-move rule even then. To take the halfmove clock of a game into account, use [`SyzygyTablebase::probe_board`].
+    pub fn probe_wdl(&self, position: &Position) -> Result<Wdl, SyzygyError> {
+        self.check(position)?;
+        self.search(position, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Probes the WDL and DTZ tables for the number of plies until the next capture or pawn move with perfect play,
+    /// positive if the side to move wins, negative if it loses, and 0 if the game is drawn.
+    ///
+    /// Cursed wins and blessed losses are counted with an extra 100 plies, so that a value of more than 100 plies (in absolute value)
+    /// means the fifty-move rule draws the game. As some tables store moves rather than plies, the value may be one ply too high,
+    /// but never so that a win within the fifty-move rule would be counted as a cursed one.
+    pub fn probe_dtz(&self, position: &Position) -> Result<i32, SyzygyError> {
+        self.check(position)?;
+        self.dtz(position)
+    }
+
+    /// Probes the tables for the result of the game with perfect play, for the side to move, taking the halfmove clock of the board into account:
+    /// wins and losses that the fifty-move rule turns into draws from the current position are reported as cursed and blessed.
+    pub fn probe_board(&self, board: &Board) -> Result<Wdl, SyzygyError> {
+        let wdl = self.probe_wdl(board.position())?;
+        if board.halfmove_clock() == 0 || !matches!(wdl, Wdl::Win | Wdl::Loss) {
+            return Ok(wdl);
+        }
+        let dtz = self.dtz(board.position())?;
+        Ok(match wdl {
+            Wdl::Win if dtz + board.halfmove_clock() as i32 > 100 => Wdl::CursedWin,
+            Wdl::Loss if -dtz + board.halfmove_clock() as i32 > 100 => Wdl::BlessedLoss,
+            _ => wdl,
+        })
+    }
+
+    /// Probes the tables for each legal move of the board, taking the halfmove clock into account, and returns the moves from the best to the worst.
+    /// Winning moves that cannot win before the fifty-move rule applies are ranked below the other wins, and likewise for losing moves.
+    ///
+    /// Draws by repetition are not taken into account.
+    pub fn probe_root(&self, board: &Board) -> Result<Vec<RootMove>, SyzygyError> {
+        let position = board.position();
+        self.check(position)?;
+        let clock = board.halfmove_clock() as i32;
+        let mut root_moves = Vec::new();
+        for move_ in legal_moves(position) {
+            let next = make_move(position, move_);
+            let mut dtz = if is_zeroing(position, move_) {
+                (-self.search(&next, false)?.0).dtz_before_zeroing()
+            } else if clock + 1 >= 100 && !is_checkmate(&next) {
+                0
+            } else {
+                let dtz = -self.dtz(&next)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && is_checkmate(&next) {
+                dtz = 1;
+            }
+            let (wdl, rank) = match dtz.cmp(&0) {
+                Ordering::Greater if dtz + clock <= 100 => (Wdl::Win, MAX_RANK - dtz),
+                Ordering::Greater => (Wdl::CursedWin, MAX_RANK / 2 - dtz),
+                Ordering::Less if -dtz + clock <= 100 => (Wdl::Loss, -MAX_RANK - dtz),
+                Ordering::Less => (Wdl::BlessedLoss, -MAX_RANK / 2 - dtz),
+                Ordering::Equal => (Wdl::Draw, 0),
+            };
+            root_moves.push(RootMove { move_, wdl, dtz, rank });
+        }
+        root_moves.sort_by_key(|m| Reverse(m.rank));
+        Ok(root_moves)
+    }
+
+    /// Checks that a position can be probed, having no castling rights and no more pieces than the largest table.
+    fn check(&self, position: &Position) -> Result<(), SyzygyError> {
+        if position.castling_rights.iter().any(Option::is_some) {
+            return Err(SyzygyError::CastlingRights);
+        }
+        let pieces = position.content.iter().flatten().count();
+        if pieces > self.max_pieces.max(2) {
+            return Err(SyzygyError::TooManyPieces(pieces, self.max_pieces));
+        }
+        Ok(())
+    }
+
+    /// Returns the result of a position by searching its captures, and its pawn moves if `pawn_moves` is set, before probing the WDL table,
+    /// which does not account for the positions whose best move is a capture. Also returns whether the best move is one of the searched moves.
+    fn search(&self, position: &Position, pawn_moves: bool) -> Result<(Wdl, bool), SyzygyError> {
+        let moves = legal_moves(position);
+        let (mut best, mut searched) = (Wdl::Loss, 0);
+        for &move_ in &moves {
+            if !(is_capture(position, move_) || pawn_moves && is_pawn_move(position, move_)) {
+                continue;
+            }
+            searched += 1;
+            let value = -self.search(&make_move(position, move_), false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let value = if no_more_moves {
+            best
+        } else if position.content.iter().flatten().count() == 2 {
+            Wdl::Draw
+        } else {
+            let (table, flipped) = lookup(&self.wdl, position, false)?;
+            Wdl::from_value(table.probe(position, flipped, Wdl::Draw)?.unwrap_or(0))
+        };
+        if best >= value {
+            Ok((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Ok((value, false))
+        }
+    }
+
+    /// Returns the DTZ of a position, searching one ply ahead if the DTZ table only stores the positions with the other side to move.
+    fn dtz(&self, position: &Position) -> Result<i32, SyzygyError> {
+        let (wdl, zeroing) = self.search(position, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing {
+            return Ok(wdl.dtz_before_zeroing());
+        }
+        let (table, flipped) = lookup(&self.dtz, position, true)?;
+        if let Some(dtz) = table.probe(position, flipped, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.value().signum());
+        }
+        let mut min = i32::MAX;
+        for move_ in legal_moves(position) {
+            let (next, zeroing) = (make_move(position, move_), is_zeroing(position, move_));
+            let mut dtz = if zeroing { -self.search(&next, false)?.0.dtz_before_zeroing() } else { -self.dtz(&next)? };
+            if dtz == 1 && is_checkmate(&next) {
+                min = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min && dtz.signum() == wdl.value().signum() {
+                min = dtz;
+            }
+        }
+        Ok(if min == i32::MAX { -1 } else { min })
+    }
+}
+
+/// Represents the file of a table, which is opened the first time it is probed
+#[derive(Debug)]
+struct TableFile {
+    /// The path of the file
+    path: PathBuf,
+    /// The name of the endgame of the table
+    name: String,
+    /// The table, once opened
+    table: OnceLock<Table>,
+}
+
+impl TableFile {
+    /// Returns the table, opening it if it has not been opened yet.
+    fn table(&self, dtz: bool) -> Result<&Table, SyzygyError> {
+        if let Some(table) = self.table.get() {
+            return Ok(table);
+        }
+        let table = Table::open(&self.path, &self.name, dtz).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => SyzygyError::InvalidTable(self.path.display().to_string()),
+            _ => e.into(),
+        })?;
+        Ok(self.table.get_or_init(|| table))
+    }
+}
+
+/// Represents an opened table, holding the header of the file in memory and reading the compressed values from the file when probed
+#[derive(Debug)]
+struct Table {
+    /// The path of the file, to report errors
+    path: PathBuf,
+    /// The file of the table
+    file: Mutex<File>,
+    /// Whether this is a DTZ table rather than a WDL table
+    dtz: bool,
+    /// Whether both sides have the same material
+    symmetric: bool,
+    /// Whether the endgame has pawns
+    has_pawns: bool,
+    /// Whether the side with the fewer pawns also has pawns
+    both_pawns: bool,
+    /// Whether a side has only one piece of a type other than the king
+    unique_pieces: bool,
+    /// The compressed values, by side to move (of the stronger side's point of view) and file of the leading pawn
+    pairs: Vec<Vec<Pairs>>,
+    /// The maps of values of DTZ tables
+    map: Vec<u8>,
+}
+
+impl Table {
+    /// Opens the file of a table and reads its header.
+    fn open(path: &Path, name: &str, dtz: bool) -> io::Result<Self> {
+        let (white, black) = name.split_once('v').ok_or_else(invalid_data)?;
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let pawn_counts = if black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns) {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+        let (symmetric, has_pawns, both_pawns) = (white == black, pawn_counts[0] > 0, pawn_counts[1] > 0);
+        let unique_pieces = [white, black].iter().any(|side| "QRBNP".chars().any(|piece| count(side, piece) == 1));
+        let piece_count = white.len() + black.len();
+        let mut header = Header {
+            reader: BufReader::new(File::open(path)?),
+            bytes: Vec::new(),
+        };
+        let flags = header.take(5)?[4];
+        if header.bytes[..4] != if dtz { DTZ_MAGIC } else { WDL_MAGIC } || (flags & SPLIT == 0) != symmetric || (flags & HAS_PAWNS != 0) != has_pawns {
+            return Err(invalid_data());
+        }
+        let (sides, files) = (if dtz || symmetric { 1 } else { 2 }, if has_pawns { 4 } else { 1 });
+        let mut pairs: Vec<Vec<Pairs>> = (0..sides).map(|_| (0..files).map(|_| Pairs::default()).collect()).collect();
+        for file in 0..files {
+            let first = header.u8()?;
+            let second = if both_pawns { header.u8()? } else { 0xFF };
+            let orders = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            let pieces = header.take(piece_count)?.to_vec();
+            for (side, pairs) in pairs.iter_mut().enumerate() {
+                let d = &mut pairs[file];
+                d.pieces = pieces.iter().map(|b| if side == 0 { b & 0xF } else { b >> 4 }).collect();
+                d.set_groups(has_pawns, unique_pieces, both_pawns, orders[side], file)?;
+            }
+        }
+        header.align(2)?;
+        for file in 0..files {
+            for side in pairs.iter_mut() {
+                side[file].read_sizes(&mut header)?;
+            }
+        }
+        let mut map = Vec::new();
+        if dtz {
+            let start = header.bytes.len();
+            for d in pairs[0].iter_mut().filter(|d| d.flags & MAPPED != 0) {
+                for i in 0..4 {
+                    if d.flags & WIDE != 0 {
+                        header.align(2)?;
+                        d.map_idx[i] = (header.bytes.len() - start) / 2 + 1;
+                        let len = header.u16()? as usize;
+                        header.take(2 * len)?;
+                    } else {
+                        d.map_idx[i] = header.bytes.len() - start + 1;
+                        let len = header.u8()? as usize;
+                        header.take(len)?;
+                    }
+                }
+            }
+            map = header.bytes[start..].to_vec();
+            header.align(2)?;
+        }
+        let mut offset = header.bytes.len() as u64;
+        let all = |pairs: &mut Vec<Vec<Pairs>>, f: &mut dyn FnMut(&mut Pairs)| (0..files).for_each(|file| pairs.iter_mut().for_each(|side| f(&mut side[file])));
+        all(&mut pairs, &mut |d| {
+            d.sparse_index = offset;
+            offset += d.sparse_index_size * 6;
+        });
+        all(&mut pairs, &mut |d| {
+            d.block_lengths = offset;
+            offset += d.block_length_size * 2;
+        });
+        all(&mut pairs, &mut |d| {
+            offset = offset.next_multiple_of(64);
+            d.data = offset;
+            offset += d.num_blocks * d.block_size;
+        });
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::new(header.reader.into_inner()),
+            dtz,
+            symmetric,
+            has_pawns,
+            both_pawns,
+            unique_pieces,
+            pairs,
+            map,
+        })
+    }
+
+    /// Returns the error for a table whose contents turn out to be invalid when probed.
+    fn invalid(&self) -> SyzygyError {
+        SyzygyError::InvalidTable(self.path.display().to_string())
+    }
+
+    /// Reads the value of a position, flipping colors and ranks if the position has the material of the table with the colors swapped.
+    /// For a WDL table, the value is the result from -2 to 2. For a DTZ table, whose values are read for the result `wdl`,
+    /// the value is the DTZ in plies, and `None` is returned if the table only stores the positions with the other side to move.
+    fn probe(&self, position: &Position, flipped: bool, wdl: Wdl) -> Result<Option<i32>, SyzygyError> {
+        let maps = maps();
+        let (flip_color, flip_squares) = if flipped { (8, 56) } else { (0, 0) };
+        let side = usize::from(flipped != position.side.is_black());
+        let (mut squares, mut pieces) = (Vec::with_capacity(MAX_PIECES), Vec::with_capacity(MAX_PIECES));
+        let (mut lead_pawns, mut file) = (0, 0);
+        let lead = self.pairs[0][0].pieces[0] ^ flip_color;
+        if self.has_pawns {
+            squares.extend((0..64).filter(|&sq| position.content[sq].is_some_and(|p| piece_code(p) == lead)).map(|sq| sq ^ flip_squares));
+            lead_pawns = squares.len();
+            pieces.resize(lead_pawns, lead ^ flip_color);
+            let leading = (0..lead_pawns).max_by_key(|&i| maps.map_pawns[squares[i]]).ok_or_else(|| self.invalid())?;
+            squares.swap(0, leading);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+        if self.dtz && usize::from(self.pairs[0][file].flags & STM) != side && (self.has_pawns || !self.symmetric) {
+            return Ok(None);
+        }
+        for (sq, piece) in position.content.iter().enumerate() {
+            if let Some(piece) = piece.filter(|&p| !self.has_pawns || piece_code(p) != lead) {
+                squares.push(sq ^ flip_squares);
+                pieces.push(piece_code(piece) ^ flip_color);
+            }
+        }
+        let d = &self.pairs[side % self.pairs.len()][file];
+        if d.pieces.len() != squares.len() {
+            return Err(self.invalid());
+        }
+        for i in lead_pawns..squares.len().saturating_sub(1) {
+            if let Some(j) = (i + 1..squares.len()).find(|&j| pieces[j] == d.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        if squares[0] % 8 > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+        let mut index;
+        if self.has_pawns {
+            index = maps.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&sq| maps.map_pawns[sq]);
+            for (i, &sq) in squares[..lead_pawns].iter().enumerate().skip(1) {
+                index += maps.binomial[i][maps.map_pawns[sq]];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                squares.iter_mut().for_each(|sq| *sq ^= 56);
+            }
+            if let Some(i) = (0..d.group_len[0]).find(|&i| off_diagonal(squares[i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    squares[i..].iter_mut().for_each(|sq| *sq = flip_diagonal(*sq));
+                }
+            }
+            index = if self.unique_pieces {
+                let (s0, s1, s2) = (squares[0] as u64, squares[1] as u64, squares[2] as u64);
+                let (adjust1, adjust2) = (u64::from(s1 > s0), u64::from(s2 > s0) + u64::from(s2 > s1));
+                if off_diagonal(squares[0]) != 0 {
+                    (maps.map_a1d1d4[squares[0]] * 63 + s1 - adjust1) * 62 + s2 - adjust2
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + s0 / 8 * 28 + maps.map_b1h1h7[squares[1]]) * 62 + s2 - adjust2
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + s0 / 8 * 7 * 28 + (s1 / 8 - adjust1) * 28 + maps.map_b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + s0 / 8 * 7 * 6 + (s1 / 8 - adjust1) * 6 + (s2 / 8 - adjust2)
+                }
+            } else {
+                maps.map_kk[maps.map_a1d1d4[squares[0]] as usize][squares[1]]
+            };
+        }
+        index *= d.group_idx[0];
+        let (mut start, mut remaining_pawns, mut next) = (d.group_len[0], self.has_pawns && self.both_pawns, 1);
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| sq > s).count() + if remaining_pawns { 8 } else { 0 };
+                n += maps.binomial[i + 1][sq - adjust];
+            }
+            remaining_pawns = false;
+            index += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        let value = self.decompress(d, index)?;
+        self.score(d, value, wdl).map(Some)
+    }
+
+    /// Converts a stored value to a WDL value, or to the DTZ in plies for a DTZ table.
+    fn score(&self, d: &Pairs, value: u16, wdl: Wdl) -> Result<i32, SyzygyError> {
+        if !self.dtz {
+            return Ok(value as i32 - 2);
+        }
+        let mut value = value as usize;
+        if d.flags & MAPPED != 0 {
+            let i = d.map_idx[[1, 3, 0, 2, 0][wdl as usize]] + value;
+            value = if d.flags & WIDE != 0 {
+                self.map.get(2 * i..2 * i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            } else {
+                self.map.get(i).map(|&b| b as usize)
+            }
+            .ok_or_else(|| self.invalid())?;
+        }
+        let plies = match wdl {
+            Wdl::Win => d.flags & WIN_PLIES != 0,
+            Wdl::Loss => d.flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+        Ok(if plies { value } else { value * 2 } as i32 + 1)
+    }
+
+    /// Reads the stored value at an index, decoding the block holding it: blocks hold symbols encoded with a canonical Huffman code,
+    /// each symbol standing for a sequence of values given by a binary tree of pairs of symbols.
+    fn decompress(&self, d: &Pairs, index: u64) -> Result<u16, SyzygyError> {
+        if d.flags & SINGLE_VALUE != 0 {
+            return Ok(d.min_sym_len as u16);
+        }
+        let k = index / d.span;
+        if k >= d.sparse_index_size {
+            return Err(self.invalid());
+        }
+        let mut entry = [0; 6];
+        self.read_at(d.sparse_index + 6 * k, &mut entry)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+        let mut offset = u16::from_le_bytes([entry[4], entry[5]]) as i64 + (index % d.span) as i64 - (d.span / 2) as i64;
+        let block_length = |block: u64| -> Result<i64, SyzygyError> {
+            if block >= d.block_length_size {
+                return Err(self.invalid());
+            }
+            let mut length = [0; 2];
+            self.read_at(d.block_lengths + 2 * block, &mut length)?;
+            Ok(u16::from_le_bytes(length) as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(|| self.invalid())?;
+            offset += block_length(block)? + 1;
+        }
+        loop {
+            let length = block_length(block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+        if block >= d.num_blocks {
+            return Err(self.invalid());
+        }
+        let mut data = vec![0; d.block_size as usize + 8];
+        self.read_at(d.data + block * d.block_size, &mut data[..d.block_size as usize])?;
+        let word = |i: usize| data.get(i..i + 4).map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u64);
+        let (mut buffer, mut buffer_size, mut next) = (word(0) << 32 | word(4), 64, 8);
+        let min_sym_len = d.min_sym_len as usize;
+        let mut sym = loop {
+            let len = d.base64.iter().position(|&base| buffer >= base).ok_or_else(|| self.invalid())?;
+            let shift = 64usize.checked_sub(len + min_sym_len).ok_or_else(|| self.invalid())?;
+            let sym = ((buffer - d.base64[len]).checked_shr(shift as u32).unwrap_or(0) as u16).wrapping_add(d.lowest_sym[len]) as usize;
+            let sym_len = *d.symlen.get(sym).ok_or_else(|| self.invalid())? as i64;
+            if offset < sym_len + 1 {
+                break sym;
+            }
+            offset -= sym_len + 1;
+            buffer = buffer.checked_shl((len + min_sym_len) as u32).unwrap_or(0);
+            buffer_size -= (len + min_sym_len) as i32;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= word(next).checked_shl((64 - buffer_size) as u32).unwrap_or(0);
+                next += 4;
+            }
+        };
+        while d.symlen[sym] != 0 {
+            let left = d.left(sym);
+            let left_len = *d.symlen.get(left).ok_or_else(|| self.invalid())? as i64;
+            if offset < left_len + 1 {
+                sym = left;
+            } else {
+                offset -= left_len + 1;
+                sym = d.right(sym);
+                if sym >= d.symlen.len() {
+                    return Err(self.invalid());
+                }
+            }
+        }
+        Ok(d.left(sym) as u16)
+    }
+
+    /// Reads bytes of the file from an offset.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), SyzygyError> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf).map_err(|e| if e.kind() == io::ErrorKind::UnexpectedEof { self.invalid() } else { e.into() })
+    }
+}
+
+/// Represents the compressed values of a table for a side to move and a file of the leading pawn
+#[derive(Debug, Default)]
+struct Pairs {
+    /// The flags of the values
+    flags: u8,
+    /// The codes of the pieces in the order in which they are encoded
+    pieces: Vec<u8>,
+    /// The numbers of pieces of the groups of pieces encoded together, followed by 0
+    group_len: Vec<usize>,
+    /// The factors of the indices of the groups in the index of a position, followed by the number of indices
+    group_idx: Vec<u64>,
+    /// The size of a block of compressed data, in bytes
+    block_size: u64,
+    /// The number of values between two entries of the sparse index
+    span: u64,
+    /// The number of entries of the sparse index
+    sparse_index_size: u64,
+    /// The number of entries of the block lengths, padding included
+    block_length_size: u64,
+    /// The number of blocks of compressed data
+    num_blocks: u64,
+    /// The length of the shortest Huffman codes, or the value of all positions for single-valued tables
+    min_sym_len: u8,
+    /// The lowest symbol for each code length
+    lowest_sym: Vec<u16>,
+    /// The lowest code for each code length, left-aligned on 64 bits
+    base64: Vec<u64>,
+    /// The number of values each symbol stands for, minus one
+    symlen: Vec<u8>,
+    /// The pairs of symbols each symbol stands for, packed in 3 bytes
+    btree: Vec<[u8; 3]>,
+    /// The offset in the file of the sparse index
+    sparse_index: u64,
+    /// The offset in the file of the block lengths
+    block_lengths: u64,
+    /// The offset in the file of the compressed data
+    data: u64,
+    /// The offsets of the maps of DTZ values for each result
+    map_idx: [usize; 4],
+}
+
+impl Pairs {
+    /// Groups the pieces which are encoded together and computes the factor of each group in the index of a position,
+    /// the groups being encoded in the order given by the header of the table.
+    fn set_groups(&mut self, has_pawns: bool, unique_pieces: bool, both_pawns: bool, order: [u8; 2], file: usize) -> io::Result<()> {
+        let maps = maps();
+        let mut first_len: isize = if has_pawns {
+            0
+        } else if unique_pieces {
+            3
+        } else {
+            2
+        };
+        self.group_len = vec![1];
+        for i in 1..self.pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                *self.group_len.last_mut().unwrap() += 1;
+            } else {
+                self.group_len.push(1);
+            }
+        }
+        self.group_len.push(0);
+        let groups = self.group_len.len() - 1;
+        if self.group_len[0] >= maps.lead_pawns_size.len() {
+            return Err(invalid_data());
+        }
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free = 64 - self.group_len[0] - if both_pawns { self.group_len[1] } else { 0 };
+        let mut index = 1;
+        self.group_idx = vec![0; groups + 1];
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                self.group_idx[0] = index;
+                index *= if has_pawns {
+                    maps.lead_pawns_size[self.group_len[0]][file]
+                } else if unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                self.group_idx[1] = index;
+                index *= maps.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = index;
+                index *= maps.binomial[self.group_len[next]][free];
+                free -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[groups] = index;
+        Ok(())
+    }
+
+    /// Reads the parameters of the compression and the Huffman code from the header.
+    fn read_sizes(&mut self, header: &mut Header) -> io::Result<()> {
+        self.flags = header.u8()?;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_sym_len = header.u8()?;
+            return Ok(());
+        }
+        let size = *self.group_idx.last().unwrap();
+        self.block_size = 1u64.checked_shl(header.u8()? as u32).ok_or_else(invalid_data)?;
+        self.span = 1u64.checked_shl(header.u8()? as u32).ok_or_else(invalid_data)?;
+        self.sparse_index_size = size.div_ceil(self.span);
+        let padding = header.u8()? as u64;
+        self.num_blocks = header.u32()? as u64;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = header.u8()?;
+        self.min_sym_len = header.u8()?;
+        let lengths = (max_sym_len as usize + 1).checked_sub(self.min_sym_len as usize).filter(|&l| l > 0).ok_or_else(invalid_data)?;
+        self.lowest_sym = (0..lengths).map(|_| header.u16()).collect::<io::Result<_>>()?;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = self.base64[i + 1].wrapping_add(self.lowest_sym[i] as u64).wrapping_sub(self.lowest_sym[i + 1] as u64) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = 64usize.checked_sub(i + self.min_sym_len as usize).and_then(|shift| base.checked_shl(shift as u32)).unwrap_or(0);
+        }
+        let symbols = header.u16()? as usize;
+        self.btree = header.take(3 * symbols)?.chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
+        header.take(symbols & 1)?;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.sym_len(sym, &mut visited)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the number of values a symbol stands for, minus one, computing those of the symbols of its pair first.
+    fn sym_len(&mut self, sym: usize, visited: &mut [bool]) -> io::Result<u8> {
+        visited[sym] = true;
+        let (left, right) = (self.left(sym), self.right(sym));
+        if right == 0xFFF {
+            return Ok(0);
+        }
+        if left >= self.symlen.len() || right >= self.symlen.len() {
+            return Err(invalid_data());
+        }
+        for child in [left, right] {
+            if !visited[child] {
+                self.symlen[child] = self.sym_len(child, visited)?;
+            }
+        }
+        Ok(self.symlen[left].wrapping_add(self.symlen[right]).wrapping_add(1))
+    }
+
+    /// Returns the first symbol of the pair a symbol stands for, or the value of the symbol if it stands for a single value.
+    fn left(&self, sym: usize) -> usize {
+        let [b0, b1, _] = self.btree[sym];
+        (b1 as usize & 0xF) << 8 | b0 as usize
+    }
+
+    /// Returns the second symbol of the pair a symbol stands for, or 0xFFF if it stands for a single value.
+    fn right(&self, sym: usize) -> usize {
+        let [_, b1, b2] = self.btree[sym];
+        (b2 as usize) << 4 | (b1 as usize) >> 4
+    }
+}
+
+/// Reads the header of a table, keeping the bytes read so that offsets within the file are known
+struct Header {
+    /// The file of the table
+    reader: BufReader<File>,
+    /// The bytes read so far
+    bytes: Vec<u8>,
+}
+
+impl Header {
+    /// Reads the next `n` bytes.
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        let start = self.bytes.len();
+        self.bytes.resize(start + n, 0);
+        self.reader.read_exact(&mut self.bytes[start..])?;
+        Ok(&self.bytes[start..])
+    }
+
+    /// Reads the next byte.
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads the next little-endian 16-bit integer.
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    /// Reads the next little-endian 32-bit integer.
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Skips bytes until the offset is a multiple of `n`.
+    fn align(&mut self, n: usize) -> io::Result<()> {
+        let len = self.bytes.len();
+        self.take(len.next_multiple_of(n) - len).map(|_| ())
+    }
+}
+
+/// The tables used to compute the index of a position
+struct Maps {
+    /// The binomial coefficients, `binomial[k][n]` being the number of ways to choose `k` squares among `n`
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// The indices of the squares of pawns, from 47 (a2) down to 0 (e7), alternating between a file and its mirror
+    map_pawns: [usize; 64],
+    /// The index of the leading pawn's square for each number of leading pawns
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    /// The number of indices of the leading pawns for each number of leading pawns and file of the leading pawn
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+    /// The indices of the squares below the a1-h8 diagonal
+    map_b1h1h7: [u64; 64],
+    /// The indices of the squares of the a1-d1-d4 triangle, those of the diagonal last
+    map_a1d1d4: [u64; 64],
+    /// The indices of the legal pairs of kings with the first king in the a1-d1-d4 triangle
+    map_kk: [[u64; 64]; 10],
+}
+
+/// Returns the tables used to compute the index of a position, computing them the first time.
+fn maps() -> &'static Maps {
+    static MAPS: OnceLock<Maps> = OnceLock::new();
+    MAPS.get_or_init(|| {
+        let mut binomial = [[0; 64]; MAX_PIECES];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 } + if k < n { binomial[k][n - 1] } else { 0 };
+            }
+        }
+        let mut map_b1h1h7 = [0; 64];
+        for (code, sq) in (0..64).filter(|&sq| off_diagonal(sq) < 0).enumerate() {
+            map_b1h1h7[sq] = code as u64;
+        }
+        let mut map_a1d1d4 = [u64::MAX; 64];
+        let (below, diagonal): (Vec<usize>, Vec<usize>) = (0..28).filter(|&sq| sq % 8 < 4 && off_diagonal(sq) <= 0).partition(|&sq| off_diagonal(sq) < 0);
+        for (code, sq) in below.into_iter().chain(diagonal).enumerate() {
+            map_a1d1d4[sq] = code as u64;
+        }
+        let mut map_kk = [[0; 64]; 10];
+        let (mut code, mut both_on_diagonal) = (0, Vec::new());
+        for (idx, row) in map_kk.iter_mut().enumerate() {
+            for s1 in (0..28).filter(|&sq| map_a1d1d4[sq] == idx as u64) {
+                for (s2, entry) in row.iter_mut().enumerate() {
+                    if (s1 % 8).abs_diff(s2 % 8) <= 1 && (s1 / 8).abs_diff(s2 / 8) <= 1 || off_diagonal(s1) == 0 && off_diagonal(s2) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        *entry = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            map_kk[idx][s2] = code;
+            code += 1;
+        }
+        let (mut map_pawns, mut lead_pawn_idx, mut lead_pawns_size) = ([0; 64], [[0; 64]; MAX_PIECES], [[0; 4]; MAX_PIECES]);
+        let mut available = 48;
+        for lead_pawns in 1..MAX_PIECES {
+            for (file, size) in lead_pawns_size[lead_pawns].iter_mut().enumerate() {
+                let mut idx = 0;
+                for sq in (1..7).map(|rank| rank * 8 + file) {
+                    if lead_pawns == 1 {
+                        map_pawns[sq] = available - 1;
+                        map_pawns[sq ^ 7] = available - 2;
+                        available -= 2;
+                    }
+                    lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += binomial[lead_pawns - 1][map_pawns[sq]];
+                }
+                *size = idx;
+            }
+        }
+        Maps {
+            binomial,
+            map_pawns,
+            lead_pawn_idx,
+            lead_pawns_size,
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+        }
+    })
+}
+
+/// Finds the table of the material of a position, returning it along with whether the position has the material of the table with the colors swapped,
+/// or with Black to move if both sides have the same material.
+fn lookup<'a>(tables: &'a HashMap<String, TableFile>, position: &Position, dtz: bool) -> Result<(&'a Table, bool), SyzygyError> {
+    let (white, black) = (material(&position.content, Color::White), material(&position.content, Color::Black));
+    if let Some(file) = tables.get(&format!("{white}v{black}")) {
+        let table = file.table(dtz)?;
+        Ok((table, table.symmetric && position.side.is_black()))
+    } else if let Some(file) = tables.get(&format!("{black}v{white}")) {
+        Ok((file.table(dtz)?, true))
+    } else {
+        Err(SyzygyError::MissingTable(format!("{white}v{black}")))
+    }
+}
+
+/// Returns the pieces of a side in the order in which they appear in the names of tables (e.g. KRP).
+fn material(content: &[Option<Piece>; 64], color: Color) -> String {
+    iter::once(PieceType::K)
+        .chain(PIECE_ORDER)
+        .flat_map(|t| iter::repeat_n(char::from(t), content.iter().flatten().filter(|&&p| p == Piece(t, color)).count()))
+        .collect()
+}
+
+/// Returns the number of pieces of an endgame named like KRvK, or `None` if the name is not the name of an endgame.
+fn piece_count(name: &str) -> Option<usize> {
+    let (white, black) = name.split_once('v')?;
+    let valid = |side: &str| side.starts_with('K') && side[1..].chars().all(|c| "QRBNP".contains(c));
+    (valid(white) && valid(black) && name.len() - 1 <= MAX_PIECES).then_some(name.len() - 1)
+}
+
+/// Returns the code of a piece in tables, from 1 (pawn) to 6 (king), plus 8 for black pieces.
+fn piece_code(Piece(piece_type, color): Piece) -> u8 {
+    let code = match piece_type {
+        PieceType::P => 1,
+        PieceType::N => 2,
+        PieceType::B => 3,
+        PieceType::R => 4,
+        PieceType::Q => 5,
+        PieceType::K => 6,
+    };
+    if color.is_black() {
+        code + 8
+    } else {
+        code
+    }
+}
+
+/// Returns the difference between the rank and the file of a square, which is 0 on the a1-h8 diagonal and negative below it.
+fn off_diagonal(sq: usize) -> isize {
+    (sq / 8) as isize - (sq % 8) as isize
+}
+
+/// Mirrors a square along the a1-h8 diagonal.
+fn flip_diagonal(sq: usize) -> usize {
+    ((sq >> 3) | (sq << 3)) & 63
+}
+
+/// Checks whether a move is a capture, en passant included.
+fn is_capture(position: &Position, Move(_, dest, spec): Move) -> bool {
+    position.content[dest].is_some() || spec == Some(SpecialMoveType::EnPassant)
+}
+
+/// Checks whether a move is a pawn move.
+fn is_pawn_move(position: &Position, Move(src, ..): Move) -> bool {
+    position.content[src].is_some_and(|p| p.0 == PieceType::P)
+}
+
+/// Checks whether a move resets the halfmove clock.
+fn is_zeroing(position: &Position, move_: Move) -> bool {
+    is_capture(position, move_) || is_pawn_move(position, move_)
+}
+
+/// Checks whether the side to move is checkmated.
+fn is_checkmate(position: &Position) -> bool {
+    king_attacked(&position.content, position.side) && legal_moves(position).is_empty()
+}
+
+/// Returns the error for a table file whose header is invalid.
+fn invalid_data() -> io::Error {
+    io::Error::from(io::ErrorKind::InvalidData)
+}
@@ -1,4 +1,6 @@
-use super::{helpers, Board, Color, Fen, Move, PieceType, SpecialMoveType};
+#[cfg(feature = "uci")]
+use super::uci;
+use super::{helpers, Board, Color, Fen, InvalidDescriptiveMoveError, InvalidLanMoveError, InvalidSanMoveError, Move, MoveNotation, NotationLocale, Piece, PieceType, SpecialMoveType};
 
 #[test]
 fn default_board() {
@@ -316,6 +318,149 @@ fn invalid_make_move_san() {
     board.make_move_san("Ne2").unwrap();
 }
 
+#[test]
+fn lenient_san() {
+    let board = Board::default();
+    assert_eq!(board.san_to_move("Ng1f3").unwrap().to_uci(), "g1f3");
+    assert_eq!(board.san_to_move("Ng1-f3!?").unwrap().to_uci(), "g1f3");
+    assert_eq!(board.san_to_move("e4!").unwrap().to_uci(), "e2e4");
+    assert!(matches!(board.san_to_move("Nd2"), Err(InvalidSanMoveError::Illegal(_))));
+    assert!(matches!(board.san_to_move("Zz9"), Err(InvalidSanMoveError::Unparseable(_))));
+    assert!(matches!(board.san_to_move("Nf3=Q"), Err(InvalidSanMoveError::Unparseable(_))));
+    let board = Board::from_fen(Fen::try_from("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap());
+    for san in ["b8Q", "b8=Q", "b8(Q)", "b8/Q", "b8=Q+"] {
+        assert_eq!(board.san_to_move(san).unwrap().to_uci(), "b7b8q");
+    }
+    assert_eq!(board.san_to_move("bxa8=N").unwrap().to_uci(), "b7a8n");
+    assert_eq!(board.san_to_move("ba8N").unwrap().to_uci(), "b7a8n");
+    assert!(matches!(board.san_to_move("b8"), Err(InvalidSanMoveError::Ambiguous(_))));
+    assert_eq!(board.san_to_move("exd6 e.p.").unwrap(), board.san_to_move("exd6").unwrap());
+    assert_eq!(board.san_to_move("0-0").unwrap(), board.san_to_move("O-O").unwrap());
+    assert_eq!(board.move_to_san(board.san_to_move("0-0-0!").unwrap()).unwrap(), "O-O-O");
+    let board = Board::from_fen(Fen::try_from("3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+    for san in ["e8q", "e8=q", "e8(q)"] {
+        assert_eq!(board.san_to_move(san).unwrap().to_uci(), "e7e8q");
+    }
+    assert_eq!(board.san_to_move("exd8q").unwrap().to_uci(), "e7d8q");
+    assert_eq!(board.san_to_move("exd8n").unwrap().to_uci(), "e7d8n");
+    let board = Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap());
+    assert!(matches!(board.san_to_move("Rd1"), Err(InvalidSanMoveError::Ambiguous(_))));
+    assert_eq!(board.san_to_move("Rhd1").unwrap().to_uci(), "h1d1");
+    assert_eq!(board.san_to_move("Rhxd1").unwrap().to_uci(), "h1d1");
+}
+
+#[test]
+fn lan() {
+    let mut board = Board::default();
+    board.make_moves_san("e4 d5 exd5 Qxd5 Nc3 Qa5 Nf3").unwrap();
+    assert_eq!(board.gen_movetext_in(MoveNotation::Lan), "1. e2-e4 d7-d5 2. e4xd5 Qd8xd5 3. Nb1-c3 Qd5-a5 4. Ng1-f3");
+    assert_eq!(board.lan_to_move("Bc8-g4").unwrap().to_uci(), "c8g4");
+    assert_eq!(board.lan_to_move("Ng8f6!").unwrap().to_uci(), "g8f6");
+    assert!(matches!(board.lan_to_move("Nf6"), Err(InvalidLanMoveError::Unparseable(_))));
+    assert!(matches!(board.lan_to_move("Ng8-e7"), Err(InvalidLanMoveError::Illegal(_))));
+    assert!(matches!(board.lan_to_move("Bg8-f6"), Err(InvalidLanMoveError::Illegal(_))));
+    let board = Board::from_fen(Fen::try_from("3qk3/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap());
+    assert_eq!(board.move_to_lan(Move::from_uci("e7d8q").unwrap()).unwrap(), "e7xd8=Q+");
+    assert_eq!(board.move_to_lan(board.lan_to_move("0-0").unwrap()).unwrap(), "O-O");
+    assert_eq!(board.lan_to_move("e7d8=N").unwrap().to_uci(), "e7d8n");
+    assert!(matches!(board.lan_to_move("e7xd8"), Err(InvalidLanMoveError::Ambiguous(_))));
+}
+
+#[test]
+fn localized_san() {
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6 Bb5 a6").unwrap();
+    assert_eq!(board.gen_movetext_localized(MoveNotation::San, &NotationLocale::GERMAN), "1. e4 e5 2. Sf3 Sc6 3. Lb5 a6");
+    assert_eq!(board.gen_movetext_localized(MoveNotation::San, &NotationLocale::FRENCH), "1. e4 e5 2. Cf3 Cc6 3. Fb5 a6");
+    assert_eq!(
+        board.gen_movetext_localized(MoveNotation::Lan, &NotationLocale::SPANISH),
+        "1. e2-e4 e7-e5 2. Cg1-f3 Cb8-c6 3. Af1-b5 a7-a6"
+    );
+    assert_eq!(
+        board.gen_movetext_localized(MoveNotation::San, &NotationLocale::FIGURINE),
+        "1. e4 e5 2. \u{2658}f3 \u{2658}c6 3. \u{2657}b5 a6"
+    );
+    assert_eq!(board.san_to_move_in("Lxc6", &NotationLocale::GERMAN).unwrap().to_uci(), "b5c6");
+    assert_eq!(board.san_to_move_in("Ab5xc6", &NotationLocale::SPANISH).unwrap().to_uci(), "b5c6");
+    assert_eq!(board.san_to_move_in("\u{2657}a4", &NotationLocale::FIGURINE).unwrap().to_uci(), "b5a4");
+    assert!(board.san_to_move_in("Bxc6", &NotationLocale::GERMAN).is_err());
+    let board = Board::from_fen(Fen::try_from("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap());
+    assert_eq!(board.san_to_move_in("e8D", &NotationLocale::GERMAN).unwrap().to_uci(), "e7e8q");
+    assert_eq!(board.san_to_move_in("e8=C", &NotationLocale::FRENCH).unwrap().to_uci(), "e7e8n");
+    assert_eq!(board.move_to_san_in(Move::from_uci("e7e8r").unwrap(), &NotationLocale::DUTCH).unwrap(), "e8=T");
+    let board = Board::from_fen(Fen::try_from("8/8/8/8/8/8/k3p3/4K3 b - - 0 1").unwrap());
+    assert_eq!(board.san_to_move_in("\u{265A}b2", &NotationLocale::FIGURINE).unwrap().to_uci(), "a2b2");
+    assert_eq!(NotationLocale::new('K', 'Q', 'R', 'B', 'N').unwrap(), NotationLocale::ENGLISH);
+    assert!(NotationLocale::new('K', 'Q', 'R', 'b', 'N').is_err());
+    assert!(NotationLocale::new('K', 'Q', 'R', 'K', 'N').is_err());
+}
+
+#[test]
+fn descriptive() {
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7").unwrap();
+    assert_eq!(
+        board.gen_movetext_in(MoveNotation::Descriptive),
+        "1. P-K4 P-K4 2. N-KB3 N-QB3 3. B-N5 P-QR3 4. B-R4 N-B3 5. O-O B-K2 6. R-K1 P-QN4 7. B-N3 P-Q3 8. P-B3 O-O 9. P-KR3 N-N1 10. P-Q4 QN-Q2"
+    );
+    assert_eq!(board.descriptive_to_move("PxP").unwrap().to_uci(), "d4e5");
+    assert!(matches!(board.descriptive_to_move("N-Q2"), Err(InvalidDescriptiveMoveError::Ambiguous(_))));
+    assert_eq!(board.descriptive_to_move("KN-Q2").unwrap().to_uci(), "f3d2");
+    assert_eq!(board.descriptive_to_move("QKt-Q2").unwrap().to_uci(), "b1d2");
+    assert_eq!(board.descriptive_to_move("N(KB3)-Q2").unwrap().to_uci(), "f3d2");
+    assert_eq!(board.descriptive_to_move("N-R4!").unwrap().to_uci(), "f3h4");
+    assert!(matches!(board.descriptive_to_move("N-K5"), Err(InvalidDescriptiveMoveError::Illegal(_))));
+    assert!(matches!(board.descriptive_to_move("N-Z9"), Err(InvalidDescriptiveMoveError::Unparseable(_))));
+    let board = Board::from_fen(Fen::try_from("4k3/8/8/3R4/8/8/8/R3K3 w - - 0 1").unwrap());
+    assert_eq!(board.move_to_descriptive(Move::from_uci("a1d1").unwrap()).unwrap(), "R(1)-Q1");
+    assert_eq!(board.move_to_descriptive(Move::from_uci("d5d8").unwrap()).unwrap(), "R-Q8ch");
+    assert_eq!(board.descriptive_to_move("R(5)-Q1").unwrap().to_uci(), "d5d1");
+    let board = Board::from_fen(Fen::try_from("r3k3/1P6/8/3pPp2/8/8/8/4K3 w - d6 0 1").unwrap());
+    assert_eq!(board.move_to_descriptive(Move::from_uci("e5d6").unwrap()).unwrap(), "PxP");
+    assert_eq!(board.descriptive_to_move("PxP e.p.").unwrap().to_uci(), "e5d6");
+    assert_eq!(board.move_to_descriptive(Move::from_uci("b7a8q").unwrap()).unwrap(), "PxR=Qch");
+    assert_eq!(board.descriptive_to_move("P-N8(N)").unwrap().to_uci(), "b7b8n");
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn descriptive_pgn() {
+    use super::pgn::{ImportMode, Pgn};
+
+    let text = "1. P-K4 P-K4 2. N-KB3 N-QB3 3. B-B4 B-B4 4. P-QN4 BxNP 5. P-B3 B-R4 6. P-Q4 PxP 7. O-O P-Q6 8. Q-N3 Q-B3
+9. P-K5 Q-N3 10. R-K1 KN-K2 11. B-R3 P-N4 12. QxP R-QN1 13. Q-R4 B-N3 14. QN-Q2 B-N2 15. N-K4 Q-B4 16. BxQP Q-R4
+17. N-B6ch PxN 18. PxP R-N1 19. QR-Q1 QxN 20. RxNch NxR 21. QxPch KxQ 22. B-B5dbl ch K-K1 23. B-Q7ch K-B1 24. BxN mate 1-0";
+    let (pgn, _) = Pgn::parse_descriptive(text, ImportMode::Lenient).unwrap();
+    assert!(pgn.board().is_checkmate());
+    assert!(pgn.board().gen_movetext().ends_with("22. Bf5+ Ke8 23. Bd7+ Kf8 24. Bxe7#"));
+    let (pgn, _) = Pgn::parse_descriptive("1. P-K4 P-QR3 2. P-K5 P-Q4 3. PxP e.p. *", ImportMode::Lenient).unwrap();
+    assert_eq!(pgn.board().gen_movetext(), "1. e4 a6 2. e5 d5 3. exd6");
+    let board = Board::from_fen(Fen::try_from("4k3/8/8/3R4/8/8/8/R3K3 w - - 0 1").unwrap());
+    let text = format!("[FEN \"{}\"]\n\n1. R(1)-Q1 K-K2 *", board.to_fen());
+    let (pgn, _) = Pgn::parse_descriptive(&text, ImportMode::Lenient).unwrap();
+    assert_eq!(pgn.board().gen_movetext(), "1. Rad1 Ke7");
+}
+
+#[test]
+fn iccf() {
+    assert_eq!(Move::from_iccf("5254").unwrap().to_uci(), "e2e4");
+    assert_eq!(Move::from_iccf("57581").unwrap().to_uci(), "e7e8q");
+    assert_eq!(Move::from_iccf("27184").unwrap().to_iccf(), "27184");
+    assert!(Move::from_iccf("5294").is_err());
+    assert!(Move::from_iccf("52545").is_err());
+    assert!(Move::from_iccf("525").is_err());
+    let mut board = Board::default();
+    for iccf in ["5254", "5755", "7163", "2836", "6125", "7866", "5171"] {
+        board.make_move_iccf(iccf).unwrap();
+    }
+    assert_eq!(board.gen_movetext(), "1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 4. O-O");
+    assert_eq!(board.gen_movetext_in(MoveNotation::Iccf), "1. 5254 5755 2. 7163 2836 3. 6125 7866 4. 5171");
+    assert!(board.make_move_iccf("5171").is_err());
+    let mut board = Board::from_fen(Fen::try_from("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap());
+    board.make_move_iccf("57584").unwrap();
+    assert_eq!(board.gen_movetext(), "1. e8=N");
+}
+
 #[test]
 fn valid_make_move_san() {
     let mut board = Board::default();
@@ -342,6 +487,70 @@ fn pgn() {
     std::fs::write("test.txt", pgn.to_string()).unwrap();
 }
 
+#[cfg(feature = "pgn")]
+#[test]
+fn pgn_game() {
+    use super::pgn::Pgn;
+
+    let pgn_str = r#"[Event "Carlsen - Karjakin World Championship Match"]
+[Site "New York, NY USA"]
+[Date "2016.11.30"]
+[Round "13.4"]
+[Result "1-0"]
+[White "Magnus Carlsen"]
+[Black "Sergey Karjakin"]
+[ECO "B54"]
+
+1.e4 c5 2.Nf3 d6 3.d4 cxd4 4.Nxd4 Nf6 5.f3 e5 6.Nb3 Be7 7.c4
+a5 8.Be3 a4 9.Nc1 O-O 10.Nc3 Qa5 11.Qd2 Na6 12.Be2 Nc5 13.O-O
+Bd7 14.Rb1 Rfc8 15.b4 axb3 16.axb3 Qd8 17.Nd3 Ne6 18.Nb4 Bc6
+19.Rfd1 h5 20.Bf1 h4 21.Qf2 Nd7 22.g3 Ra3 23.Bh3 Rca8 24.Nc2
+R3a6 25.Nb4 Ra5 26.Nc2 b6 27.Rd2 Qc7 28.Rbd1 Bf8 29.gxh4 Nf4
+30.Bxf4 exf4 31.Bxd7 Qxd7 32.Nb4 Ra3 33.Nxc6 Qxc6 34.Nb5 Rxb3
+35.Nd4 Qxc4 36.Nxb3 Qxb3 37.Qe2 Be7 38.Kg2 Qe6 39.h5 Ra3
+40.Rd3 Ra2 41.R3d2 Ra3 42.Rd3 Ra7 43.Rd5 Rc7 44.Qd2 Qf6 45.Rf5
+Qh4 46.Rc1 Ra7 47.Qxf4 Ra2+ 48.Kh1 Qf2 49.Rc8+ Kh7 50.Qh6+ 1-0"#;
+    let pgn = Pgn::try_from(pgn_str).unwrap();
+    assert_eq!(
+        pgn.board().position(),
+        Board::from_fen(Fen::try_from("2R5/4bppk/1p1p3Q/5R1P/4P3/5P2/r4q1P/7K b - - 6 50").unwrap()).position()
+    );
+    assert_eq!(pgn.board().resigned_side(), Some(Color::Black));
+    println!("{pgn}");
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn pgn_annotations() {
+    use super::pgn::Pgn;
+
+    let pgn = Pgn::try_from(
+        "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n\
+         % escaped line\n1. e4 $1 {best by test} e5!? 2. Nf3 (2. f4 exf4 (2... d5)) 2... Nc6 ; rest of line\n3. Bb5 *",
+    )
+    .unwrap();
+    assert_eq!(pgn.board().gen_movetext(), "1. e4 e5 2. Nf3 Nc6 3. Bb5");
+    assert!(pgn.board().is_ongoing());
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn invalid_pgn_location() {
+    use super::{errors::InvalidPgnError, pgn::Pgn};
+
+    let tags = "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n";
+    let location = |e: InvalidPgnError| match e {
+        InvalidPgnError::InvalidMove(l, _) | InvalidPgnError::MissingMoveNumber(l) | InvalidPgnError::InvalidMoveNumber(l) | InvalidPgnError::Unterminated(l, _) => (l.line(), l.column()),
+        e => panic!("unexpected error: {e}"),
+    };
+    assert_eq!(location(Pgn::try_from(format!("{tags}1. e4 e5\n2. Nf3 Nf6 3. Kxe1+ *").as_str()).unwrap_err()), (10, 15));
+    assert_eq!(location(Pgn::try_from(format!("{tags}1. e4 e5 Nf3 *").as_str()).unwrap_err()), (9, 10));
+    assert_eq!(location(Pgn::try_from(format!("{tags}1. e4 e5 3. Nf3 *").as_str()).unwrap_err()), (9, 10));
+    assert_eq!(location(Pgn::try_from(format!("{tags}1. e4 {{ unclosed e5 *").as_str()).unwrap_err()), (9, 7));
+    assert!(matches!(Pgn::try_from(format!("{tags}1. e4 e5").as_str()), Err(InvalidPgnError::MissingResult(_))));
+    assert!(matches!(Pgn::try_from("1. e4 e5 *"), Err(InvalidPgnError::MissingTag(_, _))));
+}
+
 #[cfg(feature = "img")]
 #[test]
 #[ignore]
@@ -399,3 +608,1213 @@ fn custom_piece_set() {
     pip.piece_set = img::PieceSet::Custom(hm);
     img::position_to_image(board.position(), pip, Color::White).unwrap().save("test1.png").unwrap();
 }
+
+#[cfg(feature = "pgn")]
+#[test]
+fn lenient_pgn() {
+    use super::{errors::InvalidPgnError, pgn::{ImportMode, Pgn, PgnWarning}};
+
+    let text = "[White \"Alice\"]\n[Black \"Bob\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q -\"]\n\nO-O-O ke7 Rd7+ Ke6";
+    assert!(Pgn::try_from(text).is_err());
+    let (pgn, warnings) = Pgn::parse_with_mode(text, ImportMode::Lenient).unwrap();
+    assert_eq!(pgn.board().gen_movetext(), "1. O-O-O Ke7 2. Rd7+ Ke6");
+    assert_eq!(pgn.tag("Event").unwrap(), "?");
+    assert_eq!(pgn.tag("Result").unwrap(), "*");
+    assert!(warnings.iter().any(|w| matches!(w, PgnWarning::MissingTag(_, t) if t == "Date")));
+    assert!(warnings.iter().any(|w| matches!(w, PgnWarning::IncompleteFen(_))));
+    assert!(warnings.iter().any(|w| matches!(w, PgnWarning::LowercasePieceLetter(_, m) if m == "ke7")));
+    assert_eq!(warnings.iter().filter(|w| matches!(w, PgnWarning::MissingMoveNumber(_))).count(), 2);
+    assert!(warnings.iter().any(|w| matches!(w, PgnWarning::MissingResult(_))));
+    let (pgn, warnings) = Pgn::parse_with_mode("[Result \"0-1\"]\n1. f3 e5 2. g4 Qh4# 1-0", ImportMode::Lenient).unwrap();
+    assert_eq!(pgn.board().checkmated_side(), Some(Color::White));
+    assert_eq!(pgn.tag("Result").unwrap(), "0-1");
+    assert!(warnings.iter().any(|w| matches!(w, PgnWarning::InconsistentResult(_, _))));
+    let (pgn, warnings) = Pgn::from_board_with_mode(Board::default(), vec![("White".to_owned(), "Alice".to_owned())], ImportMode::Lenient).unwrap();
+    assert_eq!(pgn.tag("Date").unwrap(), "????.??.??");
+    assert_eq!(warnings.len(), 5);
+    let missing = Pgn::from_board(Board::default(), vec![("Event".to_owned(), "?".to_owned()), ("Site".to_owned(), "?".to_owned())]);
+    assert!(matches!(missing, Err(InvalidPgnError::MissingRosterTag(name)) if name == "Date"));
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn pgn_export() {
+    use super::pgn::Pgn;
+
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7").unwrap();
+    let tags = [
+        ("Event", "Test \"quoted\""),
+        ("Site", "?"),
+        ("Date", "????.??.??"),
+        ("Round", "?"),
+        ("White", "?"),
+        ("Black", "?"),
+        ("Opening", "Ruy Lopez"),
+        ("Annotator", "Me"),
+    ];
+    let mut pgn = Pgn::from_board(board, tags.into_iter().map(|(t, v)| (t.to_owned(), v.to_owned())).collect()).unwrap();
+    pgn.set_tag("ECO", "C95");
+    pgn.set_tag("Annotator", "You");
+    assert_eq!(pgn.remove_tag("Opening"), Some("Ruy Lopez".to_owned()));
+    let text = pgn.to_string();
+    assert!(text.starts_with("[Event \"Test \\\"quoted\\\"\"]\n[Site \"?\"]\n"));
+    assert!(text.contains("[Result \"*\"]\n[Annotator \"You\"]\n[ECO \"C95\"]\n\n1. e4"));
+    assert!(!text.contains("FEN"));
+    assert!(text.lines().all(|l| l.chars().count() <= 80));
+    assert_eq!(Pgn::try_from(text.as_str()).unwrap().tag("Event"), Some("Test \"quoted\""));
+    let board = Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap());
+    let text = Pgn::from_board(board, tags.into_iter().take(6).map(|(t, v)| (t.to_owned(), v.to_owned())).collect())
+        .unwrap()
+        .to_string();
+    assert!(text.contains("[Result \"*\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n"));
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn pgn_comment_commands() {
+    use super::{
+        pgn::{Arrow, Evaluation, MarkupColor, Pgn, SquareHighlight},
+        Score,
+    };
+    use std::time::Duration;
+
+    let text = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcdefgh"]
+[Date "2024.01.01"]
+[Round "-"]
+[White "A"]
+[Black "B"]
+[Result "*"]
+
+{ Opening comment [%clk 0:03:00] } 1. e4 { [%eval 0.31] [%clk 0:03:00] } 1... e5 $1 { Solid [%eval 0.25,22] [%clk 0:02:58.5] [%emt 0:00:01.5] } 2. Nf3 { [%csl Ge4,Rd5] [%cal Ge2e4,Bg1f3] [%foo bar] } 2... Nc6 { [%eval #-3] } *"#;
+    let pgn = Pgn::try_from(text).unwrap();
+    assert_eq!(pgn.comment(), Some("Opening comment"));
+    assert_eq!(pgn.initial_annotation().clock, Some(Duration::from_secs(180)));
+    let annotations = pgn.annotations();
+    assert_eq!(annotations.len(), 4);
+    assert_eq!(
+        annotations[0].eval,
+        Some(Evaluation {
+            score: Score::Centipawns(31),
+            depth: None
+        })
+    );
+    assert_eq!(annotations[0].clock, Some(Duration::from_secs(180)));
+    assert_eq!(annotations[0].comment, None);
+    assert_eq!(annotations[1].nags, vec![1]);
+    assert_eq!(annotations[1].comment.as_deref(), Some("Solid"));
+    assert_eq!(
+        annotations[1].eval,
+        Some(Evaluation {
+            score: Score::Centipawns(25),
+            depth: Some(22)
+        })
+    );
+    assert_eq!(annotations[1].clock, Some(Duration::from_millis(178500)));
+    assert_eq!(annotations[1].elapsed_move_time, Some(Duration::from_millis(1500)));
+    assert_eq!(
+        annotations[2].highlights,
+        vec![
+            SquareHighlight {
+                color: MarkupColor::Green,
+                square: ('e', '4')
+            },
+            SquareHighlight {
+                color: MarkupColor::Red,
+                square: ('d', '5')
+            }
+        ]
+    );
+    assert_eq!(
+        annotations[2].arrows,
+        vec![
+            Arrow {
+                color: MarkupColor::Green,
+                from: ('e', '2'),
+                to: ('e', '4')
+            },
+            Arrow {
+                color: MarkupColor::Blue,
+                from: ('g', '1'),
+                to: ('f', '3')
+            }
+        ]
+    );
+    assert_eq!(annotations[2].comment.as_deref(), Some("[%foo bar]"));
+    assert_eq!(annotations[3].eval.unwrap().score, Score::Mate(-3));
+    let exported = pgn.to_string();
+    assert!(exported.contains("{ Opening comment [%clk 0:03:00] } 1. e4 { [%eval 0.31] [%clk 0:03:00] } 1... e5"));
+    assert_eq!(Pgn::try_from(exported.as_str()).unwrap(), pgn);
+    // A closing brace cannot be written inside a comment.
+    let mut pgn = pgn;
+    pgn.set_comment(Some("Braces {like} these".to_owned()));
+    pgn.annotations_mut()[3].comment = Some("}".to_owned());
+    let exported = pgn.to_string();
+    assert!(exported.replace('\n', " ").contains("{ Braces {like these [%clk 0:03:00] }"));
+    let reparsed = Pgn::try_from(exported.as_str()).unwrap();
+    assert_eq!(reparsed.comment(), Some("Braces {like these"));
+    assert_eq!(reparsed.annotations()[3].comment, None);
+}
+
+#[cfg(feature = "pgn")]
+#[test]
+fn pgn_writer() {
+    use super::pgn::{GameNode, GameTree, MoveNumberStyle, Pgn, PgnWriter, WriterOptions};
+
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6").unwrap();
+    let tags = vec![("Event".to_owned(), "Casual".to_owned())];
+    let mut writer = PgnWriter::new(Vec::new());
+    writer.write_board(&board, &tags).unwrap();
+    writer.write_board(&board, &tags).unwrap();
+    assert_eq!(writer.games_written(), 2);
+    let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    let game = "[Event \"Casual\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n1. e4 e5 2. Nf3 Nc6 *\n";
+    assert_eq!(text, format!("{game}\n{game}"));
+    assert_eq!(Pgn::try_from(&text[..game.len()]).unwrap().board().position(), board.position());
+
+    let mut game = GameTree::from(&board);
+    let e4 = &mut game.moves[0];
+    e4.annotation.comment = Some("Best by test".to_owned());
+    let mut c5 = GameNode::new(Move::from_uci("c7c5").unwrap());
+    c5.continuations.push(GameNode::new(Move::from_uci("g1f3").unwrap()));
+    e4.continuations.push(c5);
+    e4.continuations.push(GameNode::new(Move::from_uci("e7e6").unwrap()));
+    let mut writer = PgnWriter::new(Vec::new());
+    writer.write_game_tree(&game).unwrap();
+    let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert!(text.ends_with("\n1. e4 { Best by test } 1... e5 (1... c5 2. Nf3) (1... e6) 2. Nf3 Nc6 *\n"));
+    assert_eq!(Pgn::try_from(text.as_str()).unwrap().board().position(), board.position());
+
+    let options = WriterOptions {
+        line_width: Some(10),
+        comments: false,
+        variations: false,
+        move_numbers: MoveNumberStyle::EveryMove,
+    };
+    let mut writer = PgnWriter::with_options(Vec::new(), options);
+    writer.write_game_tree(&game).unwrap();
+    let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert!(text.ends_with("\n1. e4 1...\ne5 2. Nf3\n2... Nc6 *\n"));
+    game.moves[0].continuations[0].move_ = Move::from_uci("e2e4").unwrap();
+    assert!(PgnWriter::new(Vec::new()).write_game_tree(&game).is_err());
+}
+
+#[cfg(all(feature = "uci", unix))]
+#[test]
+fn uci_client() {
+    use super::{
+        uci::{EngineClient, OptionType, SearchLimits},
+        Score, UciError,
+    };
+    use std::process::Command;
+    let script = std::env::temp_dir().join(format!("rschess-mock-uci-{}.sh", std::process::id()));
+    std::fs::write(
+        &script,
+        r#"while read -r cmd args; do
+    case "$cmd" in
+        uci) echo "id name Mock Engine 1.0"; echo "id author rschess"
+             echo "option name Hash type spin default 16 min 1 max 1024"
+             echo "option name Play Style type combo default Normal var Solid var Normal var Risky"
+             echo "option name Ponder type check default false"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        position) position="$args" ;;
+        go) echo "info string $position; go $args"
+            echo "info depth 1 score cp 31 nodes 20 nps 2000 time 10 pv g1f3"
+            echo "info depth 2 seldepth 4 multipv 1 score mate -2 nodes 400 pv g1f3 b8c6 e1e7"
+            echo "bestmove g1f3 ponder b8c6" ;;
+        quit) [ -z "$IGNORE_QUIT" ] && exit 0 ;;
+    esac
+done
+"#,
+    )
+    .unwrap();
+    let mut command = Command::new("sh");
+    command.arg(&script).env("IGNORE_QUIT", "1");
+    let stubborn = EngineClient::from_command(command).unwrap();
+    let start = std::time::Instant::now();
+    drop(stubborn);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    let mut command = Command::new("sh");
+    command.arg(&script);
+    let mut engine = EngineClient::from_command(command).unwrap();
+    assert_eq!(engine.name(), Some("Mock Engine 1.0"));
+    assert_eq!(engine.author(), Some("rschess"));
+    assert_eq!(engine.options().len(), 3);
+    assert_eq!(engine.options()[1].name, "Play Style");
+    assert!(matches!(&engine.options()[1].option_type, OptionType::Combo { default, choices } if default == "Normal" && choices.len() == 3));
+    assert_eq!(engine.options()[0].to_string(), "option name Hash type spin default 16 min 1 max 1024");
+    engine.set_option("Hash", Some("64")).unwrap();
+    engine.new_game().unwrap();
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5").unwrap();
+    engine.set_position(&board).unwrap();
+    let result = engine.go(&SearchLimits::depth(2)).unwrap();
+    assert_eq!(result.info[0].string.as_deref(), Some("startpos moves e2e4 e7e5; go depth 2"));
+    assert_eq!(result.info[1].nps, Some(2000));
+    assert_eq!(result.info[1].time, Some(std::time::Duration::from_millis(10)));
+    assert_eq!(result.info[2].seldepth, Some(4));
+    assert_eq!(result.score(), Some(Score::Mate(-2)));
+    assert_eq!(result.pv().iter().map(Move::to_uci).collect::<Vec<_>>(), ["g1f3", "b8c6"]);
+    assert_eq!(result.best_move.map(|m| m.to_uci()).as_deref(), Some("g1f3"));
+    assert_eq!(result.ponder.map(|m| m.to_uci()).as_deref(), Some("b8c6"));
+    let mut board = Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap());
+    board.make_move_uci("e1g1").unwrap();
+    engine.set_option("UCI_Chess960", Some("true")).unwrap();
+    engine.set_position(&board).unwrap();
+    let mut sent = None;
+    let result = engine.go_with(&SearchLimits::nodes(1), |info| {
+        sent = sent.take().or(info.string.clone());
+        true
+    });
+    assert_eq!(sent.as_deref(), Some("fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1; go nodes 1"));
+    assert!(matches!(result, Err(UciError::InvalidMove(m)) if m == "g1f3"));
+    engine.quit().unwrap();
+    std::fs::remove_file(script).unwrap();
+}
+
+/// A UCI engine which plays the first legal move, reporting its skill level as the score
+#[cfg(feature = "uci")]
+struct FirstMove {
+    skill: i64,
+    searches: Vec<uci::SearchLimits>,
+}
+#[cfg(feature = "uci")]
+impl uci::Engine for FirstMove {
+    fn name(&self) -> String {
+        "First Move".to_owned()
+    }
+    fn author(&self) -> String {
+        "rschess".to_owned()
+    }
+    fn options(&self) -> Vec<uci::EngineOption> {
+        vec![uci::EngineOption {
+            name: "Skill Level".to_owned(),
+            option_type: uci::OptionType::Spin { default: 20, min: 0, max: 20 },
+        }]
+    }
+    fn set_option(&mut self, name: &str, value: Option<&str>) {
+        if name == "Skill Level" {
+            self.skill = value.unwrap().parse().unwrap();
+        }
+    }
+    fn search(&mut self, board: &Board, limits: &uci::SearchLimits, control: &uci::SearchControl) -> uci::BestMove {
+        self.searches.push(limits.clone());
+        while limits.infinite && !control.should_stop() {}
+        let moves = board.gen_legal_moves();
+        let Some(&best_move) = moves.first() else { return uci::BestMove::default() };
+        let info = uci::SearchInfo {
+            depth: Some(1),
+            score: Some(super::Score::Centipawns(self.skill as i32)),
+            pv: vec![best_move],
+            ..Default::default()
+        };
+        control.report(&info).unwrap();
+        let ponder = board.position().with_move_made(best_move).unwrap().gen_non_illegal_moves().first().copied();
+        uci::BestMove { best_move: Some(best_move), ponder }
+    }
+}
+
+#[cfg(feature = "uci")]
+#[test]
+fn uci_engine() {
+    use std::io::Cursor;
+    let input = "uci\nsetoption name Skill Level value 7\nisready\nucinewgame\nposition startpos moves e2e4 e7e5\ngo wtime 60000 btime 59000 winc 1000 binc 1000 movestogo 30\n\
+                 position fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\ngo infinite\nstop\nposition fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo depth 3\nquit\ngo depth 1\n";
+    let mut engine = FirstMove { skill: 20, searches: Vec::new() };
+    let mut output = Vec::new();
+    uci::run_with(&mut engine, Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "id name First Move",
+            "id author rschess",
+            "option name Skill Level type spin default 20 min 0 max 20",
+            "uciok",
+            "readyok",
+            "info depth 1 score cp 7 pv b1a3",
+            "bestmove b1a3 ponder a7a6",
+            "info depth 1 score cp 7 pv a1b1",
+            "bestmove a1b1 ponder e8f8",
+            "bestmove 0000",
+        ]
+    );
+    assert_eq!(engine.searches.len(), 3);
+    assert_eq!(engine.searches[0].to_string(), "go wtime 60000 btime 59000 winc 1000 binc 1000 movestogo 30");
+    assert!(engine.searches[1].infinite);
+    assert_eq!(engine.searches[2].depth, Some(3));
+    let input = "position fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\ngo infinite\nquit\ngo depth 1\n";
+    let mut engine = FirstMove { skill: 20, searches: Vec::new() };
+    let mut output = Vec::new();
+    uci::run_with(&mut engine, Cursor::new(input), &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "info depth 1 score cp 20 pv a1b1\n",
+        "quitting during a search discards its best move"
+    );
+    assert_eq!(engine.searches.len(), 1);
+}
+
+#[cfg(feature = "uci")]
+#[test]
+fn uci_engine_pondering() {
+    use std::io::Cursor;
+    let input = "position startpos moves e2e4\ngo ponder wtime 1000 btime 1000\nponderhit\nposition startpos moves e2e4 e7e5 g1f3\ngo ponder\nstop\nisready\n";
+    let mut engine = FirstMove { skill: 20, searches: Vec::new() };
+    let mut output = Vec::new();
+    uci::run_with(&mut engine, Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "info depth 1 score cp 20 pv a7a6",
+            "bestmove a7a6 ponder b1a3",
+            "info depth 1 score cp 20 pv a7a6",
+            "bestmove a7a6 ponder b1a3",
+            "readyok"
+        ]
+    );
+    assert_eq!(engine.searches.len(), 2);
+    assert!(engine.searches.iter().all(|limits| limits.ponder));
+}
+
+#[cfg(feature = "uci")]
+#[test]
+fn uci_engine_game_over() {
+    use std::io::Cursor;
+    // The starting position is repeated five times by the knight moves, which the GUI may not count as the end of the game.
+    let moves = "g1f3 g8f6 f3g1 f6g8 ".repeat(5);
+    let input = format!("position startpos moves {moves}\ngo depth 1\nposition startpos moves {moves}e2e4\ngo depth 1\n");
+    let mut engine = FirstMove { skill: 20, searches: Vec::new() };
+    let mut output = Vec::new();
+    uci::run_with(&mut engine, Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        ["info depth 1 score cp 20 pv b1a3", "bestmove b1a3 ponder a7a6", "info depth 1 score cp 20 pv a7a6", "bestmove a7a6 ponder b1a3"]
+    );
+}
+
+#[cfg(feature = "cecp")]
+#[test]
+fn cecp_engine() {
+    use super::cecp;
+    use std::{io::Cursor, time::Duration};
+    let input = "xboard\nprotover 2\nnew\noption Skill Level=3\nlevel 40 5 0\npost\ntime 30000\notim 29000\nusermove e2e4\nping 1\nusermove e2e5\n\
+                 force\nsetboard 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\nnopost\nsd 4\ngo\nusermove e8d8\nfoo\nquit\n";
+    let mut engine = FirstMove { skill: 20, searches: Vec::new() };
+    let mut output = Vec::new();
+    cecp::run_with(&mut engine, Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "feature myname=\"First Move\" setboard=1 usermove=1 ping=1 playother=1 san=0 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 done=0",
+            "feature option=\"Skill Level -spin 20 0 20\"",
+            "feature done=1",
+            "1 3 0 0 a6",
+            "move a7a6",
+            "pong 1",
+            "Illegal move: e2e5",
+            "move a1b1",
+            "move b1a1",
+            "Error (unknown command): foo",
+        ]
+    );
+    assert_eq!(engine.searches.len(), 3);
+    assert_eq!(engine.searches[0].btime, Some(Duration::from_secs(300)));
+    assert_eq!(engine.searches[0].wtime, Some(Duration::from_secs(290)));
+    assert_eq!(engine.searches[0].movestogo, Some(40));
+    assert_eq!(engine.searches[1].depth, Some(4));
+}
+
+#[cfg(all(feature = "cecp", unix))]
+#[test]
+fn cecp_client() {
+    use super::{cecp::EngineClient, uci::OptionType, Score};
+    use std::process::Command;
+    let script = std::env::temp_dir().join(format!("rschess-mock-cecp-{}.sh", std::process::id()));
+    std::fs::write(
+        &script,
+        r#"while read -r cmd args; do
+    case "$cmd" in
+        protover) echo 'feature myname="Mock XBoard" setboard=1 usermove=1 ping=1 san=1 variants="normal" done=0'
+                  echo 'feature option="Hash -spin 16 1 1024" option="Style -combo Solid /// *Normal /// Risky" done=1' ;;
+        ping) echo "pong $args" ;;
+        usermove) last="$args" ;;
+        go) case "$last" in
+                e5) echo "1 31 5 20 Nf3"; echo "2 99998 10 400 1. Nf3 Nc6 2. Bb5"; echo "move g1f3" ;;
+                *) echo "Illegal move: $last" ;;
+            esac ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    )
+    .unwrap();
+    let mut command = Command::new("sh");
+    command.arg(&script);
+    let mut engine = EngineClient::from_command(command).unwrap();
+    assert_eq!(engine.name(), Some("Mock XBoard"));
+    assert_eq!(engine.feature("variants"), None);
+    let options = engine.options();
+    assert_eq!(options.len(), 2);
+    assert!(matches!(&options[1].option_type, OptionType::Combo { default, choices } if default == "Normal" && choices.len() == 3));
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5").unwrap();
+    engine.set_position(&board).unwrap();
+    engine.ping().unwrap();
+    let reply = engine.go(&uci::SearchLimits::depth(2)).unwrap();
+    assert_eq!(reply.move_.map(|m| m.to_uci()).as_deref(), Some("g1f3"));
+    assert_eq!(reply.info.len(), 2);
+    assert_eq!(reply.info[1].score, Some(Score::Mate(2)));
+    assert_eq!(reply.info[1].pv.iter().map(Move::to_uci).collect::<Vec<_>>(), ["g1f3", "b8c6", "f1b5"]);
+    assert_eq!(engine.board().gen_movetext(), "1. e4 e5 2. Nf3");
+    engine.user_move(Move::from_uci("d7d6").unwrap()).unwrap();
+    assert!(engine.go(&uci::SearchLimits::default()).is_err());
+    engine.quit().unwrap();
+    std::fs::remove_file(script).unwrap();
+}
+
+#[cfg(all(feature = "cecp", unix))]
+#[test]
+fn cecp_client_rejected_move() {
+    use super::{cecp::EngineClient, CecpError};
+    use std::{process::Command, time::Duration};
+    let script = std::env::temp_dir().join(format!("rschess-rejecting-cecp-{}.sh", std::process::id()));
+    std::fs::write(
+        &script,
+        r#"while read -r cmd args; do
+    case "$cmd" in
+        protover) echo 'feature usermove=1 ping=1 san=1 done=1' ;;
+        ping) echo "pong $args" ;;
+        usermove) case "$args" in
+                      e4) ;;
+                      *) echo "Illegal move: $args" ;;
+                  esac ;;
+        st) secs="$args" ;;
+        go) echo "move e7e$((secs + 3))" ;;
+        quit) exit 0 ;;
+    esac
+done
+"#,
+    )
+    .unwrap();
+    let mut command = Command::new("sh");
+    command.arg(&script);
+    let mut engine = EngineClient::from_command(command).unwrap();
+    engine.user_move(Move::from_uci("e2e4").unwrap()).unwrap();
+    assert!(matches!(engine.user_move(Move::from_uci("e7e5").unwrap()), Err(CecpError::Engine(line)) if line == "Illegal move: e5"));
+    assert_eq!(engine.board().gen_movetext(), "1. e4");
+    let limits = uci::SearchLimits {
+        movetime: Some(Duration::from_millis(1600)),
+        ..Default::default()
+    };
+    assert_eq!(engine.go(&limits).unwrap().move_.map(|m| m.to_uci()).as_deref(), Some("e7e5"));
+    engine.quit().unwrap();
+    std::fs::remove_file(script).unwrap();
+}
+
+#[cfg(feature = "engine")]
+#[test]
+fn engine_search() {
+    use super::{
+        engine::{Limits, Searcher, Strength},
+        evaluation::{Breakdown, Evaluator, Term},
+        Position, Score,
+    };
+    use std::time::Duration;
+    let mut searcher = Searcher::default();
+    let position = Board::from_fen(Fen::try_from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap()).position().clone();
+    let analysis = searcher.search(&position, &Limits::depth(3));
+    assert_eq!(analysis.best_move.map(|m| m.to_uci()).as_deref(), Some("a1a8"));
+    assert_eq!(analysis.score, Score::Mate(1));
+    let position = Board::from_fen(Fen::try_from("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap()).position().clone();
+    let analysis = searcher.search(&position, &Limits::depth(2));
+    assert_eq!(analysis.best_move.map(|m| m.to_uci()).as_deref(), Some("d1d5"));
+    assert_eq!(analysis.pv.len(), 2);
+    let position = Board::from_fen(Fen::try_from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap()).position().clone();
+    let analysis = searcher.search(&position, &Limits::default());
+    assert_eq!((analysis.best_move, analysis.score), (None, Score::Centipawns(0)));
+    let position = Board::default().position().clone();
+    let analysis = searcher.search(&position, &Limits::movetime(Duration::from_millis(200)));
+    assert!(analysis.best_move.is_some() && analysis.time < Duration::from_secs(2));
+    let mut beginner = Searcher::new(Strength::Beginner);
+    let analysis = beginner.search(&position, &Limits::default());
+    assert_eq!(analysis.depth, 1);
+    let mut iterations = 0;
+    searcher.search_with(
+        &position,
+        &Limits::depth(3),
+        |_| {
+            iterations += 1;
+            iterations < 2
+        },
+        || false,
+    );
+    assert_eq!(iterations, 2);
+    // The only legal move is searched alike at every strength, as the noise only affects the choice between moves.
+    let position = Board::from_fen(Fen::try_from("k7/8/Q7/8/8/8/8/6K1 b - - 0 1").unwrap()).position().clone();
+    assert_eq!(beginner.search(&position, &Limits::depth(1)).score, Searcher::default().search(&position, &Limits::depth(1)).score);
+    // The losing side draws by repeating the starting position of the game, or by the fifty-move rule.
+    let mut board = Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap());
+    board.make_moves_san("Qd2 Kf7 Qd1").unwrap();
+    let analysis = searcher.search_game(&board, &Limits::depth(3));
+    assert_eq!((analysis.best_move.map(|m| m.to_uci()).as_deref(), analysis.score), (Some("f7e8"), Score::Centipawns(0)));
+    assert!(matches!(searcher.search(board.position(), &Limits::depth(3)).score, Score::Centipawns(score) if score < -500));
+    let board = Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/3QK3 b - - 99 80").unwrap());
+    assert_eq!(searcher.search_game(&board, &Limits::depth(3)).score, Score::Centipawns(0));
+    struct Pacifist;
+    impl Evaluator for Pacifist {
+        fn breakdown(&self, position: &Position) -> Breakdown {
+            let pieces = position.to_fen().split(' ').next().unwrap().chars().filter(char::is_ascii_alphabetic).count() as i32;
+            Breakdown {
+                terms: vec![Term::new("Pieces", pieces, 0)],
+            }
+        }
+    }
+    let mut pacifist = Searcher::with_evaluator(Strength::Maximum, Pacifist);
+    let position = Board::from_fen(Fen::try_from("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap()).position().clone();
+    assert_ne!(pacifist.search(&position, &Limits::depth(1)).best_move.map(|m| m.to_uci()).as_deref(), Some("d1d5"));
+}
+
+#[cfg(all(feature = "engine", feature = "uci"))]
+#[test]
+fn engine_pondering() {
+    use super::engine::Searcher;
+    use std::{
+        io::Cursor,
+        time::{Duration, Instant},
+    };
+    let mut searcher = Searcher::default();
+    let mut output = Vec::new();
+    let start = Instant::now();
+    uci::run_with(&mut searcher, Cursor::new("position startpos moves e2e4\ngo ponder movetime 100\nponderhit\n"), &mut output).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5), "the movetime applies once pondering ends");
+    assert!(String::from_utf8(output).unwrap().lines().last().unwrap().starts_with("bestmove "));
+}
+
+#[test]
+fn evaluation() {
+    use super::evaluation::{Breakdown, Evaluator, HandcraftedEvaluator, Term};
+    let evaluator = HandcraftedEvaluator;
+    let start = evaluator.breakdown(Board::default().position());
+    assert_eq!(start.total(), 0);
+    assert_eq!(start.terms.len(), 5);
+    assert_eq!(start.term("Material").unwrap().white, 8 * 100 + 2 * 320 + 2 * 330 + 2 * 500 + 900 + 30);
+    let breakdown = evaluator.breakdown(Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap()).position());
+    assert_eq!(breakdown.term("Material").unwrap().score(), 900);
+    assert!(breakdown.term("Mobility").unwrap().score() > 0);
+    assert!(evaluator.evaluate(Board::from_fen(Fen::try_from("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap()).position()) > 0);
+    let breakdown = evaluator.breakdown(Board::from_fen(Fen::try_from("4k3/8/8/8/P7/P7/8/4K3 w - - 0 1").unwrap()).position());
+    assert!(breakdown.term("Pawn structure").unwrap().white < 10 + 10 * 2 + 10 + 10 * 3);
+    let breakdown = evaluator.breakdown(Board::from_fen(Fen::try_from("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap()).position());
+    assert_eq!(breakdown.term("Pawn structure").unwrap().white, -15 + 10 + 10 * 4);
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6 Bc4 Nf6 O-O Be7").unwrap();
+    let safe = evaluator.breakdown(board.position()).term("King safety").unwrap().clone();
+    assert!(safe.white > safe.black);
+    for position in [Board::default().position(), board.position()] {
+        assert_eq!(evaluator.evaluate(position), evaluator.breakdown(position).total());
+    }
+    // The black pawn can push to h3 next to the white king, which only matters if the square is attacked.
+    let king_safety = |fen: &str| evaluator.breakdown(Board::from_fen(Fen::try_from(fen).unwrap()).position()).term("King safety").unwrap().white;
+    assert_eq!(king_safety("k7/8/8/8/7p/8/6K1/N2Q4 w - - 0 1"), king_safety("k7/8/8/8/7p/7N/6K1/3Q4 w - - 0 1"));
+    let custom = Breakdown {
+        terms: vec![Term::new("Space", 12, 4), Term::new("Tempo", 10, 0)],
+    };
+    assert_eq!(custom.total(), 18);
+    assert_eq!(
+        custom.to_string(),
+        "Term    White   Black   Total\nSpace      12       4       8\nTempo      10       0      10\nTotal                      18"
+    );
+}
+
+#[cfg(feature = "polyglot")]
+#[test]
+fn polyglot_book() {
+    use super::{errors::PolyglotError, polyglot};
+    let keys = [
+        ("", 0x463b96181691fc9c),
+        ("e2e4", 0x823c9b50fd114196),
+        ("e2e4 d7d5", 0x0756b94461c50fb0),
+        ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+        ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560),
+    ];
+    for (line, key) in keys {
+        let mut board = Board::default();
+        if !line.is_empty() {
+            board.make_moves_uci(line).unwrap();
+        }
+        assert_eq!(polyglot::key(board.position()), key);
+    }
+    let mut castling = Board::default();
+    castling.make_moves_san("e4 e5 Nf3 Nc6 Bc4 Bc5").unwrap();
+    let entry = |key: u64, src: u16, dest: u16, weight: u16| [&key.to_be_bytes()[..], &((src << 6) | dest).to_be_bytes(), &weight.to_be_bytes(), &[0; 4]].concat();
+    let start = polyglot::key(Board::default().position());
+    let bytes = [
+        entry(polyglot::key(castling.position()), 4, 7, 10),
+        entry(start, 12, 28, 3),
+        entry(start, 11, 27, 1),
+        entry(start, 12, 36, 50),
+        entry(start, 1, 18, 0),
+    ]
+    .concat();
+    let book = polyglot::Book::from_bytes(&bytes).unwrap();
+    assert_eq!(book.len(), 5);
+    let start = Board::default();
+    assert_eq!(book.entries_for(start.position()).len(), 4);
+    let moves: Vec<_> = book.moves(start.position()).into_iter().map(|m| (m.move_.to_uci(), m.weight)).collect();
+    assert_eq!(moves, [("e2e4".to_owned(), 3), ("d2d4".to_owned(), 1), ("b1c3".to_owned(), 0)]);
+    assert_eq!(book.best_move(start.position()).map(|m| m.to_uci()).as_deref(), Some("e2e4"));
+    assert_eq!(book.weighted_move_with(start.position(), 2).map(|m| m.to_uci()).as_deref(), Some("e2e4"));
+    assert_eq!(book.weighted_move_with(start.position(), 7).map(|m| m.to_uci()).as_deref(), Some("d2d4"));
+    assert!(book.weighted_move(start.position()).is_some_and(|m| m.to_uci() != "b1c3"));
+    assert_eq!(book.best_move(castling.position()), Some(castling.san_to_move("O-O").unwrap()));
+    assert_eq!(book.best_move(castling.position().with_move_made(castling.san_to_move("O-O").unwrap()).as_ref().unwrap()), None);
+    assert!(matches!(polyglot::Book::from_bytes(&bytes[1..]), Err(PolyglotError::Length(79))));
+}
+
+#[cfg(all(feature = "polyglot", feature = "pgn"))]
+#[test]
+fn polyglot_builder() {
+    use super::{
+        pgn::{ImportMode, Pgn},
+        polyglot::{self, Book, BookBuilder, BuilderOptions},
+    };
+    let games = r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 (2. Bc4 *) Nc6 3. Bc4 Bc5 4. O-O 1-0
+
+[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 d6 1/2-1/2
+[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+
+[Event "?"]
+[Result "*"]
+
+1. e4 e5
+[Event "?"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 1-0
+1. e4 e6 *
+"#;
+    let parsed = Pgn::parse_games(games, ImportMode::Strict).unwrap();
+    assert_eq!(parsed.len(), 6);
+    assert_eq!(parsed.iter().filter(|g| g.is_ok()).count(), 3);
+    let parsed = Pgn::parse_games(games, ImportMode::Lenient).unwrap();
+    assert!(parsed.iter().all(|g| g.is_ok()));
+    assert_eq!(parsed[1].as_ref().unwrap().0.board().gen_movetext(), "1. e4 c5 2. Nf3 d6");
+    let mut builder = BookBuilder::with_options(BuilderOptions { max_ply: 4, min_games: 1, side: None });
+    assert_eq!(builder.add_pgn(games, ImportMode::Lenient).unwrap(), 4);
+    let book = builder.build();
+    let start = Board::default();
+    let moves: Vec<_> = book.moves(start.position()).into_iter().map(|m| (m.move_.to_uci(), m.weight)).collect();
+    assert_eq!(moves, [("e2e4".to_owned(), 5)]);
+    let mut board = Board::default();
+    board.make_moves_san("e4").unwrap();
+    let moves: Vec<_> = book.moves(board.position()).into_iter().map(|m| (m.move_.to_uci(), m.weight)).collect();
+    assert_eq!(moves, [("c7c5".to_owned(), 1)]);
+    board.make_moves_san("e5 Nf3 Nc6").unwrap();
+    assert!(book.entries_for(board.position()).is_empty());
+    let mut bytes = Vec::new();
+    book.write_to(&mut bytes).unwrap();
+    assert_eq!(Book::from_bytes(&bytes).unwrap().entries(), book.entries());
+    let mut white = BookBuilder::with_options(BuilderOptions {
+        side: Some(Color::White),
+        min_games: 2,
+        ..Default::default()
+    });
+    white.add_pgn(games, ImportMode::Lenient).unwrap();
+    let book = white.build();
+    assert_eq!(book.len(), 1);
+    let mut board = Board::default();
+    board.make_moves_san("e4 e5 Nf3 Nc6 Bc4 Bc5").unwrap();
+    let castling = board.san_to_move("O-O").unwrap();
+    assert_eq!(polyglot::encode_move(castling, board.position()), (4 << 6) | 7);
+    assert_eq!(polyglot::decode_move(polyglot::encode_move(castling, board.position()), board.position()), Some(castling));
+}
+
+#[cfg(feature = "eco")]
+#[test]
+fn eco_classification() {
+    use super::eco;
+    let openings = eco::openings();
+    let mut codes: Vec<_> = openings.iter().map(|o| o.code).collect();
+    codes.dedup();
+    assert_eq!(codes.len(), 500);
+    assert_eq!((codes[0], codes[499]), ("A00", "E99"));
+    for opening in openings {
+        let mut board = Board::default();
+        for move_ in opening.line() {
+            board.make_move(move_).unwrap();
+        }
+        assert_eq!(eco::classify(board.position()).map(|o| o.moves.split(' ').count()), Some(opening.moves.split(' ').count()));
+    }
+    let mut board = Board::default();
+    assert_eq!(board.classify_opening(), None);
+    board.make_moves_san("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5 e6 f4 Qb6 Qd2 Qxb2").unwrap();
+    let najdorf = board.classify_opening().unwrap();
+    assert_eq!(najdorf.to_string(), "B97 Sicilian Defense: Najdorf Variation, Poisoned Pawn Variation");
+    let mut transposed = Board::default();
+    transposed.make_moves_san("Nf3 Nf6 c4 e6 d4 d5 Nc3 Be7").unwrap();
+    let mut direct = Board::default();
+    direct.make_moves_san("d4 d5 c4 e6 Nc3 Nf6 Nf3 Be7").unwrap();
+    assert_eq!(transposed.classify_opening(), direct.classify_opening());
+    assert_eq!(direct.classify_opening().map(|o| o.code), Some("D37"));
+    let mut board = Board::default();
+    board.make_moves_san("Nf3 d5 d4").unwrap();
+    assert_eq!(board.classify_opening().map(|o| o.code), Some("D02"));
+    board.make_moves_san("Nf6 Bf4 e6 e3 c5 c3 Nc6 Nbd2").unwrap();
+    assert_eq!(board.classify_opening().map(|o| o.full_name()).as_deref(), Some("Queen's Pawn Game: London System"));
+}
+
+#[cfg(all(feature = "eco", feature = "pgn"))]
+#[test]
+fn eco_pgn_tags() {
+    use super::pgn::Pgn;
+    let mut pgn =
+        Pgn::try_from("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n[Variation \"?\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 *").unwrap();
+    assert_eq!(pgn.fill_opening_tags().map(|o| o.code), Some("C60"));
+    assert_eq!((pgn.tag("ECO"), pgn.tag("Opening"), pgn.tag("Variation")), (Some("C60"), Some("Ruy Lopez"), None));
+    let mut pgn = Pgn::try_from("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n1. e4 e6 2. d4 d5 3. Nc3 Bb4 *").unwrap();
+    pgn.fill_opening_tags();
+    assert_eq!(
+        (pgn.tag("ECO"), pgn.tag("Opening"), pgn.tag("Variation")),
+        (Some("C15"), Some("French Defense"), Some("Winawer Variation"))
+    );
+}
+
+#[cfg(feature = "tablebase")]
+#[test]
+fn tablebase_generation() {
+    use super::{
+        errors::TablebaseError,
+        tablebase::{Endgame, Probe, Table, Tablebase, Wdl},
+    };
+    let endgame = Endgame::try_from("KvKQ").unwrap();
+    assert_eq!(endgame.to_string(), "KQvK");
+    assert!(Endgame::try_from("KQRvKB").is_err());
+    assert!(Endgame::try_from("KQK").is_err());
+    let mut tablebase = Tablebase::new();
+    tablebase.generate(&endgame);
+    assert_eq!(tablebase.tables().count(), 2);
+    let probe = |tablebase: &Tablebase, fen: &str| {
+        let board = Board::from_fen(Fen::try_from(fen).unwrap());
+        (tablebase.probe(board.position()), tablebase.best_move(board.position()).map(|m| m.to_uci()))
+    };
+    let win = |dtm| Some(Probe { wdl: Wdl::Win, dtm: Some(dtm) });
+    let loss = |dtm| Some(Probe { wdl: Wdl::Loss, dtm: Some(dtm) });
+    assert_eq!(probe(&tablebase, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), (win(1), Some("g1g8".to_owned())));
+    assert_eq!(probe(&tablebase, "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1").0, loss(0));
+    assert_eq!(
+        probe(&tablebase, "8/8/8/8/8/8/1k6/Q3K3 b - - 0 1"),
+        (Some(Probe { wdl: Wdl::Draw, dtm: None }), Some("b2a1".to_owned()))
+    );
+    assert_eq!(probe(&tablebase, "8/8/8/8/8/8/1K6/q3k3 w - - 0 1").1, Some("b2a1".to_owned()));
+    assert_eq!(probe(&tablebase, "8/8/8/3k4/8/8/8/R3K3 w Q - 0 1").0, None);
+    assert_eq!(probe(&tablebase, "8/8/8/3k4/8/8/8/R3K3 w - - 0 1").0, None);
+    let dir = std::env::temp_dir().join(format!("rschess-tablebase-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    tablebase.save(&dir).unwrap();
+    let loaded = Tablebase::load(&dir).unwrap();
+    assert!(matches!(Table::read_from(&b"RSTB\x04KQvK"[..]), Err(TablebaseError::Length(name, 0, _)) if name == "KQvK"));
+    assert!(matches!(Table::open(dir.join("missing.rtb")), Err(TablebaseError::Io(_))));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(probe(&loaded, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").0, win(1));
+    assert_eq!(probe(&loaded, "8/8/8/8/8/2k5/8/K1Q5 b - - 0 1").0, probe(&tablebase, "8/8/8/8/8/2k5/8/K1Q5 b - - 0 1").0);
+}
+
+#[cfg(feature = "tablebase")]
+#[test]
+fn tablebase_pawn_endgames() {
+    use super::tablebase::{Endgame, Tablebase, Wdl};
+    let mut tablebase = Tablebase::new();
+    tablebase.generate(&Endgame::try_from("KPvK").unwrap());
+    assert_eq!(tablebase.tables().count(), 6);
+    let probe = |fen: &str| tablebase.probe(Board::from_fen(Fen::try_from(fen).unwrap()).position()).unwrap();
+    assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").wdl, Wdl::Loss);
+    assert_eq!(probe("4k3/8/8/4K3/4P3/8/8/8 w - - 0 1").wdl, Wdl::Win);
+    assert_eq!(probe("4k3/8/8/4K3/4P3/8/8/8 b - - 0 1").wdl, Wdl::Draw);
+    assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1").wdl, Wdl::Draw);
+    assert_eq!(probe("8/8/8/8/8/8/k6p/7K b - - 0 1").wdl, Wdl::Draw);
+    let mut board = Board::from_fen(Fen::try_from("4k3/8/8/4K3/4P3/8/8/8 w - - 0 1").unwrap());
+    for _ in 0..probe(&board.to_fen().to_string()).dtm.unwrap() {
+        board.make_move(tablebase.best_move(board.position()).unwrap()).unwrap();
+    }
+    assert!(board.position().is_checkmate());
+}
+
+#[cfg(feature = "tablebase")]
+#[test]
+fn tablebase_four_pieces() {
+    use super::tablebase::{Endgame, Tablebase, Wdl};
+    let mut tablebase = Tablebase::new();
+    tablebase.generate(&Endgame::try_from("KBNvK").unwrap());
+    assert_eq!(tablebase.tables().count(), 4);
+    let probe = |fen: &str| tablebase.probe(Board::from_fen(Fen::try_from(fen).unwrap()).position()).unwrap();
+    assert_eq!(probe("8/8/8/8/8/8/2k5/KBN5 b - - 0 1").wdl, Wdl::Draw);
+    assert_eq!(probe("7k/4N3/5BK1/8/8/8/8/8 b - - 0 1").dtm, Some(0));
+    let mut board = Board::from_fen(Fen::try_from("8/8/8/4k3/8/8/8/KBN5 w - - 0 1").unwrap());
+    let dtm = probe(&board.to_fen().to_string()).dtm.unwrap();
+    // the longest mate in this endgame takes 33 moves
+    assert!((1..=65).contains(&dtm));
+    for _ in 0..dtm {
+        board.make_move(tablebase.best_move(board.position()).unwrap()).unwrap();
+    }
+    assert!(board.position().is_checkmate());
+}
+
+#[cfg(feature = "tablebase")]
+#[test]
+fn syzygy_probing() {
+    use super::{
+        errors::SyzygyError,
+        tablebase::syzygy::{SyzygyTablebase, Wdl},
+    };
+    // Hand-encoded tables holding a single value, which only cover the headers and the single-value encoding rather than real table data:
+    // KRvK is won with White to move and lost with Black to move, with a DTZ of 11 plies for White.
+    let dir = std::env::temp_dir().join(format!("rschess-syzygy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let wdl = [0x71, 0xE8, 0x23, 0x5D, 0x01, 0x00, 0x66, 0x44, 0xEE, 0x00, 0x80, 0x04, 0x80, 0x00];
+    let dtz = [0xD7, 0x66, 0x0C, 0xA5, 0x01, 0x00, 0x06, 0x04, 0x0E, 0x00, 0x80, 0x05];
+    std::fs::write(dir.join("KRvK.rtbw"), wdl).unwrap();
+    std::fs::write(dir.join("KRvK.rtbz"), dtz).unwrap();
+    std::fs::write(dir.join("KQvK.rtbw"), b"not a table").unwrap();
+    std::fs::write(dir.join("notes.txt"), b"").unwrap();
+    let tablebase = SyzygyTablebase::open(&dir).unwrap();
+    assert_eq!(tablebase.max_pieces(), 3);
+    let board = |fen: &str| Board::from_fen(Fen::try_from(fen).unwrap());
+    let wdl = |fen: &str| tablebase.probe_wdl(board(fen).position());
+    let dtz = |fen: &str| tablebase.probe_dtz(board(fen).position());
+    assert_eq!(wdl("k7/8/8/8/8/8/8/K6R w - - 0 1").unwrap(), Wdl::Win);
+    assert_eq!(wdl("k7/8/8/8/8/8/8/K6R b - - 0 1").unwrap(), Wdl::Loss);
+    assert_eq!(wdl("8/8/8/8/8/8/6k1/K6R b - - 0 1").unwrap(), Wdl::Draw);
+    assert_eq!(wdl("K7/8/8/8/8/8/8/k6r w - - 0 1").unwrap(), Wdl::Loss);
+    assert_eq!(wdl("k7/8/8/8/8/8/8/K7 w - - 0 1").unwrap(), Wdl::Draw);
+    assert_eq!(dtz("k7/8/8/8/8/8/8/K6R w - - 0 1").unwrap(), 11);
+    assert_eq!(dtz("k7/8/8/8/8/8/8/K6R b - - 0 1").unwrap(), -12);
+    assert_eq!(dtz("K7/8/8/8/8/8/8/k6r w - - 0 1").unwrap(), -12);
+    assert_eq!(dtz("8/8/8/8/8/8/6k1/K6R b - - 0 1").unwrap(), 0);
+    let root = tablebase.probe_root(&board("8/8/8/8/8/K7/2k5/7R w - - 0 1")).unwrap();
+    assert_eq!(root.len(), board("8/8/8/8/8/K7/2k5/7R w - - 0 1").gen_legal_moves().len());
+    assert!(root[..root.len() - 3].iter().all(|m| m.wdl == Wdl::Win && m.dtz == 13));
+    let mut drawn: Vec<_> = root[root.len() - 3..].iter().map(|m| (m.wdl, m.dtz, m.move_.to_uci())).collect();
+    drawn.sort_by(|a, b| a.2.cmp(&b.2));
+    assert_eq!(drawn, [(Wdl::Draw, 0, "h1b1".to_owned()), (Wdl::Draw, 0, "h1c1".to_owned()), (Wdl::Draw, 0, "h1d1".to_owned())]);
+    assert_eq!(tablebase.probe_board(&board("8/8/8/8/8/K7/2k5/7R w - - 0 1")).unwrap(), Wdl::Win);
+    assert_eq!(tablebase.probe_board(&board("8/8/8/8/8/K7/2k5/7R w - - 90 60")).unwrap(), Wdl::CursedWin);
+    assert_eq!(tablebase.probe_board(&board("k7/8/8/8/8/8/8/K6R b - - 90 60")).unwrap(), Wdl::BlessedLoss);
+    let root = tablebase.probe_root(&board("8/8/8/8/8/K7/2k5/7R w - - 90 60")).unwrap();
+    assert!(root.iter().all(|m| matches!((m.wdl, m.dtz), (Wdl::CursedWin, 13) | (Wdl::Draw, 0))));
+    assert!(matches!(wdl("k7/8/8/8/8/8/8/K5Q1 w - - 0 1"), Err(SyzygyError::InvalidTable(_))));
+    assert!(matches!(wdl("k7/8/8/8/8/8/8/K5B1 w - - 0 1"), Err(SyzygyError::MissingTable(name)) if name == "KBvK"));
+    assert!(matches!(wdl("r3k3/8/8/8/8/8/8/4K3 b q - 0 1"), Err(SyzygyError::CastlingRights)));
+    assert!(matches!(wdl("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"), Err(SyzygyError::TooManyPieces(32, 3))));
+    assert!(matches!(SyzygyTablebase::open(dir.join("missing")), Err(SyzygyError::Io(_))));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "tablebase")]
+#[test]
+#[ignore = "needs the real three-piece Syzygy tables in tests/fixtures/syzygy"]
+fn syzygy_three_piece_tables() {
+    // Probes the real KQvK, KRvK, KBvK, KNvK and KPvK Syzygy tables, which are not yet part of the repository: see tests/fixtures/syzygy/README.md.
+    use super::{
+        tablebase::{
+            syzygy::{self, SyzygyTablebase},
+            Endgame, Tablebase, Wdl,
+        },
+        Piece, Position,
+    };
+    let dir = std::env::var_os("RSCHESS_SYZYGY_DIR").map_or_else(|| std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/syzygy"), Into::into);
+    let syzygy = SyzygyTablebase::open(dir).unwrap();
+    assert_eq!(syzygy.max_pieces(), 3);
+    let board = |fen: &str| Board::from_fen(Fen::try_from(fen).unwrap());
+    let probe = |fen: &str| (syzygy.probe_wdl(board(fen).position()).unwrap(), syzygy.probe_dtz(board(fen).position()).unwrap());
+    assert_eq!(probe("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), (syzygy::Wdl::Win, 1));
+    assert_eq!(probe("8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), (syzygy::Wdl::Win, 1));
+    assert_eq!(probe("8/8/8/8/8/8/6k1/K6R b - - 0 1"), (syzygy::Wdl::Draw, 0));
+    assert_eq!(probe("4k3/8/8/4K3/4P3/8/8/8 b - - 0 1"), (syzygy::Wdl::Draw, 0));
+    assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), (syzygy::Wdl::Draw, 0));
+    assert_eq!(probe("8/8/8/4k3/8/8/8/KB6 w - - 0 1"), (syzygy::Wdl::Draw, 0));
+    assert_eq!(probe("8/8/8/4k3/8/8/8/KN6 b - - 0 1"), (syzygy::Wdl::Draw, 0));
+    assert!(matches!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), (syzygy::Wdl::Loss, dtz) if dtz < 0));
+    assert!(matches!(probe("K7/8/8/8/8/8/8/k6r w - - 0 1"), (syzygy::Wdl::Loss, dtz) if dtz < 0));
+    // The other positions are compared with the tables generated by retrograde analysis.
+    let mut tablebase = Tablebase::new();
+    tablebase.generate(&Endgame::try_from("KPvK").unwrap());
+    for piece in [PieceType::Q, PieceType::R, PieceType::B, PieceType::N, PieceType::P] {
+        for color in [Color::White, Color::Black] {
+            for (wk, bk, sq) in (0..64 * 64 * 64usize).step_by(5).map(|i| (i / 4096, i / 64 % 64, i % 64)) {
+                if wk == bk || wk == sq || bk == sq || (wk % 8).abs_diff(bk % 8) <= 1 && (wk / 8).abs_diff(bk / 8) <= 1 || piece == PieceType::P && !(8..56).contains(&sq) {
+                    continue;
+                }
+                let mut content = [None; 64];
+                content[wk] = Some(Piece(PieceType::K, Color::White));
+                content[bk] = Some(Piece(PieceType::K, Color::Black));
+                content[sq] = Some(Piece(piece, color));
+                for side in [Color::White, Color::Black] {
+                    if helpers::king_capture_pseudolegal(&content, side) {
+                        continue;
+                    }
+                    let position = Position {
+                        content,
+                        side,
+                        castling_rights: [None; 4],
+                        ep_target: None,
+                    };
+                    let expected = tablebase.probe(&position).unwrap();
+                    let wdl = syzygy.probe_wdl(&position).unwrap();
+                    let dtz = syzygy.probe_dtz(&position).unwrap();
+                    let fen = position.to_fen();
+                    match expected.wdl {
+                        Wdl::Win => assert!(matches!(wdl, syzygy::Wdl::Win | syzygy::Wdl::CursedWin) && dtz > 0, "{fen}"),
+                        Wdl::Draw => assert!(wdl == syzygy::Wdl::Draw && dtz == 0, "{fen}"),
+                        Wdl::Loss => assert!(matches!(wdl, syzygy::Wdl::Loss | syzygy::Wdl::BlessedLoss) && dtz < 0, "{fen}"),
+                    }
+                    if piece != PieceType::P && expected.wdl != Wdl::Draw {
+                        assert!((0..=1).contains(&(dtz.unsigned_abs() - expected.dtm.unwrap())), "{fen}: DTZ {dtz}, DTM {:?}", expected.dtm);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn unmoves() {
+    use super::Position;
+    let position = |fen: &str| Fen::try_from(fen).unwrap().position().clone();
+    let ucis = |position: &Position| {
+        let mut ucis: Vec<_> = position.gen_unmoves().into_iter().map(|u| (u.move_.to_uci(), u.uncaptured.map(char::from))).collect();
+        ucis.sort();
+        ucis
+    };
+    // Every retracted move is legal in its position and leads back to the position.
+    for fen in [
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1",
+        "3Q3k/8/8/8/8/8/8/4K3 b - - 0 1",
+    ] {
+        let current = position(fen);
+        let unmoves = current.gen_unmoves();
+        assert!(!unmoves.is_empty());
+        for unmove in unmoves {
+            assert_eq!(unmove.position.with_move_made(unmove.move_).unwrap().content, current.content);
+        }
+    }
+    assert_eq!(ucis(&position("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1")), [("e2e4".to_owned(), None)]);
+    let pawn = ucis(&position("4k3/8/3P4/8/8/8/8/7K b - - 0 1"));
+    assert!(pawn.contains(&("d5d6".to_owned(), None)));
+    for uncaptured in ['Q', 'R', 'B', 'N', 'P'] {
+        assert!(pawn.contains(&("c5d6".to_owned(), Some(uncaptured))));
+    }
+    let en_passant: Vec<_> = position("4k3/8/3P4/8/8/8/8/7K b - - 0 1")
+        .gen_unmoves()
+        .into_iter()
+        .filter(|u| u.move_.2 == Some(SpecialMoveType::EnPassant))
+        .collect();
+    assert_eq!(en_passant.len(), 2);
+    assert!(en_passant
+        .iter()
+        .all(|u| u.position.ep_target == Some(43) && u.position.content[35] == Some(Piece(PieceType::P, Color::Black))));
+    let promotions = ucis(&position("3Q3k/8/8/8/8/8/8/4K3 b - - 0 1"));
+    assert!(promotions.contains(&("d7d8q".to_owned(), None)) && promotions.contains(&("c7d8q".to_owned(), Some('R'))));
+    assert!(!promotions.contains(&("c7d8q".to_owned(), Some('P'))));
+    let castling = position("4k3/8/8/8/8/8/8/5RK1 b - - 0 1").gen_unmoves();
+    let castling = castling.iter().find(|u| u.move_.2 == Some(SpecialMoveType::CastlingKingside)).unwrap();
+    assert_eq!(castling.position.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K -");
+    // The king and rooks cannot have moved while White can castle, and the knight cannot have uncaptured a pawn on the last rank.
+    assert!(position("4k3/8/8/8/8/8/8/R3K2R b KQ - 0 1").gen_unmoves().is_empty());
+    assert_eq!(position("k7/8/8/8/8/8/8/K6N b - - 0 1").gen_unmoves().len(), 25);
+    // Black cannot be left in check by White's previous position.
+    assert!(ucis(&position("k7/8/8/8/8/8/8/KR6 b - - 0 1")).iter().all(|(uci, _)| !uci.starts_with("b8")));
+}
+
+#[cfg(feature = "problem")]
+#[test]
+fn mate_solver() {
+    let solve = |fen: &str, n| Fen::try_from(fen).unwrap().position().solve_mate(n);
+    let ucis = |moves: &[Move]| moves.iter().map(|m| m.to_uci()).collect::<Vec<_>>();
+    // Morphy's mate in 2: 1. Ra6! bxa6 2. b7#, and any move of the bishop is met by 2. Rxa7#.
+    let morphy = solve("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", 2);
+    assert!(morphy.is_sound() && morphy.duals().is_empty());
+    let key = &morphy.keys[0];
+    assert_eq!(key.move_.to_uci(), "a1a6");
+    assert_eq!(key.defences.len(), 7);
+    for defence in &key.defences {
+        let expected = if defence.move_.to_uci() == "b7a6" { "b6b7" } else { "a6a7" };
+        assert_eq!(defence.continuations.len(), 1);
+        assert_eq!(defence.continuations[0].move_.to_uci(), expected);
+        assert!(defence.continuations[0].is_mate());
+    }
+    assert!(!solve("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", 1).is_solvable());
+    assert!(!solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 0).is_solvable());
+    // Either rook mates on the back rank.
+    let cooked = solve("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1", 1);
+    assert!(cooked.is_cooked());
+    assert_eq!(cooked.keys.iter().map(|k| k.move_.to_uci()).collect::<Vec<_>>(), ["a1a8", "b1b8"]);
+    // A short mate counts as a solution of a longer stipulation.
+    assert_eq!(solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2).keys.len(), 1);
+    let duals = solve("k7/8/2K5/8/8/8/8/1R4Q1 w - - 0 1", 2).duals();
+    assert!(duals.iter().any(|d| ucis(&d.line) == ["g1g3", "a8a7"] && ucis(&d.continuations) == ["b1a1", "g3a3"]));
+}
+
+#[cfg(feature = "problem")]
+#[test]
+fn helpmate_and_selfmate_solvers() {
+    let position = |fen: &str| Fen::try_from(fen).unwrap().position().clone();
+    let ucis = |moves: &[Move]| moves.iter().map(|m| m.to_uci()).collect::<Vec<_>>();
+    // 1. Kh7 Kf7 2. Kh8 Nf6#, a discovered mate by the rook.
+    let helpmate = position("2R1N1k1/3N4/4K3/8/8/8/8/8 b - - 0 1").solve_helpmate(2);
+    assert!(helpmate.is_sound(1) && !helpmate.is_cooked(1));
+    assert_eq!(helpmate.lines().iter().map(|l| ucis(l)).collect::<Vec<_>>(), [["g8h7", "e6f7", "h7h8", "e8f6"]]);
+    assert!(!position("2R1N1k1/3N4/4K3/8/8/8/8/8 b - - 0 1").solve_helpmate(1).is_solvable());
+    // Either rook mates after 1. Kb8.
+    let cooked = position("k7/8/1K6/8/8/8/8/3R1R2 b - - 0 1").solve_helpmate(1);
+    assert!(cooked.is_cooked(1) && cooked.is_sound(2));
+    assert_eq!(cooked.first_moves.len(), 1);
+    // 1. Bb2! and either pawn must capture with mate.
+    let selfmate = position("7k/7P/4N1P1/8/8/p1p5/P1P5/KNB5 w - - 0 1").solve_selfmate(1);
+    assert!(selfmate.is_sound() && selfmate.duals().is_empty());
+    let key = &selfmate.keys[0];
+    assert_eq!(key.move_.to_uci(), "c1b2");
+    assert_eq!(key.defences.iter().map(|d| d.move_.to_uci()).collect::<Vec<_>>(), ["a3b2", "c3b2"]);
+    assert!(key.defences.iter().all(|d| d.is_mate()));
+    // 1. Rd8+ Qxd8#, as the king cannot escape.
+    let selfmate = position("K6k/R7/8/n7/3R4/8/8/3q4 w - - 0 1").solve_selfmate(2);
+    assert!(selfmate.is_sound());
+    assert_eq!(selfmate.keys[0].move_.to_uci(), "d4d8");
+    assert!(!position("K6k/R7/8/n7/3R4/8/8/3q4 w - - 0 1").solve_selfmate(0).is_solvable());
+}
+
+#[cfg(feature = "problem")]
+#[test]
+fn problem_twins() {
+    use super::problem::{Stipulation, Twin};
+    let twin = |label: &str, fen: &str| Twin {
+        label: label.to_owned(),
+        position: Fen::try_from(fen).unwrap().position().clone(),
+    };
+    // b) adds a second rook, which also mates, and c) gives the king a flight square.
+    let twins = [twin("b)", "6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1"), twin("c)", "6k1/5pp1/7p/8/8/8/8/R5K1 w - - 0 1")];
+    let position = Fen::try_from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap().position().clone();
+    let solutions = Stipulation::Mate(1).solve_twins(&position, &twins);
+    assert_eq!(solutions.iter().map(|s| s.label.as_str()).collect::<Vec<_>>(), ["a)", "b)", "c)"]);
+    assert!(solutions[0].solution.is_sound());
+    assert!(solutions[1].solution.is_cooked());
+    assert!(!solutions[2].solution.is_solvable() && !solutions[2].solution.is_cooked());
+    // Either rook mates after 1. Kb8, which is sound with two intended solutions, and b) leaves only one rook.
+    let position = Fen::try_from("k7/8/1K6/8/8/8/8/3R1R2 b - - 0 1").unwrap().position().clone();
+    let solutions = Stipulation::Helpmate(1, 2).solve_twins(&position, &[twin("b)", "k7/8/1K6/8/8/8/8/3R4 b - - 0 1")]);
+    assert!(solutions[0].solution.is_sound());
+    assert!(solutions[1].solution.is_solvable() && !solutions[1].solution.is_sound() && !solutions[1].solution.is_cooked());
+}
+
+#[cfg(feature = "puzzle")]
+#[test]
+fn puzzle_validation() {
+    use super::{
+        errors::InvalidPuzzleError,
+        puzzle::{EvaluationJudge, MateJudge, Puzzle},
+    };
+    let mate = Puzzle::new("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", "a1a6 b7a6 b6b7").unwrap();
+    assert_eq!((mate.solver(), mate.solver_moves()), (Color::White, 2));
+    let validation = mate.validate(&MateJudge);
+    assert_eq!(validation.steps.len(), 2);
+    assert!(validation.is_unique());
+    // Either rook mates on the back rank.
+    let validation = Puzzle::new("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1", "a1a8").unwrap().validate(&MateJudge);
+    assert!(validation.is_valid() && !validation.is_unique());
+    assert_eq!(validation.ambiguous_steps()[0].alternatives.iter().map(|m| m.to_uci()).collect::<Vec<_>>(), ["b1b8"]);
+    assert!(!Puzzle::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a7").unwrap().validate(&MateJudge).is_valid());
+    // Only the rook wins the queen.
+    let judge = EvaluationJudge::default();
+    assert!(Puzzle::new("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", "d1d5 e8e7").unwrap().validate(&judge).is_unique());
+    assert!(!Puzzle::new("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", "e1e2").unwrap().validate(&judge).is_valid());
+    assert!(matches!(Puzzle::new("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", "d1d6"), Err(InvalidPuzzleError::InvalidMove(_))));
+    assert!(matches!(Puzzle::new("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", ""), Err(InvalidPuzzleError::EmptySolution)));
+}
+
+#[cfg(feature = "puzzle")]
+#[test]
+fn lichess_puzzles() {
+    use super::{
+        errors::InvalidPuzzleError,
+        puzzle::{Attempt, LichessPuzzle, LichessReader, PuzzleSession},
+    };
+    let csv = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00sHx,4k3/8/8/8/3q4/8/8/3RK3 b - - 0 1,d4d5 d1d5 e8e7 e1e2,1520,75,91,1204,crushing hangingPiece,https://lichess.org/787zsVup/black#48,
+
+00sJ9,6k1/5ppp/8/8/8/8/5PPP/RR4K1 b - - 0 1,g8h8 a1a8,642,80,-4,517,mate mateIn1 backRankMate oneMove,https://lichess.org/Fdwm3Mxn#51,Sicilian_Defense Sicilian_Defense_Najdorf_Variation
+";
+    let puzzles: Vec<_> = LichessReader::new(csv.as_bytes()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(puzzles.len(), 2);
+    let hanging = &puzzles[0];
+    assert_eq!(
+        (hanging.id.as_str(), hanging.rating, hanging.rating_deviation, hanging.popularity, hanging.plays),
+        ("00sHx", 1520, 75, 91, 1204)
+    );
+    assert_eq!(hanging.themes, ["crushing", "hangingPiece"]);
+    assert!(hanging.opening_tags.is_empty());
+    assert_eq!(puzzles[1].opening_tags, ["Sicilian_Defense", "Sicilian_Defense_Najdorf_Variation"]);
+    // The setup move has been played, so the solver is to move.
+    assert_eq!(hanging.puzzle.board.move_history().len(), 1);
+    assert_eq!(hanging.puzzle.solver(), Color::White);
+    assert_eq!(hanging.puzzle.solution.len(), 3);
+    let mut session = PuzzleSession::new(hanging.puzzle.clone());
+    assert_eq!(session.try_uci("e1e2").unwrap(), Attempt::Incorrect);
+    assert_eq!(session.mistakes(), 1);
+    assert_eq!(session.hint().unwrap().to_uci(), "d1d5");
+    assert!(matches!(session.try_san("Rxd5").unwrap(), Attempt::Correct(reply) if reply.to_uci() == "e8e7"));
+    assert_eq!(session.moves_solved(), 1);
+    assert!(session.try_uci("e1e3").is_err());
+    assert_eq!(session.try_uci("e1e2").unwrap(), Attempt::Solved);
+    assert!(session.is_complete() && session.hint().is_none());
+    assert_eq!(session.board().move_history().len(), 4);
+    // Any mate completes a puzzle, not only the one of the solution.
+    let mut session = PuzzleSession::new(puzzles[1].puzzle.clone());
+    assert_eq!(session.try_uci("b1b8").unwrap(), Attempt::Solved);
+    assert_eq!(session.mistakes(), 0);
+    assert!(matches!(
+        LichessPuzzle::try_from("00sHx,4k3/8/8/8/3q4/8/8/3RK3 b - - 0 1,d4d5 d1d5,high"),
+        Err(InvalidPuzzleError::InvalidField("Rating", _))
+    ));
+    assert!(matches!(
+        LichessPuzzle::try_from("00sHx,4k3/8/8/8/3q4/8/8/3RK3 b - - 0 1"),
+        Err(InvalidPuzzleError::MissingField("Moves"))
+    ));
+    assert!(matches!(
+        LichessPuzzle::try_from("00sHx,4k3/8/8/8/3q4/8/8/3RK3 b - - 0 1,d4d5,1500"),
+        Err(InvalidPuzzleError::EmptySolution)
+    ));
+}
@@ -0,0 +1,865 @@
+//! Handles communication with chess engines over the Universal Chess Interface (UCI), both as a GUI and as an engine.
+
+#[cfg(feature = "cecp")]
+use super::cecp;
+use super::{helpers, Board, Fen, Move, Position, Score, UciError};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::OsStr,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long an engine is given to exit after `quit` before it is killed
+const QUIT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// The keywords of the UCI `info` command, which end a principal variation
+const INFO_KEYWORDS: [&str; 17] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "pv",
+    "multipv",
+    "score",
+    "currmove",
+    "currmovenumber",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "sbhits",
+    "cpuload",
+    "string",
+    "refutation",
+    "currline",
+];
+
+/// Represents the limits of a search, as given in the UCI `go` command. Limits which are `None` are not sent.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// The maximum depth of the search, in plies
+    pub depth: Option<u32>,
+    /// The maximum number of nodes to search
+    pub nodes: Option<u64>,
+    /// The exact time to search for
+    pub movetime: Option<Duration>,
+    /// The time left on White's clock
+    pub wtime: Option<Duration>,
+    /// The time left on Black's clock
+    pub btime: Option<Duration>,
+    /// White's increment per move
+    pub winc: Option<Duration>,
+    /// Black's increment per move
+    pub binc: Option<Duration>,
+    /// The number of moves until the next time control
+    pub movestogo: Option<u32>,
+    /// Whether the search should go on until it is stopped
+    pub infinite: bool,
+    /// Whether the engine should search in pondering mode, on the assumption that its expected reply has been played
+    pub ponder: bool,
+}
+
+impl SearchLimits {
+    /// Constructs `SearchLimits` that only limit the depth of the search.
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs `SearchLimits` that only limit the number of nodes searched.
+    pub fn nodes(nodes: u64) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs `SearchLimits` that make the engine search for exactly the given time.
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    /// Parses the arguments of a `go` command, ignoring any that are unknown or malformed.
+    fn parse(args: &str) -> Self {
+        let mut limits = Self::default();
+        let mut tokens = args.split_whitespace();
+        while let Some(token) = tokens.next() {
+            let mut millis = || tokens.next().and_then(|t| t.parse().ok()).map(Duration::from_millis);
+            match token {
+                "wtime" => limits.wtime = millis(),
+                "btime" => limits.btime = millis(),
+                "winc" => limits.winc = millis(),
+                "binc" => limits.binc = millis(),
+                "movetime" => limits.movetime = millis(),
+                "movestogo" => limits.movestogo = tokens.next().and_then(|t| t.parse().ok()),
+                "depth" => limits.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => limits.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "infinite" => limits.infinite = true,
+                "ponder" => limits.ponder = true,
+                _ => (),
+            }
+        }
+        limits
+    }
+}
+
+impl fmt::Display for SearchLimits {
+    /// Represents the limits as a UCI `go` command.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "go")?;
+        if self.ponder {
+            write!(f, " ponder")?;
+        }
+        for (name, time) in [("wtime", self.wtime), ("btime", self.btime), ("winc", self.winc), ("binc", self.binc)] {
+            if let Some(time) = time {
+                write!(f, " {name} {}", time.as_millis())?;
+            }
+        }
+        if let Some(movestogo) = self.movestogo {
+            write!(f, " movestogo {movestogo}")?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " depth {depth}")?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {nodes}")?;
+        }
+        if let Some(movetime) = self.movetime {
+            write!(f, " movetime {}", movetime.as_millis())?;
+        }
+        if self.infinite {
+            write!(f, " infinite")?;
+        }
+        Ok(())
+    }
+}
+
+/// Represents the information contained in a UCI `info` line. Fields which the engine did not send are `None` (or empty).
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+pub struct SearchInfo {
+    /// The depth of the search, in plies
+    pub depth: Option<u32>,
+    /// The selective depth of the search, in plies
+    pub seldepth: Option<u32>,
+    /// The index of the line in multi-PV mode, starting at 1
+    pub multipv: Option<u32>,
+    /// The score from the perspective of the side to move
+    pub score: Option<Score>,
+    /// The number of nodes searched
+    pub nodes: Option<u64>,
+    /// The number of nodes searched per second
+    pub nps: Option<u64>,
+    /// The time searched
+    pub time: Option<Duration>,
+    /// The principal variation, which ends early if the engine sent a move that is illegal
+    pub pv: Vec<Move>,
+    /// Any text sent with `info string`
+    pub string: Option<String>,
+}
+
+impl SearchInfo {
+    /// Parses the arguments of an `info` line, interpreting the principal variation in the given position.
+    fn parse(args: &str, position: &Position) -> Self {
+        let mut info = Self::default();
+        let mut tokens = args.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|t| t.parse().ok()),
+                "multipv" => info.multipv = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|t| t.parse().ok()),
+                "time" => info.time = tokens.next().and_then(|t| t.parse().ok()).map(Duration::from_millis),
+                "score" => {
+                    info.score = match (tokens.next(), tokens.next().and_then(|t| t.parse().ok())) {
+                        (Some("cp"), Some(cp)) => Some(Score::Centipawns(cp)),
+                        (Some("mate"), Some(moves)) => Some(Score::Mate(moves)),
+                        _ => None,
+                    }
+                }
+                "pv" => {
+                    let mut position = position.clone();
+                    while let Some(text) = tokens.next_if(|t| !INFO_KEYWORDS.contains(t)) {
+                        match parse_move(text, &position).and_then(|m| position.with_move_made(m).ok().map(|p| (m, p))) {
+                            Some((move_, next)) => {
+                                info.pv.push(move_);
+                                position = next;
+                            }
+                            _ => break,
+                        }
+                    }
+                    while tokens.next_if(|t| !INFO_KEYWORDS.contains(t)).is_some() {}
+                }
+                "string" => {
+                    info.string = Some(tokens.collect::<Vec<_>>().join(" "));
+                    break;
+                }
+                _ => (),
+            }
+        }
+        info
+    }
+
+    /// Writes the information as an `info` line, with the principal variation written from the given position.
+    fn to_uci(&self, position: &Position, chess960: bool) -> String {
+        let mut line = "info".to_owned();
+        for (name, value) in [("depth", self.depth), ("seldepth", self.seldepth), ("multipv", self.multipv)] {
+            if let Some(value) = value {
+                line.push_str(&format!(" {name} {value}"));
+            }
+        }
+        match self.score {
+            Some(Score::Centipawns(cp)) => line.push_str(&format!(" score cp {cp}")),
+            Some(Score::Mate(moves)) => line.push_str(&format!(" score mate {moves}")),
+            None => (),
+        }
+        for (name, value) in [("nodes", self.nodes), ("nps", self.nps), ("time", self.time.map(|t| t.as_millis() as u64))] {
+            if let Some(value) = value {
+                line.push_str(&format!(" {name} {value}"));
+            }
+        }
+        if !self.pv.is_empty() {
+            line.push_str(" pv");
+            let mut position = position.clone();
+            for &move_ in &self.pv {
+                line.push(' ');
+                line.push_str(&format_move(move_, &position, chess960));
+                match position.with_move_made(move_) {
+                    Ok(next) => position = next,
+                    _ => break,
+                }
+            }
+        }
+        if let Some(string) = &self.string {
+            line.push_str(&format!(" string {string}"));
+        }
+        line
+    }
+}
+
+/// Represents the outcome of a search.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SearchResult {
+    /// The best move found (`None` if the engine sent `bestmove (none)` or `bestmove 0000` because there are no legal moves)
+    pub best_move: Option<Move>,
+    /// The move that the engine expects to be played in reply, if it sent one
+    pub ponder: Option<Move>,
+    /// The information sent by the engine during the search, in order
+    pub info: Vec<SearchInfo>,
+}
+
+impl SearchResult {
+    /// Returns the last score sent by the engine, from the perspective of the side to move.
+    pub fn score(&self) -> Option<Score> {
+        self.info.iter().rev().find_map(|i| i.score)
+    }
+
+    /// Returns the last principal variation sent by the engine.
+    pub fn pv(&self) -> &[Move] {
+        self.info.iter().rev().find(|i| !i.pv.is_empty()).map_or(&[], |i| &i.pv)
+    }
+}
+
+/// Represents the type of an option declared by an engine, along with its default value and constraints.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub enum OptionType {
+    Check { default: bool },
+    Spin { default: i64, min: i64, max: i64 },
+    Combo { default: String, choices: Vec<String> },
+    Button,
+    String { default: String },
+}
+
+/// Represents an option declared by an engine with the UCI `option` command.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct EngineOption {
+    pub name: String,
+    pub option_type: OptionType,
+}
+
+impl EngineOption {
+    /// Parses the arguments of an `option` command, returning `None` if the declaration is malformed.
+    fn parse(args: &str) -> Option<Self> {
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+        for token in args.split_whitespace() {
+            match fields.last_mut() {
+                Some((keyword, values)) if !["name", "type", "default", "min", "max", "var"].contains(&token) || (*keyword == "name" && values.is_empty()) => values.push(token),
+                _ => fields.push((token, Vec::new())),
+            }
+        }
+        let field = |name| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| v.join(" "));
+        let name = field("name").filter(|n| !n.is_empty())?;
+        let default = field("default").map(|d| if d == "<empty>" { String::new() } else { d });
+        let option_type = match field("type")?.as_str() {
+            "check" => OptionType::Check { default: default? == "true" },
+            "spin" => OptionType::Spin {
+                default: default?.parse().ok()?,
+                min: field("min")?.parse().ok()?,
+                max: field("max")?.parse().ok()?,
+            },
+            "combo" => OptionType::Combo {
+                default: default?,
+                choices: fields.iter().filter(|(k, _)| *k == "var").map(|(_, v)| v.join(" ")).collect(),
+            },
+            "button" => OptionType::Button,
+            "string" => OptionType::String { default: default? },
+            _ => return None,
+        };
+        Some(Self { name, option_type })
+    }
+}
+
+impl fmt::Display for EngineOption {
+    /// Represents the option as a UCI `option` command.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.option_type {
+            OptionType::Check { default } => write!(f, "check default {default}"),
+            OptionType::Spin { default, min, max } => write!(f, "spin default {default} min {min} max {max}"),
+            OptionType::Combo { default, choices } => {
+                write!(f, "combo default {default}")?;
+                choices.iter().try_for_each(|c| write!(f, " var {c}"))
+            }
+            OptionType::Button => write!(f, "button"),
+            OptionType::String { default } if default.is_empty() => write!(f, "string default <empty>"),
+            OptionType::String { default } => write!(f, "string default {default}"),
+        }
+    }
+}
+
+/// Drives an external chess engine which speaks UCI, running as a child process.
+/// The engine is asked to quit when the `EngineClient` is dropped.
+#[derive(Debug)]
+pub struct EngineClient {
+    process: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<EngineOption>,
+    /// Whether castling moves are sent as the king capturing its own rook (set through the `UCI_Chess960` option)
+    chess960: bool,
+    /// The position most recently sent to the engine, in which its moves are interpreted
+    position: Position,
+}
+
+impl EngineClient {
+    /// Starts the engine at the given path and performs the UCI handshake.
+    pub fn spawn<S: AsRef<OsStr>>(program: S) -> Result<Self, UciError> {
+        Self::from_command(Command::new(program))
+    }
+
+    /// Starts the engine with the given command (which allows passing arguments, setting the working directory, etc.)
+    /// and performs the UCI handshake. The standard input and output of the command are replaced with pipes.
+    pub fn from_command(mut command: Command) -> Result<Self, UciError> {
+        let mut process = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let (stdin, stdout) = (process.stdin.take().unwrap(), BufReader::new(process.stdout.take().unwrap()));
+        let mut client = Self {
+            process,
+            stdin,
+            stdout,
+            name: None,
+            author: None,
+            options: Vec::new(),
+            chess960: false,
+            position: Board::default().position().clone(),
+        };
+        client.send("uci")?;
+        loop {
+            let line = client.read_line()?;
+            match line.split_once(' ').unwrap_or((&line, "")) {
+                ("uciok", _) => break,
+                ("id", args) => match args.split_once(' ') {
+                    Some(("name", name)) => client.name = Some(name.trim().to_owned()),
+                    Some(("author", author)) => client.author = Some(author.trim().to_owned()),
+                    _ => (),
+                },
+                ("option", args) => client.options.extend(EngineOption::parse(args)),
+                _ => (),
+            }
+        }
+        Ok(client)
+    }
+
+    /// Returns the name of the engine, if it sent one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the author of the engine, if it sent one.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Returns the options declared by the engine.
+    pub fn options(&self) -> &[EngineOption] {
+        &self.options
+    }
+
+    /// Sends `isready` and waits for the engine to reply with `readyok`.
+    pub fn is_ready(&mut self) -> Result<(), UciError> {
+        self.send("isready")?;
+        while self.read_line()? != "readyok" {}
+        Ok(())
+    }
+
+    /// Sets the value of an option (`None` for buttons). Setting `UCI_Chess960` also changes how castling moves are written.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UciError> {
+        if name.eq_ignore_ascii_case("UCI_Chess960") {
+            self.chess960 = value.is_some_and(|v| v.eq_ignore_ascii_case("true"));
+        }
+        match value {
+            Some(value) => self.send(&format!("setoption name {name} value {value}")),
+            _ => self.send(&format!("setoption name {name}")),
+        }
+    }
+
+    /// Tells the engine that the next search will be from a different game, and waits for it to be ready.
+    pub fn new_game(&mut self) -> Result<(), UciError> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    /// Sends the current position of the board to the engine, as its initial position followed by the moves played.
+    pub fn set_position(&mut self, board: &Board) -> Result<(), UciError> {
+        let mut command = if board.initial_fen() == Board::default().initial_fen() {
+            "position startpos".to_owned()
+        } else {
+            format!("position fen {}", board.initial_fen())
+        };
+        if !board.move_history().is_empty() {
+            command.push_str(" moves");
+            let mut position = Board::from_fen(board.initial_fen().clone()).position().clone();
+            for &move_ in board.move_history() {
+                command.push(' ');
+                command.push_str(&format_move(move_, &position, self.chess960));
+                position = position.with_move_made(move_).unwrap();
+            }
+        }
+        self.send(&command)?;
+        self.position = board.position().clone();
+        Ok(())
+    }
+
+    /// Starts a search of the position most recently sent, and waits for the engine to send its best move.
+    /// If the search is infinite, use [`EngineClient::go_with`] to stop it.
+    pub fn go(&mut self, limits: &SearchLimits) -> Result<SearchResult, UciError> {
+        self.go_with(limits, |_| true)
+    }
+
+    /// Starts a search of the position most recently sent, calling `on_info` with each `info` line as it arrives,
+    /// and waits for the engine to send its best move. When `on_info` returns `false`, the search is stopped.
+    pub fn go_with<F: FnMut(&SearchInfo) -> bool>(&mut self, limits: &SearchLimits, mut on_info: F) -> Result<SearchResult, UciError> {
+        self.send(&limits.to_string())?;
+        let mut info = Vec::new();
+        let mut stopped = false;
+        loop {
+            let line = self.read_line()?;
+            match line.split_once(' ').unwrap_or((&line, "")) {
+                ("info", args) => {
+                    let parsed = SearchInfo::parse(args, &self.position);
+                    if !on_info(&parsed) && !stopped {
+                        self.send("stop")?;
+                        stopped = true;
+                    }
+                    info.push(parsed);
+                }
+                ("bestmove", args) => {
+                    let mut args = args.split_whitespace();
+                    let best_move = match args.next() {
+                        Some("(none)" | "0000") | None => None,
+                        Some(text) => Some(parse_move(text, &self.position).ok_or_else(|| UciError::InvalidMove(text.to_owned()))?),
+                    };
+                    let ponder = match (best_move, args.next(), args.next()) {
+                        (Some(best_move), Some("ponder"), Some(text)) => self.position.with_move_made(best_move).ok().and_then(|p| parse_move(text, &p)),
+                        _ => None,
+                    };
+                    return Ok(SearchResult { best_move, ponder, info });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Asks the engine to quit and waits for its process to exit.
+    pub fn quit(mut self) -> Result<(), UciError> {
+        self.send("quit")?;
+        self.process.wait()?;
+        Ok(())
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UciError> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Reads a line sent by the engine, without its line terminator.
+    fn read_line(&mut self) -> Result<String, UciError> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(UciError::Disconnected);
+        }
+        Ok(line.trim().to_owned())
+    }
+}
+
+impl Drop for EngineClient {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        reap(&mut self.process);
+    }
+}
+
+/// Waits up to [`QUIT_GRACE_PERIOD`] for an engine process to exit, killing it if it is still running afterwards.
+pub(crate) fn reap(process: &mut Child) {
+    let deadline = Instant::now() + QUIT_GRACE_PERIOD;
+    while Instant::now() < deadline {
+        match process.try_wait() {
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            _ => return,
+        }
+    }
+    let _ = process.kill();
+    let _ = process.wait();
+}
+
+/// Represents the move chosen by an [`Engine`] at the end of a search.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct BestMove {
+    /// The move to play (`None` if there are no legal moves)
+    pub best_move: Option<Move>,
+    /// The reply which the engine expects, on which it may ponder
+    pub ponder: Option<Move>,
+}
+
+impl From<Move> for BestMove {
+    fn from(move_: Move) -> Self {
+        Self { best_move: Some(move_), ponder: None }
+    }
+}
+
+/// Represents a chess engine that can be run over UCI with [`run`], which parses the commands of the GUI and writes the replies.
+pub trait Engine {
+    /// Returns the name of the engine, sent with `id name`.
+    fn name(&self) -> String;
+
+    /// Returns the author of the engine, sent with `id author`.
+    fn author(&self) -> String;
+
+    /// Returns the options supported by the engine, which are declared to the GUI during the handshake.
+    fn options(&self) -> Vec<EngineOption> {
+        Vec::new()
+    }
+
+    /// Sets the value of an option (`None` for buttons). Options that were not declared may be ignored.
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) {}
+
+    /// Prepares for a search from a different game, e.g. by clearing hash tables.
+    fn new_game(&mut self) {}
+
+    /// Searches the current position of the board within the given limits. Long searches should regularly call
+    /// [`SearchControl::should_stop`], and can report their progress with [`SearchControl::report`].
+    fn search(&mut self, board: &Board, limits: &SearchLimits, control: &SearchControl) -> BestMove;
+}
+
+/// Lets an [`Engine`] communicate with the GUI while it searches.
+pub struct SearchControl<'a> {
+    commands: &'a Receiver<String>,
+    /// Commands received during the search that are handled after it
+    pending: RefCell<&'a mut VecDeque<String>>,
+    output: RefCell<&'a mut dyn Write>,
+    position: &'a Position,
+    protocol: Protocol,
+    classify: fn(&str) -> CommandEffect,
+    start: Instant,
+    stopped: Cell<bool>,
+    pondering: Cell<bool>,
+    /// Whether a command received during the search makes its result irrelevant
+    interrupted: Cell<bool>,
+}
+
+/// Represents the protocol over which an [`Engine`] is run, which decides how commands received during a search are handled and how information is reported.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Protocol {
+    Uci {
+        chess960: bool,
+    },
+    #[cfg(feature = "cecp")]
+    Cecp {
+        post: bool,
+    },
+}
+
+/// Describes how a command received during a search affects it, as decided by the classifier of the protocol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum CommandEffect {
+    /// Stops the search, whose result is still sent
+    Stop,
+    /// Ends pondering, after which time limits apply
+    PonderHit,
+    /// Is answered immediately with the given line
+    Reply(&'static str),
+    /// Is handled after the search
+    Defer,
+    /// Stops the search and discards its result, and is handled after it
+    Interrupt,
+}
+
+impl<'a> SearchControl<'a> {
+    pub(crate) fn new(
+        commands: &'a Receiver<String>,
+        pending: &'a mut VecDeque<String>,
+        output: &'a mut dyn Write,
+        position: &'a Position,
+        protocol: Protocol,
+        classify: fn(&str) -> CommandEffect,
+        pondering: bool,
+    ) -> Self {
+        Self {
+            commands,
+            pending: RefCell::new(pending),
+            output: RefCell::new(output),
+            position,
+            protocol,
+            classify,
+            start: Instant::now(),
+            stopped: Cell::new(false),
+            pondering: Cell::new(pondering),
+            interrupted: Cell::new(false),
+        }
+    }
+
+    /// Returns `true` if the GUI has asked the engine to stop searching (e.g. with `stop` or `quit`).
+    pub fn should_stop(&self) -> bool {
+        self.poll();
+        self.stopped.get()
+    }
+
+    /// Returns `true` if the engine is pondering, in which case time limits do not apply yet. Pondering ends when the GUI sends `ponderhit` or `stop`.
+    pub fn is_pondering(&self) -> bool {
+        self.poll();
+        self.pondering.get()
+    }
+
+    /// Returns the time elapsed since the search started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Sends information about the search to the GUI, as an `info` line in UCI or as thinking output in CECP (if the GUI has sent `post`).
+    pub fn report(&self, info: &SearchInfo) -> io::Result<()> {
+        match self.protocol {
+            Protocol::Uci { chess960 } => self.write_line(&info.to_uci(self.position, chess960)),
+            #[cfg(feature = "cecp")]
+            Protocol::Cecp { post: true } => self.write_line(&cecp::thinking_output(info, self.position)),
+            #[cfg(feature = "cecp")]
+            Protocol::Cecp { post: false } => Ok(()),
+        }
+    }
+
+    /// Checks whether a command received during the search makes its result irrelevant (e.g. `new` or `force` in CECP).
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.interrupted.get()
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut output = self.output.borrow_mut();
+        writeln!(output, "{line}")?;
+        output.flush()
+    }
+
+    /// Handles the commands which have arrived, without waiting for more. Once the search is stopped, further commands are left for after it.
+    fn poll(&self) {
+        while !self.stopped.get() {
+            match self.commands.try_recv() {
+                Ok(command) => self.handle(command),
+                _ => break,
+            }
+        }
+    }
+
+    /// Waits until the GUI allows the engine to send its best move, which is never before `stop` or `ponderhit` in infinite or pondering mode.
+    pub(crate) fn wait(&self, infinite: bool) {
+        while (infinite && !self.stopped.get()) || self.pondering.get() {
+            match self.commands.recv() {
+                Ok(command) => self.handle(command),
+                _ => break,
+            }
+        }
+    }
+
+    fn handle(&self, command: String) {
+        match (self.classify)(&command) {
+            CommandEffect::Stop => {
+                self.stopped.set(true);
+                self.pondering.set(false);
+            }
+            CommandEffect::PonderHit => self.pondering.set(false),
+            CommandEffect::Reply(reply) => {
+                let _ = self.write_line(reply);
+            }
+            CommandEffect::Defer => self.pending.borrow_mut().push_back(command),
+            CommandEffect::Interrupt => {
+                self.stopped.set(true);
+                self.pondering.set(false);
+                self.interrupted.set(true);
+                self.pending.borrow_mut().push_back(command);
+            }
+        }
+    }
+}
+
+/// Runs an engine over UCI on the standard input and output, until the GUI sends `quit` or closes the input.
+pub fn run<E: Engine>(engine: &mut E) -> io::Result<()> {
+    run_with(engine, BufReader::new(io::stdin()), io::stdout())
+}
+
+/// Runs an engine over UCI, reading commands from `input` and writing replies to `output`, until the GUI sends `quit` or the input ends.
+/// The input is read on a separate thread, so that `stop` and `ponderhit` can arrive while the engine searches.
+pub fn run_with<E: Engine, R: BufRead + Send + 'static, W: Write>(engine: &mut E, input: R, mut output: W) -> io::Result<()> {
+    let commands = spawn_reader(input);
+    let mut pending = VecDeque::new();
+    let mut board = Board::default();
+    let mut chess960 = false;
+    loop {
+        let command = match pending.pop_front() {
+            Some(command) => command,
+            _ => match commands.recv() {
+                Ok(command) => command,
+                _ => return Ok(()),
+            },
+        };
+        let (name, args) = command.split_once(' ').map_or((command.as_str(), ""), |(n, a)| (n, a.trim()));
+        match name {
+            "uci" => {
+                writeln!(output, "id name {}", engine.name())?;
+                writeln!(output, "id author {}", engine.author())?;
+                for option in engine.options() {
+                    writeln!(output, "{option}")?;
+                }
+                writeln!(output, "uciok")?;
+            }
+            "isready" => writeln!(output, "readyok")?,
+            "setoption" => {
+                let args = args.strip_prefix("name").unwrap_or(args).trim();
+                let (name, value) = match args.split_once(" value") {
+                    Some((name, value)) => (name.trim(), Some(value.trim())),
+                    _ => (args, None),
+                };
+                if name.eq_ignore_ascii_case("UCI_Chess960") {
+                    chess960 = value.is_some_and(|v| v.eq_ignore_ascii_case("true"));
+                }
+                engine.set_option(name, value);
+            }
+            "ucinewgame" => {
+                board = Board::default();
+                engine.new_game();
+            }
+            "position" => {
+                if let Err(message) = parse_position(args).map(|b| board = b) {
+                    writeln!(output, "info string {message}")?;
+                }
+            }
+            "go" => {
+                let limits = SearchLimits::parse(args);
+                let control = SearchControl::new(&commands, &mut pending, &mut output, board.position(), Protocol::Uci { chess960 }, classify_command, limits.ponder);
+                let BestMove { best_move, ponder } = engine.search(&board, &limits, &control);
+                control.wait(limits.infinite);
+                if control.is_interrupted() {
+                    continue;
+                }
+                let position = board.position();
+                match best_move.and_then(|m| helpers::as_legal(m, &position.gen_non_illegal_moves())) {
+                    Some(best_move) => {
+                        write!(output, "bestmove {}", format_move(best_move, position, chess960))?;
+                        let next = position.with_move_made(best_move).unwrap();
+                        if let Some(ponder) = ponder.and_then(|m| helpers::as_legal(m, &next.gen_non_illegal_moves())) {
+                            write!(output, " ponder {}", format_move(ponder, &next, chess960))?;
+                        }
+                        writeln!(output)?;
+                    }
+                    _ => writeln!(output, "bestmove 0000")?,
+                }
+            }
+            "quit" => return Ok(()),
+            _ => (),
+        }
+        output.flush()?;
+    }
+}
+
+/// Decides how a UCI command received during a search affects it.
+fn classify_command(command: &str) -> CommandEffect {
+    match command {
+        "stop" => CommandEffect::Stop,
+        "ponderhit" => CommandEffect::PonderHit,
+        "isready" => CommandEffect::Reply("readyok"),
+        "quit" => CommandEffect::Interrupt,
+        _ => CommandEffect::Defer,
+    }
+}
+
+/// Reads the lines of `input` on a separate thread, returning a channel through which the non-empty lines are received (trimmed) until the input ends.
+pub(crate) fn spawn_reader<R: BufRead + Send + 'static>(input: R) -> Receiver<String> {
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            match line {
+                Ok(line) if !line.trim().is_empty() => {
+                    if sender.send(line.trim().to_owned()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                _ => break,
+            }
+        }
+    });
+    commands
+}
+
+/// Parses the arguments of a `position` command into a board, returning a message describing the problem if they are invalid.
+fn parse_position(args: &str) -> Result<Board, String> {
+    let (setup, moves) = match args.split_once("moves") {
+        Some((setup, moves)) => (setup.trim(), moves),
+        _ => (args, ""),
+    };
+    let mut board = match setup.split_once(' ') {
+        _ if setup == "startpos" => Board::default(),
+        Some(("fen", fen)) => Board::from_fen(Fen::try_from(fen.trim()).map_err(|e| e.to_string())?),
+        _ => return Err(format!("invalid position command: 'position {args}'")),
+    };
+    for text in moves.split_whitespace() {
+        match parse_move(text, board.position()) {
+            Some(move_) => board.replay_move(move_).map_err(|e| e.to_string())?,
+            _ => return Err(format!("illegal move in position command: '{text}'")),
+        }
+    }
+    Ok(board)
+}
+
+/// Writes a legal move in UCI, with castling written as the king capturing its own rook if `chess960` is `true`.
+pub(crate) fn format_move(move_: Move, position: &Position, chess960: bool) -> String {
+    match helpers::castling_rook(move_, position) {
+        Some(rook) if chess960 => Move(move_.0, rook, None).to_uci(),
+        _ => move_.to_uci(),
+    }
+}
+
+/// Interprets a move sent by the engine as a legal move in the given position, accepting castling written as the king capturing its own rook.
+pub(crate) fn parse_move(text: &str, position: &Position) -> Option<Move> {
+    let move_ = Move::from_uci(text).ok()?;
+    let legal = position.gen_non_illegal_moves();
+    helpers::as_legal(move_, &legal).or_else(|| legal.into_iter().find(|&m| m.0 == move_.0 && helpers::castling_rook(m, position) == Some(move_.1)))
+}